@@ -0,0 +1,125 @@
+use scrypto::prelude::*;
+
+// A halt of trading by a circuit breaker
+#[derive(ScryptoCategorize, ScryptoEncode, ScryptoDecode, LegacyDescribe, Clone, Debug)]
+pub struct Halt {
+    // Seconds since the unix epoch at which trading halted
+    pub timestamp: i64,
+    // Price the trade which tripped the breaker would have moved to
+    pub price: Decimal,
+    // Price at the start of the window when the halt happened
+    pub window_start_price: Decimal,
+    // Reference price (TWAP or price source) when the halt happened
+    pub reference_price: Decimal,
+    // Seconds since the unix epoch at which a guardian resumed trading, if one did before the cooldown ended
+    pub resumed_at: Option<i64>,
+}
+
+// Circuit breaker state kept by the pools. Trading halts for `cooldown` seconds when a trade would move the
+// price more than `max_move` from the price at the start of the current `window`, or more than
+// `max_reference_deviation` from the reference price. Withdrawals are never halted.
+#[derive(ScryptoCategorize, ScryptoEncode, ScryptoDecode, LegacyDescribe, Clone, Debug)]
+pub struct CircuitBreaker {
+    // Largest relative price move allowed within a window
+    pub max_move: Decimal,
+    // Length of a window in seconds
+    pub window: i64,
+    // Largest relative deviation allowed from the reference price
+    pub max_reference_deviation: Decimal,
+    // How long trading stays halted in seconds, unless a guardian resumes it earlier
+    pub cooldown: i64,
+    // Badge allowed to resume trading
+    pub guardian_badge: ResourceAddress,
+    // Seconds since the unix epoch at which the current window started
+    pub window_start: i64,
+    // Price at the start of the current window, zero when no window is running
+    pub window_start_price: Decimal,
+    // Seconds since the unix epoch until which trading is halted
+    pub halted_until: i64,
+    // Every halt, oldest first
+    pub halts: Vec<Halt>,
+}
+
+impl CircuitBreaker {
+    // Breaker with default limits: 10% within 5 minutes, 20% away from the reference, 1 hour cooldown
+    pub fn new(guardian_badge: ResourceAddress) -> Self {
+        Self {
+            max_move: dec!("0.1"),
+            window: 300,
+            max_reference_deviation: dec!("0.2"),
+            cooldown: 3600,
+            guardian_badge,
+            window_start: 0,
+            window_start_price: Decimal::zero(),
+            halted_until: 0,
+            halts: Vec::new(),
+        }
+    }
+
+    pub fn configure(&mut self, max_move: Decimal, window: i64, max_reference_deviation: Decimal, cooldown: i64,
+        guardian_badge: ResourceAddress) {
+        assert!(max_move > Decimal::zero() && max_reference_deviation > Decimal::zero(), "Invalid price limits");
+        assert!(window > 0 && cooldown >= 0, "Invalid durations");
+        self.max_move = max_move;
+        self.window = window;
+        self.max_reference_deviation = max_reference_deviation;
+        self.cooldown = cooldown;
+        self.guardian_badge = guardian_badge;
+    }
+
+    pub fn is_halted(&self, now: i64) -> bool {
+        now < self.halted_until
+    }
+
+    pub fn assert_open(&self, now: i64) {
+        assert!(!self.is_halted(now), "Trading is halted by the circuit breaker");
+    }
+
+    // Checks a trade moving the price from `price_before` to `price_after`. Returns true and halts trading when
+    // the trade trips the breaker, in which case the caller must not execute it.
+    pub fn trip(&mut self, now: i64, price_before: Decimal, price_after: Decimal, reference_price: Decimal) -> bool {
+        if self.window_start_price.is_zero() || now >= self.window_start + self.window {
+            self.window_start = now;
+            self.window_start_price = price_before;
+        }
+
+        let moved = Self::relative_change(self.window_start_price, price_after) > self.max_move;
+        let deviated = Self::relative_change(reference_price, price_after) > self.max_reference_deviation;
+        if !moved && !deviated {
+            return false;
+        }
+
+        self.halt(now, price_after, reference_price);
+        true
+    }
+
+    // Halts trading for `cooldown` seconds, for pools which check limits of their own on top of the breaker's
+    pub fn halt(&mut self, now: i64, price: Decimal, reference_price: Decimal) {
+        self.halted_until = now + self.cooldown;
+        self.halts.push(Halt {
+            timestamp: now,
+            price,
+            window_start_price: self.window_start_price,
+            reference_price,
+            resumed_at: None,
+        });
+    }
+
+    // Resumes trading after a guardian checked the market, starting a fresh window
+    pub fn resume(&mut self, guardian: Proof, now: i64) {
+        guardian.validate_proof(self.guardian_badge).expect("Invalid guardian badge");
+        assert!(self.is_halted(now), "Trading is not halted");
+        self.halted_until = now;
+        self.window_start_price = Decimal::zero();
+        if let Some(halt) = self.halts.last_mut() {
+            halt.resumed_at = Some(now);
+        }
+    }
+
+    fn relative_change(from: Decimal, to: Decimal) -> Decimal {
+        if from.is_zero() {
+            return Decimal::zero();
+        }
+        if to > from { (to - from) / from } else { (from - to) / from }
+    }
+}
@@ -1,14 +1,92 @@
 use scrypto::radix_engine_interface::time::*;
 use scrypto::prelude::*;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::insurance_fund::InsuranceFundComponentTarget;
+use crate::price_source::PriceSourceComponentTarget;
+use crate::rate_model::{clamp_rate, RateModelComponentTarget, SECONDS_PER_YEAR};
   
   // importing a radiswap method that needs to be used in this blueprint
 external_component! {
     RadiswapComponentTarget {
         // Imported method
         fn swap(&mut self, input_tokens: Bucket) -> Bucket;
+        fn spot_price(&self, base: ResourceAddress) -> Decimal;
+        fn is_halted(&self) -> bool;
     }
 }
 
+// Receipt given to a borrower, the loan itself is tracked in the component state
+#[derive(NonFungibleData)]
+pub struct LoanBadge {
+    // Maturity of the pool the loan was taken from
+    pub maturity: i64,
+}
+
+// Debt and collateral of a single borrow
+#[derive(ScryptoCategorize, ScryptoEncode, ScryptoDecode, LegacyDescribe, Clone, Debug)]
+pub struct Loan {
+    // Amount of token_b owed to the pool, the token_b borrowed plus the interest at the strike rate
+    pub debt: Decimal,
+    // Amount of token_a locked in the pool as collateral
+    pub collateral: Decimal,
+}
+
+// Terms of a Dutch auction of forfeited collateral, fixed when the lot is created
+#[derive(NonFungibleData)]
+pub struct AuctionLotBadge {
+    // Resource being auctioned
+    pub collateral: ResourceAddress,
+    // Amount of collateral put up for auction
+    pub amount: Decimal,
+    // Seconds since the unix epoch at which the auction started
+    pub start_time: i64,
+}
+
+// State of an auction lot, the price decays linearly from `start_price` to `floor_price` over `duration` seconds
+#[derive(ScryptoCategorize, ScryptoEncode, ScryptoDecode, LegacyDescribe, Clone, Debug)]
+pub struct AuctionLot {
+    // Collateral still for sale
+    pub remaining: Decimal,
+    // Price in token_b of one token_a when the auction starts
+    pub start_price: Decimal,
+    // Price in token_b of one token_a once the decay is over
+    pub floor_price: Decimal,
+    // Seconds since the unix epoch at which the auction started
+    pub start_time: i64,
+    // Seconds it takes for the price to decay to the floor
+    pub duration: i64,
+    // Debt of the forfeited loan the sale has to recover
+    pub debt: Decimal,
+    // token_b raised by the sale so far
+    pub proceeds: Decimal,
+}
+
+// Record of a loss which was not fully covered by the insurance fund
+#[derive(ScryptoCategorize, ScryptoEncode, ScryptoDecode, LegacyDescribe, Clone, Debug)]
+pub struct Haircut {
+    // Seconds since the unix epoch at which the loss was realized
+    pub timestamp: i64,
+    // Debt of token_b left unpaid
+    pub shortfall: Decimal,
+    // Part of the shortfall paid by the insurance fund
+    pub covered: Decimal,
+    // Part of the shortfall spread across the holders of cctoken_b
+    pub socialized: Decimal,
+    // Amount of token_b one cctoken_b redeems for after the haircut
+    pub redemption_rate: Decimal,
+}
+
+// Transient receipt of a flash loan, it can't be deposited so it has to be burned by `repay_flash_loan`
+#[derive(NonFungibleData)]
+pub struct FlashLoanReceipt {
+    // Resource lent out
+    pub resource: ResourceAddress,
+    // Amount lent out
+    pub amount: Decimal,
+    // Fee owed on top of the amount
+    pub fee: Decimal,
+}
+
 #[blueprint]
 mod amm_implementation {
     struct ConstantSumAmm {
@@ -36,29 +114,91 @@ mod amm_implementation {
         interest: Decimal,
         // Component Address of Radiswap
         amm_address: ComponentAddress,
+        // Component used as reference price, either a Radiswap pool (TWAP) or a PriceOracle
+        price_source: ComponentAddress,
+        // Maximum relative difference between the Radiswap spot price and the reference price allowed for rebalancing
+        max_price_deviation: Decimal,
+        // Share of the rebalancing surplus paid to the caller
+        keeper_reward: Decimal,
+        // Rate model bounding the rates quoted by the pool
+        rate_model: ComponentAddress,
+        // Vault for storing the badge allowed to feed utilization to the rate model
+        rate_model_badge: Vault,
+        // Resource address of the loan NFTs given to borrowers
+        loan_badge_address: ResourceAddress,
+        // Debt and collateral of every open borrow
+        loans: HashMap<NonFungibleLocalId, Loan>,
+        // cctoken_a minted for the collateral of the open loans, burned as the collateral leaves token_a_vault
+        loan_claims: Vault,
+        // Share of the collateral value that counts towards the health factor
+        liquidation_threshold: Decimal,
+        // Maximum share of a loan's debt a liquidator can repay in one call
+        close_factor: Decimal,
+        // Extra collateral given to liquidators on top of the repaid value
+        liquidation_bonus: Decimal,
+        // When true forfeited collateral is sold in Dutch auctions, otherwise it is swapped through Radiswap
+        auction_mode: bool,
+        // Resource address of the auction lot NFTs
+        auction_lot_address: ResourceAddress,
+        // Vault for storing the NFTs of the running auction lots
+        auction_lot_vault: Vault,
+        // State of every running auction lot
+        auction_lots: HashMap<NonFungibleLocalId, AuctionLot>,
+        // Auction start price relative to the reference price, e.g. 1.2 starts 20% above
+        auction_start_multiplier: Decimal,
+        // Auction floor price relative to the reference price, e.g. 0.5 ends 50% below
+        auction_floor_multiplier: Decimal,
+        // Seconds it takes for an auction price to decay to the floor
+        auction_duration: i64,
+        // Insurance fund covering bad debt and receiving part of the protocol fees
+        insurance_fund: ComponentAddress,
+        // Vault for storing the badge allowing the pool to draw from the insurance fund
+        insurance_badge: Vault,
+        // Share of the rebalancing surplus kept by the pool which is sent to the insurance fund
+        protocol_fee_share: Decimal,
+        // Amount of token_b one cctoken_b redeems for, lowered by socialized losses
+        redemption_rate: Decimal,
+        // Every loss the insurance fund could not fully cover
+        haircuts: Vec<Haircut>,
+        // Resource address of the flash loan receipts
+        flash_loan_receipt_address: ResourceAddress,
+        // Fee charged on flash loans, as a share of the amount lent
+        flash_loan_fee: Decimal,
+        // Halts rebalancing when the market price jumps, guarded by the admin badge by default
+        circuit_breaker: CircuitBreaker,
     }
 
     impl ConstantSumAmm {
-        // Locking a token and minting collateral claim tokens and bond tokens
+        // Locking a token and minting collateral claim tokens and bond tokens. Also returns the admin badge
+        // allowed to change the pool parameters, meant to be handed to a governor.
         pub fn locking_liquidity(token_a: Bucket, token_a_name: String, token_a_symbol: String, token_b: Bucket,
         token_b_name: String, token_b_symbol: String, duration: i64, required_interest: Decimal, strike_rate: Decimal, 
-        lp_name: String, lp_symbol: String, amm_address: ComponentAddress) -> (ComponentAddress, Bucket, Bucket, Bucket) {   
+        lp_name: String, lp_symbol: String, amm_address: ComponentAddress, price_source: ComponentAddress,
+        rate_model: ComponentAddress, rate_model_badge: Bucket, insurance_fund: ComponentAddress,
+        insurance_badge: Bucket) -> (ComponentAddress, Bucket, Bucket, Bucket, Bucket) {   
             // Checking whether the ratio in which tokens are provided are correct 
             assert!(token_a.amount() / token_b.amount() == dec!(1) / strike_rate, "Tokens provided in the wrong ratio");
 
             // Admin badge used for doing privilaged actions like minting and burning cctokens, bonded tokens, LP tokens, etc
             let mint_badge: Bucket = ResourceBuilder::new_fungible()
-                .metadata("Name", "LP Mint Badge")
+                .metadata("name", "LP Mint Badge")
                 .divisibility(DIVISIBILITY_NONE)
                 .mint_initial_supply(1);
             
             // Mint badge access rule
             let mint_badge_rule: AccessRule = rule!(require(mint_badge.resource_address()));
 
+            // Admin badge allowed to change the pool parameters
+            let admin_badge: Bucket = ResourceBuilder::new_fungible()
+                .metadata("name", "ConstantSumAmm Admin Badge")
+                .divisibility(DIVISIBILITY_NONE)
+                .mint_initial_supply(1);
+            let admin_rule: AccessRule = rule!(require(admin_badge.resource_address()));
+
             // Collateral-claim-token of token_a
             let cctoken_a: Bucket = ResourceBuilder::new_fungible()
-                .metadata("Name", token_a_name)
-                .metadata("Symbol", token_a_symbol)
+                .metadata("name", token_a_name)
+                .metadata("symbol", token_a_symbol)
                 .mintable(mint_badge_rule.clone(), LOCKED)
                 .burnable(mint_badge_rule.clone(), LOCKED)
                 .divisibility(DIVISIBILITY_MAXIMUM)
@@ -66,36 +206,65 @@ mod amm_implementation {
     
             // Collateral-claim-token of token_b
             let cctoken_b: Bucket = ResourceBuilder::new_fungible()
-                .metadata("Name", token_b_name)
-                .metadata("Symbol", token_b_symbol)
+                .metadata("name", token_b_name)
+                .metadata("symbol", token_b_symbol)
                 .mintable(mint_badge_rule.clone(), LOCKED)
                 .burnable(mint_badge_rule.clone(), LOCKED)
                 .divisibility(DIVISIBILITY_MAXIMUM)
                 .mint_initial_supply(token_b.amount());
                 
-            // Bonded token    
+            // Bonded token, named after the market key so the bonds of different markets can be told apart
+            let pair = format!("{}/{}", Self::symbol_of(token_a.resource_address()),
+                Self::symbol_of(token_b.resource_address()));
             let bonded_token: Bucket = ResourceBuilder::new_fungible()
-                .metadata("Name", "Bond Token")
-                .metadata("Symbol", "BT")
+                .metadata("name", format!("{} {}-{} Bond", pair, strike_rate, duration))
+                .metadata("symbol", format!("BT-{}-{}-{}", pair.replace('/', ""), strike_rate, duration))
+                .metadata("pair", pair.clone())
+                .metadata("strike_rate", strike_rate.to_string())
+                .metadata("maturity", duration.to_string())
                 .mintable(mint_badge_rule.clone(), LOCKED)
                 .burnable(mint_badge_rule.clone(), LOCKED)
                 .divisibility(DIVISIBILITY_MAXIMUM)
-                .mint_initial_supply(required_interest * (cctoken_a.amount() + (cctoken_b.amount() * strike_rate)));
+                .mint_initial_supply(required_interest * (cctoken_a.amount() + (cctoken_b.amount() / strike_rate)));
 
             // Resource address of LP token
             let lp_resource_address = ResourceBuilder::new_fungible()
-                .metadata("Name", lp_name)
-                .metadata("Symbol", lp_symbol)
+                .metadata("name", lp_name)
+                .metadata("symbol", lp_symbol)
                 .divisibility(DIVISIBILITY_MAXIMUM)
                 .mintable(mint_badge_rule.clone(), LOCKED)
                 .burnable(mint_badge_rule.clone(), LOCKED)
                 .create_with_no_initial_supply();
 
-            // Checking whether the minted collateral claim and bond tokens are in correct ratio
-            assert!(bonded_token.amount() / cctoken_a.amount() + cctoken_b.amount() == required_interest, "Token ratios are wrong");
+            // Resource address of the loan NFTs
+            let loan_badge_address = ResourceBuilder::new_uuid_non_fungible()
+                .metadata("name", "ConstantSumAmm Loan")
+                .mintable(mint_badge_rule.clone(), LOCKED)
+                .burnable(mint_badge_rule.clone(), LOCKED)
+                .create_with_no_initial_supply();
+
+            // Resource address of the auction lot NFTs
+            let auction_lot_address = ResourceBuilder::new_uuid_non_fungible()
+                .metadata("name", "ConstantSumAmm Auction Lot")
+                .mintable(mint_badge_rule.clone(), LOCKED)
+                .burnable(mint_badge_rule.clone(), LOCKED)
+                .create_with_no_initial_supply();
+
+            // Resource address of the flash loan receipts, which can never be deposited in an account
+            let flash_loan_receipt_address = ResourceBuilder::new_uuid_non_fungible()
+                .metadata("name", "ConstantSumAmm Flash Loan Receipt")
+                .mintable(mint_badge_rule.clone(), LOCKED)
+                .burnable(mint_badge_rule.clone(), LOCKED)
+                .restrict_deposit(rule!(deny_all), LOCKED)
+                .create_with_no_initial_supply();
+
+            // Checking whether the minted collateral claim and bond tokens are in correct ratio, token_b counts
+            // at the strike rate
+            assert!(bonded_token.amount() / (cctoken_a.amount() + (cctoken_b.amount() / strike_rate)) == required_interest, "Token ratios are wrong");
 
-            // Interest to be recieved from liquidity pool
-            let interest: Decimal = dec!(0);
+            // Interest to be recieved from liquidity pool, the bond tokens deposited per token A of collateral claims
+            // until the pool holds liquidity
+            let interest: Decimal = required_interest;
 
             // Constant product of the AMM
             let constant_product: Decimal = dec!(0);
@@ -104,7 +273,18 @@ mod amm_implementation {
             assert!(Clock::current_time_is_at_or_before(Instant::new(duration), TimePrecision::Minute), "Maturity of the pool is over");
     
 
-            let amm_implementation = Self {
+            // Parameter setters can only be called with the admin badge
+            let access_rules = AccessRules::new()
+                .method("set_strike_rate", admin_rule.clone(), LOCKED)
+                .method("set_rebalance_params", admin_rule.clone(), LOCKED)
+                .method("set_liquidation_params", admin_rule.clone(), LOCKED)
+                .method("set_auction_params", admin_rule.clone(), LOCKED)
+                .method("set_fee_params", admin_rule.clone(), LOCKED)
+                .method("set_price_source", admin_rule.clone(), LOCKED)
+                .method("set_circuit_breaker", admin_rule.clone(), LOCKED)
+                .default(rule!(allow_all), LOCKED);
+
+            let mut amm_implementation = Self {
                 token_a_vault: Vault::with_bucket(token_a),
                 token_b_vault: Vault::with_bucket(token_b),
                 cct_a: Vault::new(cctoken_a.resource_address()),
@@ -116,13 +296,39 @@ mod amm_implementation {
                 strike_rate,
                 interest,
                 amm_address,
-                constant_product
+                constant_product,
+                price_source,
+                max_price_deviation: dec!("0.05"),
+                keeper_reward: dec!("0.1"),
+                rate_model,
+                rate_model_badge: Vault::with_bucket(rate_model_badge),
+                loan_badge_address,
+                loans: HashMap::new(),
+                loan_claims: Vault::new(cctoken_a.resource_address()),
+                liquidation_threshold: dec!("0.9"),
+                close_factor: dec!("0.5"),
+                liquidation_bonus: dec!("0.05"),
+                auction_mode: true,
+                auction_lot_address,
+                auction_lot_vault: Vault::new(auction_lot_address),
+                auction_lots: HashMap::new(),
+                auction_start_multiplier: dec!("1.2"),
+                auction_floor_multiplier: dec!("0.5"),
+                auction_duration: 3600,
+                insurance_fund,
+                insurance_badge: Vault::with_bucket(insurance_badge),
+                protocol_fee_share: dec!("0.1"),
+                redemption_rate: dec!(1),
+                haircuts: Vec::new(),
+                flash_loan_receipt_address,
+                flash_loan_fee: dec!("0.0009"),
+                circuit_breaker: CircuitBreaker::new(admin_badge.resource_address()),
             }
-            .instantiate()
-            .globalize();
+            .instantiate();
+            amm_implementation.add_access_check(access_rules);
             
-            // Returning Component Address, collateral claim tokens and bonded tokens minted
-            return (amm_implementation, cctoken_a, cctoken_b, bonded_token)
+            // Returning Component Address, collateral claim tokens, bonded tokens minted and the admin badge
+            return (amm_implementation.globalize(), cctoken_a, cctoken_b, bonded_token, admin_badge)
         }
 
         // Method to be called when spot_price is lesser than strike_rate, here you deposit cctoken_a and bonded_token   
@@ -132,33 +338,24 @@ mod amm_implementation {
             assert!(strike_price == self.strike_rate, "Wrong strike rate");
         
             // Checking whether collateral claim tokens and bond tokens provided are correct 
-            assert!(cctoken_a.resource_address() == self.cct_a.resource_address() || bonded_token.resource_address() ==
+            assert!(cctoken_a.resource_address() == self.cct_a.resource_address() && bonded_token.resource_address() ==
             self.bt_per_second_vault.resource_address(), "Wrong collateral claim or bond token provided");
 
             // Checking whether collateral claim tokens and bond tokens provided are empty 
             assert!(!cctoken_a.is_empty() && !bonded_token.is_empty(), "Empty tokens provided");
 
-            // Checking whether collateral claim tokens and bond tokens are provided in the correct ratio
-            assert!(bonded_token.amount() / cctoken_a.amount() == self.interest, "Ratio of the tokens provided are wrong");
-
-            // Setting up the interest rate
-            self.interest += (bonded_token.amount() / Decimal::from(duration)) / cctoken_a.amount();
-
-            // Adding current constant product to the actual constant product
-            let delta_constant_product = cctoken_a.amount() * (bonded_token.amount() / Decimal::from(duration));
-            self.constant_product += delta_constant_product;
-
-            // Minting LP tokens
-            let lp_token: Bucket = self.lp_admin_badge_vault.authorize(|| {
-                borrow_resource_manager!(self.lp_resource_address).mint(delta_constant_product.powi(1/2))
-            });
+            // Checking whether collateral claim tokens and bond tokens are provided in the ratio of the pool
+            assert!(bonded_token.amount() == cctoken_a.amount() * self.interest(), "Ratio of the tokens provided are wrong");
 
             // Sending collateral claim tokens and bonded tokens to the liquidity pool
+            let (constant_product, pool_claims) = (self.constant_product, self.pool_claims());
+            let claims = cctoken_a.amount();
             self.cct_a.put(cctoken_a);
             self.bt_per_second_vault.put(bonded_token);
+            self.update_constant_product();
 
             // Returning LP tokens for the user to withdraw
-            return lp_token;
+            return self.mint_lp(claims, pool_claims, constant_product);
         }
 
         // Method to be called when spot_price is greater than strike_rate, here you deposit cctoken_b and bonded_token
@@ -167,26 +364,20 @@ mod amm_implementation {
             assert!(duration == self.duration, "Maturity provided is wrong");
             assert!(strike_price == self.strike_rate, "Wrong strike rate");
             
-            assert!(cctoken_b.resource_address() == self.cct_b.resource_address() || bonded_token.resource_address() ==
+            assert!(cctoken_b.resource_address() == self.cct_b.resource_address() && bonded_token.resource_address() ==
             self.bt_per_second_vault.resource_address(), "Wrong collateral claim or bond token provided");
     
             assert!(!cctoken_b.is_empty() && !bonded_token.is_empty(), "Empty tokens provided");
     
-            assert!(bonded_token.amount() / (cctoken_b.amount() / strike_price) == self.interest, "Ratio of the tokens provided are wrong");
+            assert!(bonded_token.amount() == (cctoken_b.amount() / strike_price) * self.interest(), "Ratio of the tokens provided are wrong");
 
-            self.interest += (bonded_token.amount() / Decimal::from(duration)) / (cctoken_b.amount() / strike_price);
-    
-            let delta_constant_product = (cctoken_b.amount() / strike_price) * (bonded_token.amount() / Decimal::from(duration));
-            self.constant_product += delta_constant_product;
-    
-             let lp_token: Bucket = self.lp_admin_badge_vault.authorize(|| {
-                borrow_resource_manager!(self.lp_resource_address).mint(delta_constant_product.powi(1/2))
-            });
-    
+            let (constant_product, pool_claims) = (self.constant_product, self.pool_claims());
+            let claims = cctoken_b.amount() / self.strike_rate;
             self.cct_b.put(cctoken_b);
             self.bt_per_second_vault.put(bonded_token);
+            self.update_constant_product();
     
-            return lp_token;
+            return self.mint_lp(claims, pool_claims, constant_product);
         }
 
          
@@ -201,64 +392,136 @@ mod amm_implementation {
             let lp_manager = borrow_resource_manager!(self.lp_resource_address);
             let share = lp_token.amount() / lp_manager.total_supply();
 
-            self.constant_product = ((self.cct_a.amount() - (self.cct_a.amount() * share)) +
-            ((self.cct_b.amount() / strike_price) - ((self.cct_b.amount() / strike_price) * share))) *
-            ((self.bt_per_second_vault.amount() / Decimal::from(duration)) - (self.bt_per_second_vault.amount() / Decimal::from(duration)) * share);
-
-            self.interest = ((self.bt_per_second_vault.amount() / Decimal::from(duration)) - (self.bt_per_second_vault.amount() / Decimal::from(duration)) * share) /
-            (self.cct_a.amount() - (self.cct_a.amount() * share)) + ((self.cct_b.amount() / strike_price) - ((self.cct_b.amount() / strike_price) * share));
+            // Keeping the interest of the pool, a pro-rata withdrawal leaves the ratio of the pool unchanged
+            self.interest = self.interest();
 
             self.lp_admin_badge_vault.authorize(|| {
                 borrow_resource_manager!(self.lp_resource_address).burn(lp_token)
             });
 
-            (
+            let withdrawn = (
                 self.cct_a.take(self.cct_a.amount() * share),
                 self.cct_b.take(self.cct_b.amount() * share),
                 self.bt_per_second_vault.take(self.bt_per_second_vault.amount() * share)
-            )
+            );
+            self.update_constant_product();
+            withdrawn
         }  
 
         
-        pub fn rebalance_transaction(&mut self, collateral: Bucket) -> Bucket {
+        // Rebalancing converts the pool's exposure at the strike rate and swaps the proceeds through Radiswap.
+        // It is only allowed in the direction justified by the market price and the swap must return at least
+        // `min_output`. The caller gets back the collateral value plus `keeper_reward` of the surplus, the rest
+        // stays with the pool. When the market price trips the circuit breaker, or the Radiswap spot price strays
+        // more than `max_price_deviation` from the price source, rebalancing halts and the collateral is returned
+        // untouched.
+        pub fn rebalance_transaction(&mut self, collateral: Bucket, min_output: Decimal) -> Bucket {
             assert!(!collateral.is_empty(), "No tokens provided");
 
             assert!(collateral.resource_address() == self.token_a_vault.resource_address() || 
             collateral.resource_address() == self.token_b_vault.resource_address(), "Wrong token provided");
 
+            // Market price of token_a in terms of token_b, checked against manipulation
+            let market_price = match self.checked_market_price() {
+                Some(market_price) => market_price,
+                None => return collateral,
+            };
+            let collateral_amount = collateral.amount();
+            let collateral_resource = collateral.resource_address();
+
+            let mut amm_component = RadiswapComponentTarget::at(self.amm_address);
+
             // When strike price is lesser than market price
-            if collateral.resource_address() == self.token_b_vault.resource_address() {
+            let mut output = if collateral.resource_address() == self.token_b_vault.resource_address() {
+                assert!(market_price > self.strike_rate, "Market price does not justify rebalancing into token b");
+
                 let withdraw = self.cct_a.take(collateral.amount() / self.strike_rate);
 
                 let convert = self.convert_option(collateral, withdraw);
 
-                let mut amm_component = RadiswapComponentTarget::at(self.amm_address);
                 let token_b = amm_component.swap(convert.0);
 
                 self.cct_b.put(convert.1);
 
-                return token_b
+                token_b
             }
             // When strike price is bigger than market price
             else {
+                assert!(market_price < self.strike_rate, "Market price does not justify rebalancing into token a");
+
                 let withdraw = self.cct_b.take(collateral.amount() * self.strike_rate);
 
                 let convert = self.convert_option(collateral, withdraw);
 
-                let mut amm_component = RadiswapComponentTarget::at(self.amm_address);
                 let token_a = amm_component.swap(convert.0);
 
                 self.cct_a.put(convert.1);
 
-                return token_a
+                token_a
+            };
+
+            // Radiswap hands the input back when the swap trips its circuit breaker
+            assert!(output.resource_address() == collateral_resource, "Radiswap halted trading during the rebalance");
+            assert!(output.amount() >= min_output, "Rebalance output is below the minimum");
+            assert!(output.amount() > collateral_amount, "Rebalance is not profitable at the market price");
+
+            // The keeper keeps its collateral and a share of the surplus, the pool keeps the remainder
+            let surplus = output.amount() - collateral_amount;
+            let mut pool_share = output.take(surplus * (dec!(1) - self.keeper_reward));
+            let protocol_fee = pool_share.take(pool_share.amount() * self.protocol_fee_share);
+            InsuranceFundComponentTarget::at(self.insurance_fund).deposit_fees(protocol_fee);
+            if pool_share.resource_address() == self.token_a_vault.resource_address() {
+                self.token_a_vault.put(pool_share);
+            } else {
+                self.token_b_vault.put(pool_share);
             }
+
+            output
         }
 
+        // Radiswap spot price of token_a in token_b. Returns None and halts the circuit breaker when the price trips
+        // it or strays more than `max_price_deviation` from the price source, so a price shock halts the pool the
+        // same way whichever bound it crosses.
+        fn checked_market_price(&mut self) -> Option<Decimal> {
+            let now = Clock::current_time_rounded_to_minutes().seconds_since_unix_epoch;
+            self.circuit_breaker.assert_open(now);
+
+            let amm_component = RadiswapComponentTarget::at(self.amm_address);
+            assert!(!amm_component.is_halted(), "Radiswap trading is halted");
+            let price_source = PriceSourceComponentTarget::at(self.price_source);
+            let token_a = self.token_a_vault.resource_address();
+
+            let spot = amm_component.spot_price(token_a);
+            let reference = price_source.price(token_a);
+            if self.circuit_breaker.trip(now, spot, spot, reference) {
+                return None;
+            }
+
+            let deviation = if spot > reference { (spot - reference) / reference } else { (reference - spot) / reference };
+            if deviation > self.max_price_deviation {
+                self.circuit_breaker.halt(now, spot, reference);
+                return None;
+            }
+
+            Some(spot)
+        }
+
+        // Swaps token_a for token_b or back at the strike rate, burning the collateral claims on the token taken
+        // out and minting them on the token put in. Socialized losses lower what the cctoken_b burned pay out, as
+        // they do when redeeming.
         pub fn convert_option(&mut self, lock_token: Bucket, cctoken: Bucket) -> (Bucket, Bucket) {
             assert!((lock_token.resource_address() == self.token_a_vault.resource_address() && cctoken.resource_address() ==
             self.cct_b.resource_address()) || (lock_token.resource_address() == self.token_b_vault.resource_address() && 
             cctoken.resource_address() == self.cct_a.resource_address()) , "Provided collateral or collateral claim token is wrong");
 
+            // The claims burned are worth the collateral locked at the strike rate
+            let cc_amount = if lock_token.resource_address() == self.token_a_vault.resource_address() {
+                lock_token.amount() * self.strike_rate
+            } else {
+                lock_token.amount() / self.strike_rate
+            };
+            assert!(cctoken.amount() == cc_amount, "Collateral claim tokens do not match the collateral at the strike rate");
+
             // If collateral claim token is cctoken_a
             if lock_token.resource_address() == self.token_a_vault.resource_address() {
                 let collateral_claim_token =  self.lp_admin_badge_vault.authorize(|| {
@@ -269,7 +532,7 @@ mod amm_implementation {
                     borrow_resource_manager!(self.cct_b.resource_address()).burn(cctoken)
                 });
 
-                let output_token = self.token_b_vault.take(lock_token.amount() * self.strike_rate);
+                let output_token = self.token_b_vault.take(lock_token.amount() * self.strike_rate * self.redemption_rate);
 
                 self.token_a_vault.put(lock_token);
 
@@ -355,7 +618,8 @@ mod amm_implementation {
             bonded_token.resource_address() == self.bt_per_second_vault.resource_address(), "Wrong tokens provided");
             assert!(unlock_token == self.token_b_vault.resource_address(), "Wrong token provided");
             
-            let output_token: Bucket = self.token_b_vault.take(cctoken_b.amount());
+            // Socialized losses lower what each cctoken_b redeems for
+            let output_token: Bucket = self.token_b_vault.take(cctoken_b.amount() * self.redemption_rate);
 
             self.lp_admin_badge_vault.authorize(|| {
                 borrow_resource_manager!(self.cct_b.resource_address()).burn(cctoken_b)
@@ -370,7 +634,7 @@ mod amm_implementation {
 
         // When strike rate is lesser than market price
         pub fn lend_a(&mut self, lend_token: Bucket) -> (Bucket, Bucket) {
-            assert!(lend_token.resource_address() == self.cct_b.resource_address(), "Swap the token");
+            assert!(lend_token.resource_address() == self.token_b_vault.resource_address(), "Swap the token");
 
             let returns = self.option_b_deposit(lend_token);
 
@@ -378,16 +642,18 @@ mod amm_implementation {
             (self.constant_product / ((self.cct_b.amount() / self.strike_rate) + returns.1.amount()));
                                     // doubt^ 
             let bond_token = bond_token_per_second * Decimal::from(self.duration);
+            let bond_token = self.clamp_quote(bond_token, returns.1.amount());
 
             self.cct_b.put(returns.0);
             let required_bond_token = self.bt_per_second_vault.take(bond_token);
+            self.update_rate_model();
 
             (returns.1, required_bond_token)
         }
 
         // When strike rate is bigger than market price
         pub fn lend_b(&mut self, lend_token: Bucket) -> (Bucket, Bucket) {
-            assert!(lend_token.resource_address() == self.cct_a.resource_address(), "Swap the token");
+            assert!(lend_token.resource_address() == self.token_a_vault.resource_address(), "Swap the token");
 
             let returns = self.option_a_deposit(lend_token);
 
@@ -395,14 +661,23 @@ mod amm_implementation {
             (self.constant_product / (self.cct_a.amount() + returns.1.amount()));
 
             let bond_token = bond_token_per_second * Decimal::from(self.duration);
+            let bond_token = self.clamp_quote(bond_token, returns.1.amount());
 
             self.cct_a.put(returns.0);
             let required_bond_token = self.bt_per_second_vault.take(bond_token);
+            self.update_rate_model();
             
             (returns.1, required_bond_token)
         }
         
-        pub fn borrow_a(&mut self, borrow_amount: Decimal, mut collateral: Bucket, cc_token: Bucket) -> (Bucket, Bucket, Bucket) {
+        // Borrowing token_b against token_a collateral, the returned loan NFT tracks the debt and collateral.
+        // The interest is paid upfront out of the collateral and owed on top of the token_b borrowed, so
+        // repaying releases it with the rest of the collateral only once it is paid too. The cctoken_a minted for
+        // the collateral stay in the pool, so the collateral is only claimed back by repaying. Fails when the
+        // converted collateral pays out less than `borrow_amount`. The cctoken_b the conversion does not need are
+        // handed back.
+        pub fn borrow_a(&mut self, borrow_amount: Decimal, mut collateral: Bucket,
+        mut cc_token: Bucket) -> (Bucket, Bucket, Bucket) {
             let y: Decimal = self.cct_b.amount() / self.strike_rate;
             let z: Decimal = self.bt_per_second_vault.amount() / Decimal::from(self.duration);
             let delta_y: Decimal = collateral.amount() / self.strike_rate;
@@ -410,21 +685,495 @@ mod amm_implementation {
             let bonded_token_per_second: Decimal = (self.constant_product / (y - delta_y)) - z;
 
             let bond_token: Decimal = bonded_token_per_second * Decimal::from(self.duration);
+            let bond_token: Decimal = self.clamp_quote(bond_token, borrow_amount / self.strike_rate);
 
             let first_batch: Bucket = collateral.take(bond_token);
-            let second_batch: Bucket = collateral.take(collateral.amount() - first_batch.amount());
+            let second_batch: Bucket = collateral.take(collateral.amount());
 
-            self.cct_b.take(borrow_amount);
+            let collateral_amount = first_batch.amount() + second_batch.amount();
+            let interest = first_batch.amount() * self.strike_rate;
 
             let returns = self.option_a_deposit(first_batch);
             self.bt_per_second_vault.put(returns.1);
 
-            let convert = self.convert_option(second_batch, cc_token);
+            let cc_amount = second_batch.amount() * self.strike_rate;
+            let convert = self.convert_option(second_batch, cc_token.take(cc_amount));
+            assert!(convert.0.amount() >= borrow_amount, "Collateral does not cover the borrow amount");
+            self.update_rate_model();
 
-            (convert.0, convert.1, returns.0)
+            // Recording the loan so it can be repaid or liquidated before maturity
+            let loan = Loan { debt: convert.0.amount() + interest, collateral: collateral_amount };
+            let loan_badge: Bucket = self.lp_admin_badge_vault.authorize(|| {
+                borrow_resource_manager!(self.loan_badge_address).mint_uuid_non_fungible(LoanBadge { maturity: self.duration })
+            });
+            self.loans.insert(loan_badge.non_fungible_local_id(), loan);
+
+            self.loan_claims.put(returns.0);
+            self.loan_claims.put(convert.1);
+
+            (convert.0, loan_badge, cc_token)
         }
 
-        
+        // Repays the debt of a loan in token_b and releases the matching share of its collateral. Anything
+        // above the debt is returned as change.
+        pub fn repay_a(&mut self, loan_badge: Proof, mut repayment: Bucket) -> (Bucket, Bucket) {
+            let loan_id = loan_badge.validate_proof(self.loan_badge_address).expect("Invalid loan badge")
+                .non_fungible_local_id();
+            assert!(repayment.resource_address() == self.token_b_vault.resource_address(), "Wrong token provided");
+
+            let loan = self.loans.get_mut(&loan_id).expect("Loan is closed");
+            let repaid = std::cmp::min(loan.debt, repayment.amount());
+            // Paying off the debt releases all the collateral left, without the rounding of the share
+            let released = if repaid == loan.debt { loan.collateral } else { loan.collateral * repaid / loan.debt };
+            loan.debt -= repaid;
+            loan.collateral -= released;
+            if loan.debt == Decimal::zero() {
+                self.loans.remove(&loan_id);
+            }
+
+            self.token_b_vault.put(repayment.take(repaid));
+            self.burn_loan_claims(released);
+            (self.token_a_vault.take(released), repayment)
+        }
+
+        // Collateral value over debt, weighted by the liquidation threshold. Below 1 the loan can be liquidated.
+        pub fn health_factor(&self, loan_id: NonFungibleLocalId) -> Decimal {
+            let loan = self.loans.get(&loan_id).expect("Loan is closed");
+            let price = PriceSourceComponentTarget::at(self.price_source).price(self.token_a_vault.resource_address());
+
+            loan.collateral * price * self.liquidation_threshold / loan.debt
+        }
+
+        // Debt and collateral of an open loan
+        pub fn loan(&self, loan_id: NonFungibleLocalId) -> Loan {
+            self.loans.get(&loan_id).expect("Loan is closed").clone()
+        }
+
+        // Repays up to `close_factor` of an unhealthy loan's debt in token_b and seizes collateral worth the
+        // repaid amount plus `liquidation_bonus`. With `swap_collateral` the seized token_a is swapped to
+        // token_b through Radiswap in the same call, which fails while Radiswap has halted trading. Returns the
+        // seized (or swapped) tokens and the change. The price is checked as for rebalancing: liquidations fail
+        // while the circuit breaker is halted, and a price which trips it or strays from the price source halts
+        // it and hands the repayment back untouched.
+        pub fn liquidate(&mut self, loan_id: NonFungibleLocalId, mut repayment: Bucket, swap_collateral: bool) -> (Bucket, Bucket) {
+            assert!(repayment.resource_address() == self.token_b_vault.resource_address(), "Wrong token provided");
+            if self.checked_market_price().is_none() {
+                let seized = if swap_collateral { &self.token_b_vault } else { &self.token_a_vault };
+                return (Bucket::new(seized.resource_address()), repayment);
+            }
+            assert!(self.health_factor(loan_id.clone()) < dec!(1), "Loan is healthy");
+
+            let price = PriceSourceComponentTarget::at(self.price_source).price(self.token_a_vault.resource_address());
+            let loan = self.loans.get_mut(&loan_id).unwrap();
+
+            let repaid = std::cmp::min(loan.debt * self.close_factor, repayment.amount());
+            let seized = std::cmp::min(repaid / price * (dec!(1) + self.liquidation_bonus), loan.collateral);
+            loan.debt -= repaid;
+            loan.collateral -= seized;
+            let bad_debt = if loan.collateral == Decimal::zero() { loan.debt } else { Decimal::zero() };
+            if loan.debt == Decimal::zero() || loan.collateral == Decimal::zero() {
+                self.loans.remove(&loan_id);
+            }
+
+            self.token_b_vault.put(repayment.take(repaid));
+            self.burn_loan_claims(seized);
+            let collateral = self.token_a_vault.take(seized);
+
+            // Debt left once all the collateral is gone is covered by the insurance fund or socialized
+            if bad_debt > Decimal::zero() {
+                self.absorb_shortfall(bad_debt);
+            }
+
+            if swap_collateral {
+                let mut amm_component = RadiswapComponentTarget::at(self.amm_address);
+                let output = amm_component.swap(collateral);
+
+                // Radiswap hands the input back when the swap trips its circuit breaker
+                assert!(output.resource_address() == self.token_b_vault.resource_address(),
+                    "Radiswap halted trading during the liquidation");
+                (output, repayment)
+            } else {
+                (collateral, repayment)
+            }
+        }
+
+        // Share of the bond tokens which are out of the pool, 1 when `bt_per_second_vault` is depleted
+        pub fn utilization(&self) -> Decimal {
+            let supply = borrow_resource_manager!(self.bt_per_second_vault.resource_address()).total_supply();
+            if supply == Decimal::zero() {
+                return Decimal::zero();
+            }
+            dec!(1) - self.bt_per_second_vault.amount() / supply
+        }
+
+        // Yearly rate paid by borrowers according to the rate model
+        pub fn borrow_rate(&self) -> Decimal {
+            RateModelComponentTarget::at(self.rate_model).borrow_rate(self.utilization())
+        }
+
+        // Yearly rate earned by lenders, the borrow rate scaled by utilization
+        pub fn supply_rate(&self) -> Decimal {
+            self.borrow_rate() * self.utilization()
+        }
+
+        // Restricts the bond tokens quoted for `principal` (in token_a terms) to the rate model bounds,
+        // scaled to the time left until maturity
+        fn clamp_quote(&self, bond_token: Decimal, principal: Decimal) -> Decimal {
+            let (min_rate, max_rate) = RateModelComponentTarget::at(self.rate_model).rate_bounds();
+
+            let now = Clock::current_time_rounded_to_minutes().seconds_since_unix_epoch;
+            let period = Decimal::from(std::cmp::max(self.duration - now, 0)) / Decimal::from(SECONDS_PER_YEAR);
+
+            clamp_rate(bond_token, principal * min_rate * period, principal * max_rate * period)
+        }
+
+        // Feeds the new utilization to the rate model
+        fn update_rate_model(&mut self) {
+            let utilization = self.utilization();
+            let mut rate_model = RateModelComponentTarget::at(self.rate_model);
+            self.rate_model_badge.authorize(|| rate_model.update(utilization));
+        }
+
+        // Closes a loan left unpaid after maturity. Its collateral is put up for a Dutch auction, or swapped to
+        // token_b through Radiswap right away when auction mode is off and the swap doesn't trip the circuit
+        // breaker. The debt the sale leaves unpaid is absorbed once it settles. Returns the auction lot id if any.
+        pub fn forfeit_loan(&mut self, loan_id: NonFungibleLocalId) -> Option<NonFungibleLocalId> {
+            assert!(Clock::current_time_is_strictly_after(Instant::new(self.duration), TimePrecision::Minute),
+            "Loans can only be forfeited after maturity");
+
+            let loan = self.loans.remove(&loan_id).expect("Loan is closed");
+            // The collateral is sold off, nobody claims it back with cctoken_a
+            self.burn_loan_claims(loan.collateral);
+
+            let price = PriceSourceComponentTarget::at(self.price_source).price(self.token_a_vault.resource_address());
+            if self.auction_mode {
+                return Some(self.start_auction(loan, price));
+            }
+
+            // Selling the collateral through Radiswap, at no less than the reference price allows
+            let collateral = self.token_a_vault.take(loan.collateral);
+            let min_output = loan.collateral * price * (dec!(1) - self.max_price_deviation);
+            let mut amm_component = RadiswapComponentTarget::at(self.amm_address);
+            let output = amm_component.swap(collateral);
+            if output.resource_address() == self.token_a_vault.resource_address() {
+                // The circuit breaker of Radiswap tripped and handed the collateral back, it is auctioned instead
+                self.token_a_vault.put(output);
+                return Some(self.start_auction(loan, price));
+            }
+            assert!(output.amount() >= min_output, "Collateral sells too far below the reference price");
+
+            // Debt the sale did not recover is covered by the insurance fund or socialized
+            let proceeds = output.amount();
+            self.token_b_vault.put(output);
+            if proceeds < loan.debt {
+                self.absorb_shortfall(loan.debt - proceeds);
+            }
+            None
+        }
+
+        // Current price in token_b of one token_a of an auction lot
+        pub fn auction_price(&self, lot_id: NonFungibleLocalId) -> Decimal {
+            let lot = self.auction_lots.get(&lot_id).expect("Auction lot is closed");
+            let now = Clock::current_time_rounded_to_minutes().seconds_since_unix_epoch;
+
+            let elapsed = std::cmp::min(std::cmp::max(now - lot.start_time, 0), lot.duration);
+            lot.start_price - (lot.start_price - lot.floor_price) * Decimal::from(elapsed) / Decimal::from(lot.duration)
+        }
+
+        // Buys collateral from an auction lot at the current price with token_b. The proceeds go to the pool
+        // for the lenders. Returns the bought collateral and the change.
+        pub fn bid(&mut self, lot_id: NonFungibleLocalId, mut payment: Bucket) -> (Bucket, Bucket) {
+            assert!(payment.resource_address() == self.token_b_vault.resource_address(), "Wrong token provided");
+
+            let price = self.auction_price(lot_id.clone());
+            let lot = self.auction_lots.get_mut(&lot_id).unwrap();
+
+            let bought = std::cmp::min(payment.amount() / price, lot.remaining);
+            lot.remaining -= bought;
+            lot.proceeds += bought * price;
+            self.token_b_vault.put(payment.take(bought * price));
+
+            // Once the lot is sold out, debt the proceeds did not recover is covered by the insurance fund or
+            // socialized
+            if lot.remaining == Decimal::zero() {
+                let shortfall = lot.debt - std::cmp::min(lot.proceeds, lot.debt);
+                self.auction_lots.remove(&lot_id);
+                let lot_badge = self.auction_lot_vault.take_non_fungible(&lot_id);
+                self.lp_admin_badge_vault.authorize(|| lot_badge.burn());
+                if shortfall > Decimal::zero() {
+                    self.absorb_shortfall(shortfall);
+                }
+            }
+
+            (self.token_a_vault.take(bought), payment)
+        }
+
+        // State of a running auction lot
+        pub fn auction_lot(&self, lot_id: NonFungibleLocalId) -> AuctionLot {
+            self.auction_lots.get(&lot_id).expect("Auction lot is closed").clone()
+        }
+
+        // State of every running auction lot
+        pub fn auction_lots(&self) -> HashMap<NonFungibleLocalId, AuctionLot> {
+            self.auction_lots.clone()
+        }
+
+        // Lends token_a or token_b for the duration of the transaction. The returned receipt can't be
+        // deposited, so the transaction only succeeds if `repay_flash_loan` burns it.
+        pub fn flash_loan(&mut self, resource: ResourceAddress, amount: Decimal) -> (Bucket, Bucket) {
+            let vault = if resource == self.token_a_vault.resource_address() {
+                &mut self.token_a_vault
+            } else if resource == self.token_b_vault.resource_address() {
+                &mut self.token_b_vault
+            } else {
+                panic!("Wrong token provided")
+            };
+            assert!(amount > Decimal::zero() && amount <= vault.amount(), "Invalid flash loan amount");
+
+            let loan = vault.take(amount);
+            let fee = amount * self.flash_loan_fee;
+
+            let receipt: Bucket = self.lp_admin_badge_vault.authorize(|| {
+                borrow_resource_manager!(self.flash_loan_receipt_address)
+                    .mint_uuid_non_fungible(FlashLoanReceipt { resource, amount, fee })
+            });
+
+            (loan, receipt)
+        }
+
+        // Takes back a flash loan plus its fee and burns the receipt. Anything above what is owed is returned.
+        pub fn repay_flash_loan(&mut self, mut repayment: Bucket, receipt: Bucket) -> Bucket {
+            assert!(receipt.resource_address() == self.flash_loan_receipt_address, "Invalid flash loan receipt");
+
+            let terms: FlashLoanReceipt = receipt.non_fungible::<FlashLoanReceipt>().data();
+            assert!(repayment.resource_address() == terms.resource, "Wrong token provided");
+            assert!(repayment.amount() >= terms.amount + terms.fee, "Flash loan was not repaid in full");
+
+            let owed = repayment.take(terms.amount + terms.fee);
+            if owed.resource_address() == self.token_a_vault.resource_address() {
+                self.token_a_vault.put(owed);
+            } else {
+                self.token_b_vault.put(owed);
+            }
+
+            self.lp_admin_badge_vault.authorize(|| receipt.burn());
+            repayment
+        }
+
+        // Changes the strike rate of the pool. Every collateral claim, and so every loan and LP token, was minted
+        // against the current one, so it can only change once all of them are redeemed, e.g. before a drained
+        // pool is reused. The bond token metadata and the factory listing keep the strike rate the pool was
+        // created with.
+        pub fn set_strike_rate(&mut self, strike_rate: Decimal) {
+            assert!(strike_rate > Decimal::zero(), "Strike rate must be positive");
+            let outstanding = borrow_resource_manager!(self.cct_a.resource_address()).total_supply()
+                + borrow_resource_manager!(self.cct_b.resource_address()).total_supply();
+            assert!(outstanding == Decimal::zero(), "Collateral claims are outstanding at the current strike rate");
+            self.strike_rate = strike_rate;
+        }
+
+        // Changes the manipulation bound and keeper reward of rebalancing
+        pub fn set_rebalance_params(&mut self, max_price_deviation: Decimal, keeper_reward: Decimal) {
+            assert!(max_price_deviation >= Decimal::zero(), "Invalid price deviation");
+            assert!(keeper_reward >= Decimal::zero() && keeper_reward <= dec!(1), "Invalid keeper reward");
+            self.max_price_deviation = max_price_deviation;
+            self.keeper_reward = keeper_reward;
+        }
+
+        // Changes the liquidation threshold, close factor and liquidation bonus
+        pub fn set_liquidation_params(&mut self, liquidation_threshold: Decimal, close_factor: Decimal,
+        liquidation_bonus: Decimal) {
+            assert!(liquidation_threshold > Decimal::zero() && liquidation_threshold <= dec!(1), "Invalid liquidation threshold");
+            assert!(close_factor > Decimal::zero() && close_factor <= dec!(1), "Invalid close factor");
+            assert!(liquidation_bonus >= Decimal::zero(), "Invalid liquidation bonus");
+            self.liquidation_threshold = liquidation_threshold;
+            self.close_factor = close_factor;
+            self.liquidation_bonus = liquidation_bonus;
+        }
+
+        // Changes how forfeited collateral is disposed of
+        pub fn set_auction_params(&mut self, auction_mode: bool, auction_start_multiplier: Decimal,
+        auction_floor_multiplier: Decimal, auction_duration: i64) {
+            assert!(auction_floor_multiplier > Decimal::zero() && auction_floor_multiplier <= auction_start_multiplier,
+            "Invalid auction prices");
+            assert!(auction_duration > 0, "Invalid auction duration");
+            self.auction_mode = auction_mode;
+            self.auction_start_multiplier = auction_start_multiplier;
+            self.auction_floor_multiplier = auction_floor_multiplier;
+            self.auction_duration = auction_duration;
+        }
+
+        // Changes the protocol fee share sent to the insurance fund and the flash loan fee
+        pub fn set_fee_params(&mut self, protocol_fee_share: Decimal, flash_loan_fee: Decimal) {
+            assert!(protocol_fee_share >= Decimal::zero() && protocol_fee_share <= dec!(1), "Invalid protocol fee share");
+            assert!(flash_loan_fee >= Decimal::zero(), "Invalid flash loan fee");
+            self.protocol_fee_share = protocol_fee_share;
+            self.flash_loan_fee = flash_loan_fee;
+        }
+
+        // Changes the component used as reference price
+        pub fn set_price_source(&mut self, price_source: ComponentAddress) {
+            self.price_source = price_source;
+        }
+
+        // Changes the limits of the circuit breaker and the badge of the guardians allowed to resume rebalancing
+        pub fn set_circuit_breaker(&mut self, max_move: Decimal, window: i64, max_reference_deviation: Decimal,
+        cooldown: i64, guardian_badge: ResourceAddress) {
+            self.circuit_breaker.configure(max_move, window, max_reference_deviation, cooldown, guardian_badge);
+        }
+
+        // Resumes rebalancing halted by the circuit breaker before the end of the cooldown, requires a guardian badge
+        pub fn resume_trading(&mut self, guardian: Proof) {
+            self.circuit_breaker.resume(guardian, Clock::current_time_rounded_to_minutes().seconds_since_unix_epoch);
+        }
+
+        // State of the circuit breaker, including every halt
+        pub fn circuit_breaker(&self) -> CircuitBreaker {
+            self.circuit_breaker.clone()
+        }
+
+        // Every loss the insurance fund could not fully cover
+        pub fn haircuts(&self) -> Vec<Haircut> {
+            self.haircuts.clone()
+        }
+
+        // Amount of token_b one cctoken_b currently redeems for
+        pub fn redemption_rate(&self) -> Decimal {
+            self.redemption_rate
+        }
+
+        // Draws a token_b shortfall from the insurance fund. Whatever the fund cannot pay is taken pro-rata
+        // from every cctoken_b holder by lowering the redemption rate, and recorded as a haircut.
+        fn absorb_shortfall(&mut self, shortfall: Decimal) {
+            let mut insurance_fund = InsuranceFundComponentTarget::at(self.insurance_fund);
+            let token_b = self.token_b_vault.resource_address();
+            let cover = self.insurance_badge.authorize(|| insurance_fund.cover_shortfall(token_b, shortfall));
+            let covered = cover.amount();
+            self.token_b_vault.put(cover);
+
+            let socialized = shortfall - covered;
+            if socialized == Decimal::zero() {
+                return;
+            }
+
+            let outstanding = borrow_resource_manager!(self.cct_b.resource_address()).total_supply();
+            if outstanding > Decimal::zero() {
+                let rate_drop = std::cmp::min(socialized / outstanding, self.redemption_rate);
+                self.redemption_rate -= rate_drop;
+            }
+
+            self.haircuts.push(Haircut {
+                timestamp: Clock::current_time_rounded_to_minutes().seconds_since_unix_epoch,
+                shortfall,
+                covered,
+                socialized,
+                redemption_rate: self.redemption_rate,
+            });
+        }
+
+        // Puts the collateral of a forfeited loan up for auction, starting around `price`. The collateral stays in
+        // token_a_vault until it is bought.
+        fn start_auction(&mut self, loan: Loan, price: Decimal) -> NonFungibleLocalId {
+            let now = Clock::current_time_rounded_to_minutes().seconds_since_unix_epoch;
+
+            let lot_badge: Bucket = self.lp_admin_badge_vault.authorize(|| {
+                borrow_resource_manager!(self.auction_lot_address).mint_uuid_non_fungible(AuctionLotBadge {
+                    collateral: self.token_a_vault.resource_address(),
+                    amount: loan.collateral,
+                    start_time: now,
+                })
+            });
+            let lot_id = lot_badge.non_fungible_local_id();
+
+            self.auction_lots.insert(lot_id.clone(), AuctionLot {
+                remaining: loan.collateral,
+                start_price: price * self.auction_start_multiplier,
+                floor_price: price * self.auction_floor_multiplier,
+                start_time: now,
+                duration: self.auction_duration,
+                debt: loan.debt,
+                proceeds: Decimal::zero(),
+            });
+            self.auction_lot_vault.put(lot_badge);
+
+            lot_id
+        }
+
+        // Burns the cctoken_a backing `amount` of loan collateral which leaves the loans
+        fn burn_loan_claims(&mut self, amount: Decimal) {
+            let claims = self.loan_claims.take(amount);
+            self.lp_admin_badge_vault.authorize(|| {
+                borrow_resource_manager!(self.cct_a.resource_address()).burn(claims)
+            });
+        }
+
+        // Collateral claims held by the pool in token A, token_b counts at the strike rate
+        fn pool_claims(&self) -> Decimal {
+            self.cct_a.amount() + (self.cct_b.amount() / self.strike_rate)
+        }
+
+        // Mints the LP tokens for a deposit of `claims` into a pool which held `pool_claims` at `constant_product`.
+        // The first deposit mints the square root of the constant product it adds, later ones their share of the
+        // pool's claims.
+        fn mint_lp(&mut self, claims: Decimal, pool_claims: Decimal, constant_product: Decimal) -> Bucket {
+            let supply = borrow_resource_manager!(self.lp_resource_address).total_supply();
+            let amount = if supply == Decimal::zero() || pool_claims == Decimal::zero() {
+                Self::sqrt(self.constant_product - constant_product)
+            } else {
+                supply * claims / pool_claims
+            };
+            self.lp_admin_badge_vault.authorize(|| {
+                borrow_resource_manager!(self.lp_resource_address).mint(amount)
+            })
+        }
+
+        // Square root by Newton's method, starting above the root so the iterates decrease until they settle
+        fn sqrt(value: Decimal) -> Decimal {
+            assert!(value >= Decimal::zero(), "Square root of a negative number");
+            if value == Decimal::zero() {
+                return value;
+            }
+            let mut root = std::cmp::max(value, dec!(1));
+            loop {
+                let next = (root + value / root) / dec!(2);
+                if next >= root {
+                    return root;
+                }
+                root = next;
+            }
+        }
+
+        // Recomputes the constant product from the claims and bond tokens the pool holds
+        fn update_constant_product(&mut self) {
+            self.constant_product = self.pool_claims() * (self.bt_per_second_vault.amount() / Decimal::from(self.duration));
+        }
+
+        fn symbol_of(resource_address: ResourceAddress) -> String {
+            borrow_resource_manager!(resource_address)
+                .get_metadata("symbol")
+                .unwrap_or_else(|| "TKN".to_owned())
+        }
+
+        // Resource address of the LP token minted by this pool
+        pub fn lp_resource_address(&self) -> ResourceAddress {
+            self.lp_resource_address
+        }
+
+        // Resource addresses of cctoken_a, cctoken_b, the bond token, the LP token and the loan NFTs
+        pub fn resource_addresses(&self) -> (ResourceAddress, ResourceAddress, ResourceAddress, ResourceAddress, ResourceAddress) {
+            (self.cct_a.resource_address(), self.cct_b.resource_address(), self.bt_per_second_vault.resource_address(),
+            self.lp_resource_address, self.loan_badge_address)
+        }
+
+        // Interest currently quoted by the pool, the bond tokens per token A of collateral claims it holds. Liquidity
+        // is deposited at this ratio.
+        pub fn interest(&self) -> Decimal {
+            let pool_claims = self.pool_claims();
+            if pool_claims == Decimal::zero() || self.bt_per_second_vault.is_empty() {
+                return self.interest;
+            }
+            self.bt_per_second_vault.amount() / pool_claims
+        }
 
     }
 
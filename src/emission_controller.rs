@@ -0,0 +1,241 @@
+use scrypto::radix_engine_interface::time::*;
+use scrypto::prelude::*;
+use crate::staking_rewards::StakingRewardsComponentTarget;
+use crate::vote_escrow::VoteEscrowComponentTarget;
+
+/// A pool receiving emissions. The emissions go to a `StakingRewards` component
+/// owned by the controller where the pool units of the pool are staked.
+#[derive(ScryptoCategorize, ScryptoEncode, ScryptoDecode, LegacyDescribe, Clone, Debug)]
+pub struct Gauge {
+    /// The `Radiswap` pool or `ConstantSumAmm` market.
+    pub pool: ComponentAddress,
+    /// The pool unit or LP token of the pool.
+    pub lp_resource: ResourceAddress,
+    /// The `StakingRewards` component streaming the emissions to the stakers.
+    pub staking: ComponentAddress,
+    /// Whether the reward stream of `staking` was created yet.
+    pub funded: bool,
+}
+
+#[blueprint]
+mod emission_controller {
+    struct EmissionController {
+        /// A vault containing the reward tokens not emitted yet.
+        rewards: Vault,
+        /// The amount of reward tokens emitted per epoch.
+        emission_per_epoch: Decimal,
+        /// Seconds since the unix epoch at which epoch 0 starts.
+        genesis: i64,
+        /// The length of an epoch in seconds.
+        epoch_length: i64,
+        /// The `VoteEscrow` whose locks vote on the weights.
+        vote_escrow: ComponentAddress,
+        /// The resource address of the lock NFTs of `vote_escrow`.
+        lock_badge_address: ResourceAddress,
+        /// Every registered pool, by gauge id.
+        gauges: Vec<Gauge>,
+        /// The admin badges of the `StakingRewards` components, by gauge id.
+        staking_admin_badges: Vec<Vault>,
+        /// The voting power allocated to each gauge, per epoch.
+        weights: HashMap<u64, HashMap<usize, Decimal>>,
+        /// The locks which voted, per epoch.
+        voters: HashMap<u64, HashSet<NonFungibleLocalId>>,
+        /// The reward tokens sent to each gauge, per distributed epoch.
+        payouts: HashMap<u64, HashMap<usize, Decimal>>,
+        /// The oldest epoch not distributed yet.
+        next_epoch: u64,
+    }
+
+    impl EmissionController {
+        /// Creates a controller emitting `emission_per_epoch` of `reward_token`
+        /// every `epoch_length` seconds, weighted by the votes of the locks of
+        /// `vote_escrow`. The returned admin badge registers pools.
+        pub fn instantiate_emission_controller(
+            reward_token: ResourceAddress,
+            emission_per_epoch: Decimal,
+            epoch_length: i64,
+            vote_escrow: ComponentAddress,
+        ) -> (ComponentAddress, Bucket) {
+            assert!(epoch_length > 0, "Epoch length must be positive");
+            assert!(emission_per_epoch >= Decimal::zero(), "Invalid emission rate");
+
+            let admin_badge: Bucket = ResourceBuilder::new_fungible()
+                .divisibility(DIVISIBILITY_NONE)
+                .metadata("name", "Emission Controller Admin Badge")
+                .mint_initial_supply(1);
+
+            let access_rules: AccessRules = AccessRules::new()
+                .method("register_pool", rule!(require(admin_badge.resource_address())), LOCKED)
+                .method("set_emission_per_epoch", rule!(require(admin_badge.resource_address())), LOCKED)
+                .default(rule!(allow_all), LOCKED);
+
+            let mut component = Self {
+                rewards: Vault::new(reward_token),
+                emission_per_epoch,
+                genesis: Self::now(),
+                epoch_length,
+                vote_escrow,
+                lock_badge_address: VoteEscrowComponentTarget::at(vote_escrow).lock_badge_address(),
+                gauges: Vec::new(),
+                staking_admin_badges: Vec::new(),
+                weights: HashMap::new(),
+                voters: HashMap::new(),
+                payouts: HashMap::new(),
+                next_epoch: 0,
+            }
+            .instantiate();
+            component.add_access_check(access_rules);
+
+            (component.globalize(), admin_badge)
+        }
+
+        /// Registers a pool and creates the `StakingRewards` component where its
+        /// pool units are staked to earn emissions. Returns the gauge id.
+        pub fn register_pool(&mut self, pool: ComponentAddress, lp_resource: ResourceAddress) -> usize {
+            assert!(
+                self.gauges.iter().all(|gauge| gauge.pool != pool),
+                "Pool is already registered"
+            );
+
+            let (staking, staking_admin_badge): (ComponentAddress, Bucket) = Runtime::call_function(
+                Runtime::package_address(),
+                "StakingRewards",
+                "instantiate_staking_rewards",
+                args!(lp_resource),
+            );
+
+            self.gauges.push(Gauge {
+                pool,
+                lp_resource,
+                staking,
+                funded: false,
+            });
+            self.staking_admin_badges.push(Vault::with_bucket(staking_admin_badge));
+
+            self.gauges.len() - 1
+        }
+
+        /// Changes the amount emitted by the epochs not distributed yet.
+        pub fn set_emission_per_epoch(&mut self, emission_per_epoch: Decimal) {
+            assert!(emission_per_epoch >= Decimal::zero(), "Invalid emission rate");
+            self.emission_per_epoch = emission_per_epoch;
+        }
+
+        /// Adds reward tokens to be emitted.
+        pub fn fund(&mut self, rewards: Bucket) {
+            self.rewards.put(rewards);
+        }
+
+        /// Allocates the voting power a lock had at the start of the current
+        /// epoch across gauges. `allocation` maps gauge ids to fractions which
+        /// add up to at most one. Each lock votes once per epoch.
+        pub fn vote(&mut self, lock_badge: Proof, allocation: HashMap<usize, Decimal>) {
+            let id: NonFungibleLocalId = lock_badge
+                .validate_proof(self.lock_badge_address)
+                .expect("Invalid lock badge")
+                .non_fungible_local_id();
+
+            let epoch: u64 = self.current_epoch();
+            let total: Decimal = allocation.values().fold(Decimal::zero(), |sum, fraction| {
+                assert!(*fraction >= Decimal::zero(), "Invalid allocation");
+                sum + *fraction
+            });
+            assert!(total <= Decimal::one(), "Allocation exceeds the voting power");
+            assert!(
+                allocation.keys().all(|gauge| *gauge < self.gauges.len()),
+                "Unknown gauge"
+            );
+
+            let power: Decimal = VoteEscrowComponentTarget::at(self.vote_escrow)
+                .voting_power(id.clone(), self.epoch_start(epoch));
+            assert!(power > Decimal::zero(), "No voting power");
+            assert!(
+                self.voters.entry(epoch).or_insert_with(HashSet::new).insert(id),
+                "This lock already voted this epoch"
+            );
+
+            let weights: &mut HashMap<usize, Decimal> = self.weights.entry(epoch).or_insert_with(HashMap::new);
+            for (gauge, fraction) in allocation {
+                *weights.entry(gauge).or_insert(Decimal::zero()) += power * fraction;
+            }
+        }
+
+        /// Distributes the oldest finished epoch which wasn't distributed yet.
+        /// Its emission is split across the gauges in proportion to their weight
+        /// and streamed to their stakers over the following epoch. An epoch
+        /// without votes emits nothing. Anyone can call this.
+        pub fn distribute(&mut self) {
+            let epoch: u64 = self.next_epoch;
+            assert!(epoch < self.current_epoch(), "No finished epoch to distribute");
+            self.next_epoch += 1;
+
+            let weights: HashMap<usize, Decimal> = self.weights.get(&epoch).cloned().unwrap_or_default();
+            let total_weight: Decimal = weights.values().fold(Decimal::zero(), |sum, weight| sum + *weight);
+            let mut payouts: HashMap<usize, Decimal> = HashMap::new();
+
+            if total_weight > Decimal::zero() {
+                let emission: Decimal = std::cmp::min(self.emission_per_epoch, self.rewards.amount());
+                let start: i64 = std::cmp::max(Self::now(), self.epoch_start(epoch + 1));
+                let end: i64 = start + self.epoch_length;
+
+                for (gauge, weight) in weights {
+                    let amount: Decimal = emission * weight / total_weight;
+                    if amount.is_zero() {
+                        continue;
+                    }
+                    let rewards: Bucket = self.rewards.take(amount);
+                    let mut staking = StakingRewardsComponentTarget::at(self.gauges[gauge].staking);
+
+                    if self.gauges[gauge].funded {
+                        self.staking_admin_badges[gauge].authorize(|| staking.top_up(rewards, end));
+                    } else {
+                        self.staking_admin_badges[gauge].authorize(|| staking.add_reward(rewards, start, end));
+                        self.gauges[gauge].funded = true;
+                    }
+                    payouts.insert(gauge, amount);
+                }
+            }
+
+            self.payouts.insert(epoch, payouts);
+        }
+
+        /// Returns the current epoch.
+        pub fn current_epoch(&self) -> u64 {
+            ((Self::now() - self.genesis) / self.epoch_length) as u64
+        }
+
+        /// Returns every registered gauge, by gauge id.
+        pub fn gauges(&self) -> Vec<Gauge> {
+            self.gauges.clone()
+        }
+
+        /// Returns the voting power allocated to each gauge in `epoch`.
+        pub fn weights(&self, epoch: u64) -> HashMap<usize, Decimal> {
+            self.weights.get(&epoch).cloned().unwrap_or_default()
+        }
+
+        /// Returns whether a lock voted in `epoch`.
+        pub fn has_voted(&self, epoch: u64, id: NonFungibleLocalId) -> bool {
+            self.voters.get(&epoch).map(|voters| voters.contains(&id)).unwrap_or(false)
+        }
+
+        /// Returns the reward tokens sent to each gauge for `epoch`, or `None`
+        /// if the epoch wasn't distributed yet.
+        pub fn payouts(&self, epoch: u64) -> Option<HashMap<usize, Decimal>> {
+            self.payouts.get(&epoch).cloned()
+        }
+
+        /// Returns the amount of reward tokens not emitted yet.
+        pub fn remaining_rewards(&self) -> Decimal {
+            self.rewards.amount()
+        }
+
+        fn epoch_start(&self, epoch: u64) -> i64 {
+            self.genesis + epoch as i64 * self.epoch_length
+        }
+
+        fn now() -> i64 {
+            Clock::current_time_rounded_to_minutes().seconds_since_unix_epoch
+        }
+    }
+}
@@ -0,0 +1,329 @@
+use scrypto::radix_engine_interface::time::*;
+use scrypto::prelude::*;
+
+// importing the ConstantSumAmm methods that the factory needs after instantiating a market
+external_component! {
+    ConstantSumAmmComponentTarget {
+        fn lp_resource_address(&self) -> ResourceAddress;
+        fn interest(&self) -> Decimal;
+        fn withdraw_liquidity(&mut self, lp_token: Bucket, strike_price: Decimal, duration: i64) -> (Bucket, Bucket, Bucket);
+        fn deposit_liquidity_a(&mut self, cctoken_a: Bucket, bonded_token: Bucket, strike_price: Decimal, duration: i64) -> Bucket;
+        fn deposit_liquidity_b(&mut self, cctoken_b: Bucket, bonded_token: Bucket, strike_price: Decimal, duration: i64) -> Bucket;
+        fn option_a_deposit(&mut self, lock_token: Bucket) -> (Bucket, Bucket);
+        fn option_b_deposit(&mut self, lock_token: Bucket) -> (Bucket, Bucket);
+        fn option_a_withdraw(&mut self, unlock_token: ResourceAddress, cctoken_a: Bucket, bonded_token: Bucket) -> Bucket;
+        fn option_b_withdraw(&mut self, unlock_token: ResourceAddress, cctoken_b: Bucket, bonded_token: Bucket) -> Bucket;
+        fn lend_a(&mut self, lend_token: Bucket) -> (Bucket, Bucket);
+        fn lend_b(&mut self, lend_token: Bucket) -> (Bucket, Bucket);
+    }
+}
+
+// Key identifying a single options market. The strike rate is quoted as token B per token A, so (B, A) at
+// 1 / strike is the same market as (A, B) at strike, see `MarketKey::same_market`.
+#[derive(ScryptoCategorize, ScryptoEncode, ScryptoDecode, LegacyDescribe, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MarketKey {
+    pub token_a: ResourceAddress,
+    pub token_b: ResourceAddress,
+    pub strike_rate: Decimal,
+    pub maturity: i64,
+}
+
+impl MarketKey {
+    // True when both keys name the same market, quoted in the same or the opposite direction. Inverting a
+    // strike rate rounds, so opposite quotes only have to agree within a billionth.
+    pub fn same_market(&self, other: &MarketKey) -> bool {
+        if self.maturity != other.maturity {
+            return false;
+        }
+        if self.token_a == other.token_a && self.token_b == other.token_b {
+            return self.strike_rate == other.strike_rate;
+        }
+        let product = self.strike_rate * other.strike_rate;
+        self.token_a == other.token_b && self.token_b == other.token_a
+            && product >= dec!("0.999999999") && product <= dec!("1.000000001")
+    }
+
+    // True when the key is on the pair, in either direction
+    pub fn is_pair(&self, token_a: ResourceAddress, token_b: ResourceAddress) -> bool {
+        (self.token_a == token_a && self.token_b == token_b) || (self.token_a == token_b && self.token_b == token_a)
+    }
+}
+
+// Everything a front end needs to interact with a market created by the factory
+#[derive(ScryptoCategorize, ScryptoEncode, ScryptoDecode, LegacyDescribe, Clone, Debug)]
+pub struct MarketInfo {
+    pub key: MarketKey,
+    pub component: ComponentAddress,
+    pub cctoken_a: ResourceAddress,
+    pub cctoken_b: ResourceAddress,
+    pub bond_token: ResourceAddress,
+    pub lp_token: ResourceAddress,
+    pub amm_address: ComponentAddress,
+}
+
+#[blueprint]
+mod market_factory {
+    struct MarketFactory {
+        // Every market ever created, keyed by pair, strike and maturity
+        markets: HashMap<MarketKey, MarketInfo>,
+        // Keys in creation order so listings are stable
+        market_keys: Vec<MarketKey>,
+    }
+
+    impl MarketFactory {
+        pub fn instantiate_market_factory() -> ComponentAddress {
+            Self {
+                markets: HashMap::new(),
+                market_keys: Vec::new(),
+            }
+            .instantiate()
+            .globalize()
+        }
+
+        // Instantiates a new ConstantSumAmm for (token A, token B, strike, maturity) with standardized
+        // names and symbols for the collateral claim and LP tokens. Fails if that market already exists,
+        // including as (token B, token A, 1 / strike, maturity).
+        pub fn create_market(&mut self, token_a: Bucket, token_b: Bucket, maturity: i64, required_interest: Decimal,
+        strike_rate: Decimal, amm_address: ComponentAddress, price_source: ComponentAddress,
+        rate_model: ComponentAddress, rate_model_badge: Bucket, insurance_fund: ComponentAddress,
+        insurance_badge: Bucket) -> (ComponentAddress, Bucket, Bucket, Bucket, Bucket) {
+            assert!(!token_a.is_empty() && !token_b.is_empty(), "Empty tokens provided");
+            assert!(token_a.resource_address() != token_b.resource_address(), "Both tokens of a market must differ");
+            assert!(strike_rate > Decimal::zero(), "Strike rate must be positive");
+
+            let key = MarketKey {
+                token_a: token_a.resource_address(),
+                token_b: token_b.resource_address(),
+                strike_rate,
+                maturity,
+            };
+            assert!(self.find_market(&key).is_none(), "A market for this pair, strike and maturity already exists");
+
+            // Standardized metadata, e.g. "ccBTC-9000-1680000000" for the collateral claim token of BTC
+            let symbol_a = Self::symbol_of(key.token_a);
+            let symbol_b = Self::symbol_of(key.token_b);
+            let suffix = format!("{}-{}", strike_rate, maturity);
+
+            let (component, cctoken_a, cctoken_b, bonded_token, admin_badge): (ComponentAddress, Bucket, Bucket, Bucket, Bucket) =
+                Runtime::call_function(
+                    Runtime::package_address(),
+                    "ConstantSumAmm",
+                    "locking_liquidity",
+                    args!(
+                        token_a,
+                        format!("Collateral Claim {} {}/{} {}", symbol_a, symbol_a, symbol_b, suffix),
+                        format!("cc{}-{}", symbol_a, suffix),
+                        token_b,
+                        format!("Collateral Claim {} {}/{} {}", symbol_b, symbol_a, symbol_b, suffix),
+                        format!("cc{}-{}", symbol_b, suffix),
+                        maturity,
+                        required_interest,
+                        strike_rate,
+                        format!("{}/{} {} LP", symbol_a, symbol_b, suffix),
+                        format!("LP-{}{}-{}", symbol_a, symbol_b, suffix),
+                        amm_address,
+                        price_source,
+                        rate_model,
+                        rate_model_badge,
+                        insurance_fund,
+                        insurance_badge
+                    ),
+                );
+
+            let market = ConstantSumAmmComponentTarget::at(component);
+
+            let info = MarketInfo {
+                key: key.clone(),
+                component,
+                cctoken_a: cctoken_a.resource_address(),
+                cctoken_b: cctoken_b.resource_address(),
+                bond_token: bonded_token.resource_address(),
+                lp_token: market.lp_resource_address(),
+                amm_address,
+            };
+
+            self.markets.insert(key.clone(), info);
+            self.market_keys.push(key);
+
+            (component, cctoken_a, cctoken_b, bonded_token, admin_badge)
+        }
+
+        // Looks up the market for a pair, strike and maturity, quoted in either direction. The key of the
+        // returned market is in the direction it was created with.
+        pub fn get_market(&self, token_a: ResourceAddress, token_b: ResourceAddress, strike_rate: Decimal,
+        maturity: i64) -> Option<MarketInfo> {
+            self.find_market(&MarketKey { token_a, token_b, strike_rate, maturity })
+        }
+
+        // All markets of a pair in either direction, in creation order
+        pub fn markets_for_pair(&self, token_a: ResourceAddress, token_b: ResourceAddress) -> Vec<MarketInfo> {
+            self.market_keys
+                .iter()
+                .filter(|key| key.is_pair(token_a, token_b))
+                .map(|key| self.markets[key].clone())
+                .collect()
+        }
+
+        // Markets whose maturity has not been reached yet
+        pub fn active_markets(&self) -> Vec<MarketInfo> {
+            self.market_keys
+                .iter()
+                .filter(|key| !Self::is_expired(key.maturity))
+                .map(|key| self.markets[key].clone())
+                .collect()
+        }
+
+        // Markets whose maturity is over
+        pub fn expired_markets(&self) -> Vec<MarketInfo> {
+            self.market_keys
+                .iter()
+                .filter(|key| Self::is_expired(key.maturity))
+                .map(|key| self.markets[key].clone())
+                .collect()
+        }
+
+        // Moves a lending position (collateral claim tokens plus bond tokens) from a maturing market into a
+        // later market of the same pair and strike. The underlying is redeemed from `source` and lent to
+        // `target` in the same transaction; the transaction fails if the resulting rate, bond tokens
+        // received per token A of principal with token B counting at the strike rate, falls outside
+        // [min_rate, max_rate]. Redeeming doesn't take bond tokens, so the source bond tokens are handed
+        // back along with the target ones.
+        pub fn rollover_lend(&mut self, source: ComponentAddress, target: ComponentAddress, cctoken: Bucket,
+        bonded_token: Bucket, min_rate: Decimal, max_rate: Decimal) -> (Bucket, Bucket) {
+            let (source_info, target_info) = self.rollover_markets(source, target);
+            let mut source_market = ConstantSumAmmComponentTarget::at(source);
+            let mut target_market = ConstantSumAmmComponentTarget::at(target);
+            assert!(bonded_token.resource_address() == source_info.bond_token,
+            "Bond token does not belong to the source market");
+
+            // Redeeming the underlying from the maturing market and lending it to the later one
+            let no_bonds = Bucket::new(source_info.bond_token);
+            let (principal, mut bonds, returned_bonds): (Decimal, Bucket, Bucket) =
+                if cctoken.resource_address() == source_info.cctoken_a {
+                    let underlying = source_market.option_a_withdraw(source_info.key.token_a, cctoken, no_bonds);
+                    let principal = underlying.amount();
+                    let (bonds, returned_bonds) = target_market.lend_b(underlying);
+                    (principal, bonds, returned_bonds)
+                } else if cctoken.resource_address() == source_info.cctoken_b {
+                    let underlying = source_market.option_b_withdraw(source_info.key.token_b, cctoken, no_bonds);
+                    let principal = underlying.amount() / source_info.key.strike_rate;
+                    let (bonds, returned_bonds) = target_market.lend_a(underlying);
+                    (principal, bonds, returned_bonds)
+                } else {
+                    panic!("Collateral claim token does not belong to the source market")
+                };
+            bonds.put(returned_bonds);
+
+            let rate = bonds.amount() / principal;
+            assert!(rate >= min_rate && rate <= max_rate, "Rollover rate is outside the requested bounds");
+
+            assert!(bonds.resource_address() == target_info.bond_token, "Target market returned a foreign bond token");
+            (bonds, bonded_token)
+        }
+
+        // Moves liquidity from a maturing market into a later market of the same pair and strike. The LP
+        // tokens are burned in `source`, the collateral claim tokens are redeemed for the underlying and
+        // re-locked in `target`, then deposited as liquidity at the bond to claim ratio of `target`. Returns
+        // the new LP tokens and everything left over: the source bond tokens, which redeeming doesn't take,
+        // and the target claims or bond tokens in excess of that ratio. Fails if the interest the liquidity
+        // was deposited at is outside [min_interest, max_interest].
+        pub fn rollover_liquidity(&mut self, source: ComponentAddress, target: ComponentAddress, lp_token: Bucket,
+        min_interest: Decimal, max_interest: Decimal) -> (Bucket, Vec<Bucket>) {
+            let (source_info, target_info) = self.rollover_markets(source, target);
+            let mut source_market = ConstantSumAmmComponentTarget::at(source);
+            let mut target_market = ConstantSumAmmComponentTarget::at(target);
+
+            let strike_rate = source_info.key.strike_rate;
+            let (cctoken_a, cctoken_b, bonded_token) =
+                source_market.withdraw_liquidity(lp_token, strike_rate, source_info.key.maturity);
+
+            let underlying_a = source_market.option_a_withdraw(source_info.key.token_a, cctoken_a,
+                Bucket::new(source_info.bond_token));
+            let underlying_b = source_market.option_b_withdraw(source_info.key.token_b, cctoken_b,
+                Bucket::new(source_info.bond_token));
+
+            let mut lp_out = Bucket::new(target_info.lp_token);
+            let mut leftovers = vec![bonded_token];
+            let (mut claims, mut bonds) = (Decimal::zero(), Decimal::zero());
+            let maturity = target_info.key.maturity;
+            if !underlying_a.is_empty() {
+                let (mut cctoken, mut bond_token) = target_market.option_a_deposit(underlying_a);
+                let interest = target_market.interest();
+                let deposit = std::cmp::min(cctoken.amount(), bond_token.amount() / interest);
+                let cctoken_in = cctoken.take(deposit);
+                let bond_token_in = bond_token.take(deposit * interest);
+                claims += cctoken_in.amount();
+                bonds += bond_token_in.amount();
+                lp_out.put(target_market.deposit_liquidity_a(cctoken_in, bond_token_in, strike_rate, maturity));
+                leftovers.push(cctoken);
+                leftovers.push(bond_token);
+            } else {
+                underlying_a.drop_empty();
+            }
+            if !underlying_b.is_empty() {
+                let (mut cctoken, mut bond_token) = target_market.option_b_deposit(underlying_b);
+                let interest = target_market.interest();
+                let deposit = std::cmp::min(cctoken.amount(), (bond_token.amount() / interest) * strike_rate);
+                let cctoken_in = cctoken.take(deposit);
+                let bond_token_in = bond_token.take((deposit / strike_rate) * interest);
+                claims += cctoken_in.amount() / strike_rate;
+                bonds += bond_token_in.amount();
+                lp_out.put(target_market.deposit_liquidity_b(cctoken_in, bond_token_in, strike_rate, maturity));
+                leftovers.push(cctoken);
+                leftovers.push(bond_token);
+            } else {
+                underlying_b.drop_empty();
+            }
+
+            // Checking the interest the liquidity actually settled at
+            assert!(claims > Decimal::zero(), "No liquidity to roll over");
+            let interest = bonds / claims;
+            assert!(interest >= min_interest && interest <= max_interest,
+            "Target market interest is outside the requested bounds");
+
+            (lp_out, leftovers)
+        }
+
+        // Checks that `target` rolls `source` forward: same pair, same strike and a later, still open maturity
+        fn rollover_markets(&self, source: ComponentAddress, target: ComponentAddress) -> (MarketInfo, MarketInfo) {
+            let source_info = self.market_by_component(source);
+            let target_info = self.market_by_component(target);
+
+            assert!(source_info.key.token_a == target_info.key.token_a && source_info.key.token_b ==
+            target_info.key.token_b, "Markets are on different pairs");
+            assert!(source_info.key.strike_rate == target_info.key.strike_rate, "Markets have different strike rates");
+            assert!(target_info.key.maturity > source_info.key.maturity, "Target market must mature later");
+            assert!(!Self::is_expired(target_info.key.maturity), "Target market is expired");
+
+            (source_info, target_info)
+        }
+
+        fn find_market(&self, key: &MarketKey) -> Option<MarketInfo> {
+            if let Some(info) = self.markets.get(key) {
+                return Some(info.clone());
+            }
+            self.market_keys
+                .iter()
+                .find(|other| other.same_market(key))
+                .map(|other| self.markets[other].clone())
+        }
+
+        fn market_by_component(&self, component: ComponentAddress) -> MarketInfo {
+            self.markets
+                .values()
+                .find(|info| info.component == component)
+                .cloned()
+                .expect("Market was not created by this factory")
+        }
+
+        fn is_expired(maturity: i64) -> bool {
+            Clock::current_time_is_strictly_after(Instant::new(maturity), TimePrecision::Minute)
+        }
+
+        fn symbol_of(resource_address: ResourceAddress) -> String {
+            borrow_resource_manager!(resource_address)
+                .get_metadata("symbol")
+                .unwrap_or_else(|| "TKN".to_owned())
+        }
+    }
+}
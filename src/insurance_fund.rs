@@ -0,0 +1,115 @@
+use scrypto::radix_engine_interface::time::*;
+use scrypto::prelude::*;
+
+// Interface used by the pools to feed and draw from the fund
+external_component! {
+    InsuranceFundComponentTarget {
+        fn deposit_fees(&mut self, fees: Bucket);
+        fn cover_shortfall(&mut self, resource: ResourceAddress, amount: Decimal) -> Bucket;
+    }
+}
+
+// A payout made by the fund to cover a shortfall
+#[derive(ScryptoCategorize, ScryptoEncode, ScryptoDecode, LegacyDescribe, Clone, Debug)]
+pub struct Payout {
+    // Seconds since the unix epoch at which the payout was made
+    pub timestamp: i64,
+    // Resource paid out
+    pub resource: ResourceAddress,
+    // Shortfall the pool asked to cover
+    pub requested: Decimal,
+    // Amount actually paid, less than requested when the fund ran dry
+    pub paid: Decimal,
+}
+
+#[blueprint]
+mod insurance_fund {
+    struct InsuranceFund {
+        // Fees collected from the pools, per resource
+        vaults: HashMap<ResourceAddress, Vault>,
+        // Resource address of the badges allowed to draw from the fund
+        claimant_badge_address: ResourceAddress,
+        // Vault for storing the badge minting claimant badges
+        claimant_minter_badge: Vault,
+        // Every payout made by the fund, oldest first
+        payouts: Vec<Payout>,
+    }
+
+    impl InsuranceFund {
+        // Creates an empty fund. The admin badge issues the claimant badges handed to the pools which may
+        // draw from the fund; anyone can deposit fees.
+        pub fn instantiate_insurance_fund() -> (ComponentAddress, Bucket) {
+            let admin_badge: Bucket = ResourceBuilder::new_fungible()
+                .divisibility(DIVISIBILITY_NONE)
+                .metadata("name", "Insurance Fund Admin Badge")
+                .mint_initial_supply(1);
+
+            let claimant_minter_badge: Bucket = ResourceBuilder::new_fungible()
+                .divisibility(DIVISIBILITY_NONE)
+                .metadata("name", "Insurance Claimant Mint Auth")
+                .mint_initial_supply(1);
+
+            let claimant_badge_address: ResourceAddress = ResourceBuilder::new_fungible()
+                .divisibility(DIVISIBILITY_NONE)
+                .metadata("name", "Insurance Claimant Badge")
+                .mintable(rule!(require(claimant_minter_badge.resource_address())), LOCKED)
+                .burnable(rule!(require(claimant_minter_badge.resource_address())), LOCKED)
+                .create_with_no_initial_supply();
+
+            let access_rules = AccessRules::new()
+                .method("issue_claimant_badge", rule!(require(admin_badge.resource_address())), LOCKED)
+                .method("cover_shortfall", rule!(require(claimant_badge_address)), LOCKED)
+                .default(rule!(allow_all), LOCKED);
+
+            let mut component = Self {
+                vaults: HashMap::new(),
+                claimant_badge_address,
+                claimant_minter_badge: Vault::with_bucket(claimant_minter_badge),
+                payouts: Vec::new(),
+            }
+            .instantiate();
+            component.add_access_check(access_rules);
+
+            (component.globalize(), admin_badge)
+        }
+
+        // Mints a badge allowing a pool to draw from the fund
+        pub fn issue_claimant_badge(&mut self) -> Bucket {
+            self.claimant_minter_badge.authorize(|| {
+                borrow_resource_manager!(self.claimant_badge_address).mint(1)
+            })
+        }
+
+        // Adds protocol fees to the fund
+        pub fn deposit_fees(&mut self, fees: Bucket) {
+            let resource = fees.resource_address();
+            self.vaults.entry(resource).or_insert_with(|| Vault::new(resource)).put(fees);
+        }
+
+        // Pays out up to `amount` of `resource` to cover a shortfall. When the fund holds less, everything
+        // it has is paid and the caller has to deal with the rest.
+        pub fn cover_shortfall(&mut self, resource: ResourceAddress, amount: Decimal) -> Bucket {
+            let vault = self.vaults.entry(resource).or_insert_with(|| Vault::new(resource));
+            let paid = std::cmp::min(amount, vault.amount());
+
+            self.payouts.push(Payout {
+                timestamp: Clock::current_time_rounded_to_minutes().seconds_since_unix_epoch,
+                resource,
+                requested: amount,
+                paid,
+            });
+
+            vault.take(paid)
+        }
+
+        // Amount of `resource` held by the fund
+        pub fn balance(&self, resource: ResourceAddress) -> Decimal {
+            self.vaults.get(&resource).map(|vault| vault.amount()).unwrap_or(Decimal::zero())
+        }
+
+        // Every payout made by the fund
+        pub fn payouts(&self) -> Vec<Payout> {
+            self.payouts.clone()
+        }
+    }
+}
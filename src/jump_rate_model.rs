@@ -0,0 +1,52 @@
+use scrypto::prelude::*;
+use crate::rate_model::*;
+
+#[blueprint]
+mod jump_rate_model {
+    struct JumpRateModel {
+        // Yearly rate at zero utilization
+        base_rate: Decimal,
+        // Yearly rate added per unit of utilization below the kink
+        slope: Decimal,
+        // Yearly rate added per unit of utilization above the kink
+        jump_slope: Decimal,
+        // Utilization from which `jump_slope` applies
+        kink: Decimal,
+        // Lowest yearly rate quoted
+        min_rate: Decimal,
+        // Highest yearly rate quoted
+        max_rate: Decimal,
+    }
+
+    impl JumpRateModel {
+        // The rate grows by `slope` up to the kink and by the much steeper `jump_slope` above it, pushing
+        // borrowers out before the pool runs dry. Returns the component and the updater badge.
+        pub fn instantiate_jump_rate_model(base_rate: Decimal, slope: Decimal, jump_slope: Decimal,
+        kink: Decimal, min_rate: Decimal, max_rate: Decimal) -> (ComponentAddress, Bucket) {
+            assert!(kink > Decimal::zero() && kink < Decimal::one(), "Kink must be between 0 and 1");
+            assert!(min_rate >= Decimal::zero() && min_rate <= max_rate, "Invalid rate bounds");
+
+            let component = Self { base_rate, slope, jump_slope, kink, min_rate, max_rate }
+                .instantiate()
+                .globalize();
+
+            (component, new_updater_badge())
+        }
+
+        pub fn borrow_rate(&self, utilization: Decimal) -> Decimal {
+            let rate = if utilization <= self.kink {
+                self.base_rate + self.slope * utilization
+            } else {
+                self.base_rate + self.slope * self.kink + self.jump_slope * (utilization - self.kink)
+            };
+            clamp_rate(rate, self.min_rate, self.max_rate)
+        }
+
+        pub fn rate_bounds(&self) -> (Decimal, Decimal) {
+            (self.min_rate, self.max_rate)
+        }
+
+        // The jump rate model is stateless
+        pub fn update(&mut self, _utilization: Decimal) {}
+    }
+}
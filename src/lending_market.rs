@@ -0,0 +1,579 @@
+use scrypto::radix_engine_interface::time::*;
+use scrypto::prelude::*;
+use crate::insurance_fund::InsuranceFundComponentTarget;
+use crate::price_source::PriceSourceComponentTarget;
+use crate::rate_model::SECONDS_PER_YEAR;
+
+/// The data held by the NFT which identifies a borrowing position.
+#[derive(NonFungibleData)]
+pub struct PositionBadge {
+    /// Seconds since the unix epoch at which the position was opened.
+    pub opened_at: i64,
+}
+
+/// The transient receipt of a flash loan. It can't be deposited, so it has to be
+/// burned by `repay_flash_loan` before the transaction ends.
+#[derive(NonFungibleData)]
+pub struct FlashLoanReceipt {
+    /// The resource lent out.
+    pub resource: ResourceAddress,
+    /// The amount lent out.
+    pub amount: Decimal,
+    /// The fee owed on top of the amount.
+    pub fee: Decimal,
+}
+
+/// The configuration and accounting of a single asset of the market.
+#[derive(ScryptoCategorize, ScryptoEncode, ScryptoDecode, LegacyDescribe, Clone, Debug)]
+pub struct AssetState {
+    /// The exchange rate receipt token given to lenders of this asset.
+    pub receipt_token: ResourceAddress,
+    /// The component giving the price of this asset in terms of the quote token.
+    /// Ignored for the quote token itself, which is always worth 1.
+    pub price_source: ComponentAddress,
+    /// The maximum value which can be borrowed against one unit of value of this
+    /// asset posted as collateral, 0 <= ltv < 1.
+    pub ltv: Decimal,
+    /// The yearly borrow rate at zero utilization.
+    pub base_rate: Decimal,
+    /// The yearly borrow rate added at full utilization.
+    pub slope: Decimal,
+    /// The share of the interest kept by the market as reserves.
+    pub reserve_factor: Decimal,
+    /// The amount owed by all borrowers, including accrued interest.
+    pub total_borrows: Decimal,
+    /// The part of the pool which belongs to the market and not to the lenders.
+    pub total_reserves: Decimal,
+    /// Grows with the accrued interest. A debt of `d` taken at index `i` is worth
+    /// `d * borrow_index / i` today.
+    pub borrow_index: Decimal,
+    /// Seconds since the unix epoch at which interest was last accrued.
+    pub last_accrual: i64,
+    /// The amount currently out in flash loans, still counted as lenders' funds.
+    pub flash_loaned: Decimal,
+}
+
+/// The collateral and debts of a borrowing position.
+#[derive(ScryptoCategorize, ScryptoEncode, ScryptoDecode, LegacyDescribe, Clone, Debug, Default)]
+pub struct Position {
+    /// The amount of each asset posted as collateral.
+    pub collateral: HashMap<ResourceAddress, Decimal>,
+    /// The debt of each asset, scaled down by the borrow index at which it was
+    /// last updated. Multiply by the current index to get the amount owed.
+    pub scaled_debt: HashMap<ResourceAddress, Decimal>,
+}
+
+#[blueprint]
+mod lending_market {
+    struct LendingMarket {
+        /// The token in which all prices are expressed.
+        quote: ResourceAddress,
+        /// The configuration and accounting of every listed asset.
+        assets: HashMap<ResourceAddress, AssetState>,
+        /// The lent tokens of every listed asset which are not borrowed.
+        pools: HashMap<ResourceAddress, Vault>,
+        /// The collateral posted by all borrowers, per asset.
+        collateral_vaults: HashMap<ResourceAddress, Vault>,
+        /// The resource address of the position NFTs.
+        position_badge_address: ResourceAddress,
+        /// The positions of all borrowers.
+        positions: HashMap<NonFungibleLocalId, Position>,
+        /// A vault containing the badge which mints receipt tokens and position NFTs.
+        minter_badge: Vault,
+        /// The insurance fund the reserves can be swept to, if any.
+        insurance_fund: Option<ComponentAddress>,
+        /// The resource address of the flash loan receipts.
+        flash_loan_receipt_address: ResourceAddress,
+        /// The fee charged on flash loans, as a share of the amount lent.
+        flash_loan_fee: Decimal,
+        /// The largest share of a debt which can be repaid by a single liquidation.
+        close_factor: Decimal,
+        /// The share of the repaid value a liquidator gets on top in collateral.
+        liquidation_bonus: Decimal,
+    }
+
+    impl LendingMarket {
+        /// Creates a new market whose prices are expressed in `quote`. The returned
+        /// admin badge is needed to list assets.
+        pub fn instantiate_lending_market(quote: ResourceAddress) -> (ComponentAddress, Bucket) {
+            let admin_badge: Bucket = ResourceBuilder::new_fungible()
+                .divisibility(DIVISIBILITY_NONE)
+                .metadata("name", "Lending Market Admin Badge")
+                .mint_initial_supply(1);
+
+            let minter_badge: Bucket = ResourceBuilder::new_fungible()
+                .divisibility(DIVISIBILITY_NONE)
+                .metadata("name", "Lending Market Mint Auth")
+                .mint_initial_supply(1);
+
+            let position_badge_address: ResourceAddress = ResourceBuilder::new_uuid_non_fungible()
+                .metadata("name", "Lending Position")
+                .mintable(rule!(require(minter_badge.resource_address())), LOCKED)
+                .burnable(rule!(require(minter_badge.resource_address())), LOCKED)
+                .create_with_no_initial_supply();
+
+            let flash_loan_receipt_address: ResourceAddress = ResourceBuilder::new_uuid_non_fungible()
+                .metadata("name", "Lending Market Flash Loan Receipt")
+                .mintable(rule!(require(minter_badge.resource_address())), LOCKED)
+                .burnable(rule!(require(minter_badge.resource_address())), LOCKED)
+                .restrict_deposit(rule!(deny_all), LOCKED)
+                .create_with_no_initial_supply();
+
+            let access_rules: AccessRules = AccessRules::new()
+                .method("list_asset", rule!(require(admin_badge.resource_address())), LOCKED)
+                .method("collect_reserves", rule!(require(admin_badge.resource_address())), LOCKED)
+                .method("set_insurance_fund", rule!(require(admin_badge.resource_address())), LOCKED)
+                .method("set_liquidation_params", rule!(require(admin_badge.resource_address())), LOCKED)
+                .default(rule!(allow_all), LOCKED);
+
+            let mut component = Self {
+                quote,
+                assets: HashMap::new(),
+                pools: HashMap::new(),
+                collateral_vaults: HashMap::new(),
+                position_badge_address,
+                positions: HashMap::new(),
+                minter_badge: Vault::with_bucket(minter_badge),
+                insurance_fund: None,
+                flash_loan_receipt_address,
+                flash_loan_fee: dec!("0.0009"),
+                close_factor: dec!("0.5"),
+                liquidation_bonus: dec!("0.05"),
+            }
+            .instantiate();
+            component.add_access_check(access_rules);
+
+            (component.globalize(), admin_badge)
+        }
+
+        /// Lists a new asset which can be lent, borrowed and posted as collateral.
+        /// The borrow rate grows linearly from `base_rate` at zero utilization to
+        /// `base_rate + slope` at full utilization.
+        pub fn list_asset(
+            &mut self,
+            asset: ResourceAddress,
+            price_source: ComponentAddress,
+            ltv: Decimal,
+            base_rate: Decimal,
+            slope: Decimal,
+            reserve_factor: Decimal,
+        ) {
+            assert!(!self.assets.contains_key(&asset), "Asset is already listed");
+            assert!(ltv >= Decimal::zero() && ltv < Decimal::one(), "Invalid loan to value");
+            assert!(base_rate >= Decimal::zero() && slope >= Decimal::zero(), "Invalid rates");
+            assert!(
+                reserve_factor >= Decimal::zero() && reserve_factor <= Decimal::one(),
+                "Invalid reserve factor"
+            );
+
+            let symbol: String = borrow_resource_manager!(asset)
+                .get_metadata("symbol")
+                .unwrap_or_else(|| "TKN".to_owned());
+            let receipt_token: ResourceAddress = ResourceBuilder::new_fungible()
+                .divisibility(DIVISIBILITY_MAXIMUM)
+                .metadata("name", format!("Lending Receipt {}", symbol))
+                .metadata("symbol", format!("l{}", symbol))
+                .mintable(rule!(require(self.minter_badge.resource_address())), LOCKED)
+                .burnable(rule!(require(self.minter_badge.resource_address())), LOCKED)
+                .create_with_no_initial_supply();
+
+            self.assets.insert(
+                asset,
+                AssetState {
+                    receipt_token,
+                    price_source,
+                    ltv,
+                    base_rate,
+                    slope,
+                    reserve_factor,
+                    total_borrows: Decimal::zero(),
+                    total_reserves: Decimal::zero(),
+                    borrow_index: Decimal::one(),
+                    last_accrual: Self::now(),
+                    flash_loaned: Decimal::zero(),
+                },
+            );
+            self.pools.insert(asset, Vault::new(asset));
+            self.collateral_vaults.insert(asset, Vault::new(asset));
+        }
+
+        /// Lends tokens to the market in exchange for receipt tokens. Receipt
+        /// tokens are redeemed at an exchange rate which grows as interest accrues.
+        pub fn deposit(&mut self, tokens: Bucket) -> Bucket {
+            let asset: ResourceAddress = tokens.resource_address();
+            self.accrue_interest(asset);
+
+            let exchange_rate: Decimal = self.exchange_rate(asset);
+            let receipt_amount: Decimal = tokens.amount() / exchange_rate;
+            self.pools.get_mut(&asset).unwrap().put(tokens);
+
+            let receipt_token: ResourceAddress = self.assets[&asset].receipt_token;
+            self.minter_badge
+                .authorize(|| borrow_resource_manager!(receipt_token).mint(receipt_amount))
+        }
+
+        /// Burns receipt tokens and returns the tokens they are worth.
+        pub fn redeem(&mut self, receipt: Bucket) -> Bucket {
+            let asset: ResourceAddress = self
+                .assets
+                .iter()
+                .find(|(_, state)| state.receipt_token == receipt.resource_address())
+                .map(|(asset, _)| *asset)
+                .expect("Not a receipt token of this market");
+            self.accrue_interest(asset);
+
+            let amount: Decimal = receipt.amount() * self.exchange_rate(asset);
+            assert!(amount <= self.pools[&asset].amount(), "Not enough liquidity in the market");
+
+            self.minter_badge.authorize(|| receipt.burn());
+            self.pools.get_mut(&asset).unwrap().take(amount)
+        }
+
+        /// Opens an empty borrowing position and returns the NFT controlling it.
+        pub fn open_position(&mut self) -> Bucket {
+            let badge: Bucket = self.minter_badge.authorize(|| {
+                borrow_resource_manager!(self.position_badge_address)
+                    .mint_uuid_non_fungible(PositionBadge { opened_at: Self::now() })
+            });
+            self.positions
+                .insert(badge.non_fungible_local_id(), Position::default());
+            badge
+        }
+
+        /// Adds collateral to a position.
+        pub fn deposit_collateral(&mut self, position_badge: Proof, collateral: Bucket) {
+            let id: NonFungibleLocalId = self.position_id(position_badge);
+            let asset: ResourceAddress = collateral.resource_address();
+            assert!(self.assets.contains_key(&asset), "Asset is not listed");
+
+            let position: &mut Position = self.positions.get_mut(&id).unwrap();
+            *position.collateral.entry(asset).or_insert(Decimal::zero()) += collateral.amount();
+            self.collateral_vaults.get_mut(&asset).unwrap().put(collateral);
+        }
+
+        /// Removes collateral from a position. Fails if the remaining collateral
+        /// does not cover the debts of the position.
+        pub fn withdraw_collateral(
+            &mut self,
+            position_badge: Proof,
+            asset: ResourceAddress,
+            amount: Decimal,
+        ) -> Bucket {
+            let id: NonFungibleLocalId = self.position_id(position_badge);
+            self.accrue_all();
+
+            let position: &mut Position = self.positions.get_mut(&id).unwrap();
+            let posted: &mut Decimal = position
+                .collateral
+                .get_mut(&asset)
+                .expect("No collateral of this asset in the position");
+            assert!(amount <= *posted, "Not enough collateral in the position");
+            *posted -= amount;
+
+            self.assert_healthy(&id);
+            self.collateral_vaults.get_mut(&asset).unwrap().take(amount)
+        }
+
+        /// Borrows tokens against the collateral of a position. Fails if the debts
+        /// would exceed the loan to value limits of the collateral.
+        pub fn borrow(
+            &mut self,
+            position_badge: Proof,
+            asset: ResourceAddress,
+            amount: Decimal,
+        ) -> Bucket {
+            let id: NonFungibleLocalId = self.position_id(position_badge);
+            self.accrue_all();
+            assert!(amount > Decimal::zero(), "Nothing to borrow");
+            assert!(
+                amount <= self.pools[&asset].amount(),
+                "Not enough liquidity in the market"
+            );
+
+            let state: &mut AssetState = self.assets.get_mut(&asset).unwrap();
+            state.total_borrows += amount;
+            let scaled: Decimal = amount / state.borrow_index;
+
+            let position: &mut Position = self.positions.get_mut(&id).unwrap();
+            *position.scaled_debt.entry(asset).or_insert(Decimal::zero()) += scaled;
+
+            self.assert_healthy(&id);
+            self.pools.get_mut(&asset).unwrap().take(amount)
+        }
+
+        /// Repays the debt of a position in the given token. Anything above the
+        /// debt is returned.
+        pub fn repay(&mut self, position_badge: Proof, mut repayment: Bucket) -> Bucket {
+            let id: NonFungibleLocalId = self.position_id(position_badge);
+            let asset: ResourceAddress = repayment.resource_address();
+            self.accrue_interest(asset);
+
+            let state: &mut AssetState = self.assets.get_mut(&asset).expect("Asset is not listed");
+            let position: &mut Position = self.positions.get_mut(&id).unwrap();
+            let scaled_debt: &mut Decimal = position
+                .scaled_debt
+                .get_mut(&asset)
+                .expect("No debt of this asset in the position");
+
+            let owed: Decimal = *scaled_debt * state.borrow_index;
+            let paid: Decimal = std::cmp::min(owed, repayment.amount());
+            *scaled_debt -= paid / state.borrow_index;
+            state.total_borrows -= std::cmp::min(paid, state.total_borrows);
+
+            self.pools.get_mut(&asset).unwrap().put(repayment.take(paid));
+            repayment
+        }
+
+        /// Repays up to `close_factor` of the debt of an unhealthy position in the
+        /// token of `repayment`, and seizes collateral of `collateral_asset` worth
+        /// the repaid value plus `liquidation_bonus`. A position is unhealthy once
+        /// its debts exceed the loan to value limits of its collateral. When the
+        /// collateral of that asset is not enough, only the debt it covers is
+        /// repaid. Returns the seized collateral and the change.
+        pub fn liquidate(
+            &mut self,
+            id: NonFungibleLocalId,
+            mut repayment: Bucket,
+            collateral_asset: ResourceAddress,
+        ) -> (Bucket, Bucket) {
+            self.accrue_all();
+            let (borrow_limit, debt) = self.position_values(id.clone());
+            assert!(debt > borrow_limit, "Position is healthy");
+
+            let asset: ResourceAddress = repayment.resource_address();
+            let debt_price: Decimal = self.price(asset);
+            let collateral_price: Decimal = self.price(collateral_asset);
+            let bonus: Decimal = Decimal::one() + self.liquidation_bonus;
+
+            let state: &mut AssetState = self.assets.get_mut(&asset).expect("Asset is not listed");
+            let position: &mut Position = self.positions.get_mut(&id).unwrap();
+            let scaled_debt: &mut Decimal = position
+                .scaled_debt
+                .get_mut(&asset)
+                .expect("No debt of this asset in the position");
+            let posted: &mut Decimal = position
+                .collateral
+                .get_mut(&collateral_asset)
+                .expect("No collateral of this asset in the position");
+
+            let owed: Decimal = *scaled_debt * state.borrow_index;
+            let mut repaid: Decimal = std::cmp::min(owed * self.close_factor, repayment.amount());
+            let mut seized: Decimal = repaid * debt_price * bonus / collateral_price;
+            if seized > *posted {
+                seized = *posted;
+                repaid = seized * collateral_price / (debt_price * bonus);
+            }
+
+            *scaled_debt -= repaid / state.borrow_index;
+            *posted -= seized;
+            state.total_borrows -= std::cmp::min(repaid, state.total_borrows);
+
+            self.pools.get_mut(&asset).unwrap().put(repayment.take(repaid));
+            let collateral: Bucket = self
+                .collateral_vaults
+                .get_mut(&collateral_asset)
+                .unwrap()
+                .take(seized);
+            (collateral, repayment)
+        }
+
+        /// Withdraws the reserves accumulated for an asset.
+        pub fn collect_reserves(&mut self, asset: ResourceAddress) -> Bucket {
+            self.accrue_interest(asset);
+            let state: &mut AssetState = self.assets.get_mut(&asset).expect("Asset is not listed");
+            let amount: Decimal = std::cmp::min(state.total_reserves, self.pools[&asset].amount());
+            state.total_reserves -= amount;
+            self.pools.get_mut(&asset).unwrap().take(amount)
+        }
+
+        /// Lends tokens which are not borrowed for the duration of the transaction.
+        /// The returned receipt can't be deposited, so the transaction only
+        /// succeeds if `repay_flash_loan` burns it.
+        pub fn flash_loan(&mut self, asset: ResourceAddress, amount: Decimal) -> (Bucket, Bucket) {
+            let pool: &mut Vault = self.pools.get_mut(&asset).expect("Asset is not listed");
+            assert!(
+                amount > Decimal::zero() && amount <= pool.amount(),
+                "Invalid flash loan amount"
+            );
+            let loan: Bucket = pool.take(amount);
+
+            let state: &mut AssetState = self.assets.get_mut(&asset).unwrap();
+            state.flash_loaned += amount;
+
+            let fee: Decimal = amount * self.flash_loan_fee;
+            let receipt: Bucket = self.minter_badge.authorize(|| {
+                borrow_resource_manager!(self.flash_loan_receipt_address)
+                    .mint_uuid_non_fungible(FlashLoanReceipt { resource: asset, amount, fee })
+            });
+
+            (loan, receipt)
+        }
+
+        /// Takes back a flash loan plus its fee and burns the receipt. The fee goes
+        /// to the lenders. Anything above what is owed is returned.
+        pub fn repay_flash_loan(&mut self, mut repayment: Bucket, receipt: Bucket) -> Bucket {
+            assert!(
+                receipt.resource_address() == self.flash_loan_receipt_address,
+                "Invalid flash loan receipt"
+            );
+            let terms: FlashLoanReceipt = receipt.non_fungible::<FlashLoanReceipt>().data();
+            assert!(repayment.resource_address() == terms.resource, "Wrong token provided");
+            assert!(
+                repayment.amount() >= terms.amount + terms.fee,
+                "Flash loan was not repaid in full"
+            );
+
+            let state: &mut AssetState = self.assets.get_mut(&terms.resource).unwrap();
+            state.flash_loaned -= terms.amount;
+            self.pools
+                .get_mut(&terms.resource)
+                .unwrap()
+                .put(repayment.take(terms.amount + terms.fee));
+
+            self.minter_badge.authorize(|| receipt.burn());
+            repayment
+        }
+
+        /// Sets the insurance fund the reserves are swept to.
+        pub fn set_insurance_fund(&mut self, insurance_fund: ComponentAddress) {
+            self.insurance_fund = Some(insurance_fund);
+        }
+
+        /// Changes the share of a debt a liquidation can repay and the bonus paid
+        /// to liquidators.
+        pub fn set_liquidation_params(&mut self, close_factor: Decimal, liquidation_bonus: Decimal) {
+            assert!(
+                close_factor > Decimal::zero() && close_factor <= Decimal::one(),
+                "Invalid close factor"
+            );
+            assert!(liquidation_bonus >= Decimal::zero(), "Invalid liquidation bonus");
+            self.close_factor = close_factor;
+            self.liquidation_bonus = liquidation_bonus;
+        }
+
+        /// Sends the reserves accumulated for an asset to the insurance fund. Can
+        /// be called by anyone once a fund is set.
+        pub fn sweep_reserves_to_insurance(&mut self, asset: ResourceAddress) {
+            let insurance_fund: ComponentAddress =
+                self.insurance_fund.expect("No insurance fund is set");
+            let reserves: Bucket = self.collect_reserves(asset);
+            InsuranceFundComponentTarget::at(insurance_fund).deposit_fees(reserves);
+        }
+
+        /// Returns the amount of tokens one receipt token of `asset` is worth,
+        /// including interest accrued up to the last accrual.
+        pub fn exchange_rate(&self, asset: ResourceAddress) -> Decimal {
+            let state: &AssetState = self.assets.get(&asset).expect("Asset is not listed");
+            let supply: Decimal = borrow_resource_manager!(state.receipt_token).total_supply();
+            if supply == Decimal::zero() {
+                return Decimal::one();
+            }
+            (self.pools[&asset].amount() + state.flash_loaned + state.total_borrows - state.total_reserves)
+                / supply
+        }
+
+        /// Returns the share of the lent tokens of `asset` which are borrowed.
+        pub fn utilization(&self, asset: ResourceAddress) -> Decimal {
+            let state: &AssetState = self.assets.get(&asset).expect("Asset is not listed");
+            let supplied: Decimal =
+                self.pools[&asset].amount() + state.flash_loaned + state.total_borrows;
+            if supplied == Decimal::zero() {
+                Decimal::zero()
+            } else {
+                state.total_borrows / supplied
+            }
+        }
+
+        /// Returns the yearly rate paid by borrowers of `asset`.
+        pub fn borrow_rate(&self, asset: ResourceAddress) -> Decimal {
+            let state: &AssetState = self.assets.get(&asset).expect("Asset is not listed");
+            state.base_rate + state.slope * self.utilization(asset)
+        }
+
+        /// Returns the yearly rate earned by lenders of `asset`.
+        pub fn supply_rate(&self, asset: ResourceAddress) -> Decimal {
+            let state: &AssetState = self.assets.get(&asset).expect("Asset is not listed");
+            self.borrow_rate(asset) * self.utilization(asset) * (Decimal::one() - state.reserve_factor)
+        }
+
+        /// Returns the state of an asset.
+        pub fn asset_state(&self, asset: ResourceAddress) -> AssetState {
+            self.assets.get(&asset).expect("Asset is not listed").clone()
+        }
+
+        /// Returns the collateral and scaled debts of a position.
+        pub fn position(&self, id: NonFungibleLocalId) -> Position {
+            self.positions.get(&id).expect("Unknown position").clone()
+        }
+
+        /// Returns the value in quote tokens of the collateral of a position
+        /// weighted by the loan to value of each asset, and the value of its debts.
+        pub fn position_values(&self, id: NonFungibleLocalId) -> (Decimal, Decimal) {
+            let position: &Position = self.positions.get(&id).expect("Unknown position");
+
+            let mut borrow_limit: Decimal = Decimal::zero();
+            for (asset, amount) in position.collateral.iter() {
+                borrow_limit += *amount * self.price(*asset) * self.assets[asset].ltv;
+            }
+
+            let mut debt: Decimal = Decimal::zero();
+            for (asset, scaled) in position.scaled_debt.iter() {
+                debt += *scaled * self.assets[asset].borrow_index * self.price(*asset);
+            }
+
+            (borrow_limit, debt)
+        }
+
+        /// Accrues the interest of an asset since the last accrual. Interest is
+        /// compounded each time the asset is touched.
+        fn accrue_interest(&mut self, asset: ResourceAddress) {
+            let now: i64 = Self::now();
+            let borrow_rate: Decimal = self.borrow_rate(asset);
+            let state: &mut AssetState = self.assets.get_mut(&asset).expect("Asset is not listed");
+
+            let elapsed: i64 = now - state.last_accrual;
+            if elapsed <= 0 {
+                return;
+            }
+
+            let factor: Decimal =
+                borrow_rate * Decimal::from(elapsed) / Decimal::from(SECONDS_PER_YEAR);
+            let interest: Decimal = state.total_borrows * factor;
+            state.total_borrows += interest;
+            state.total_reserves += interest * state.reserve_factor;
+            state.borrow_index += state.borrow_index * factor;
+            state.last_accrual = now;
+        }
+
+        fn accrue_all(&mut self) {
+            let assets: Vec<ResourceAddress> = self.assets.keys().copied().collect();
+            for asset in assets {
+                self.accrue_interest(asset);
+            }
+        }
+
+        fn assert_healthy(&self, id: &NonFungibleLocalId) {
+            let (borrow_limit, debt) = self.position_values(id.clone());
+            assert!(debt <= borrow_limit, "Position would exceed its loan to value limit");
+        }
+
+        fn price(&self, asset: ResourceAddress) -> Decimal {
+            if asset == self.quote {
+                Decimal::one()
+            } else {
+                PriceSourceComponentTarget::at(self.assets[&asset].price_source).price(asset)
+            }
+        }
+
+        fn position_id(&self, position_badge: Proof) -> NonFungibleLocalId {
+            position_badge
+                .validate_proof(self.position_badge_address)
+                .expect("Invalid position badge")
+                .non_fungible_local_id()
+        }
+
+        fn now() -> i64 {
+            Clock::current_time_rounded_to_minutes().seconds_since_unix_epoch
+        }
+    }
+}
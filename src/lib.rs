@@ -1,2 +1,18 @@
+mod circuit_breaker;
 mod constantsum;
+mod emission_controller;
+mod factory;
+mod insurance_fund;
+mod jump_rate_model;
+mod lending_market;
+mod linear_rate_model;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod manifests;
+mod multisig;
+mod oracle;
+mod pid_rate_model;
+mod price_source;
 mod radiswap;
+mod rate_model;
+mod staking_rewards;
+mod vote_escrow;
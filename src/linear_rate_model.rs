@@ -0,0 +1,42 @@
+use scrypto::prelude::*;
+use crate::rate_model::*;
+
+#[blueprint]
+mod linear_rate_model {
+    struct LinearRateModel {
+        // Yearly rate at zero utilization
+        base_rate: Decimal,
+        // Yearly rate added at full utilization
+        slope: Decimal,
+        // Lowest yearly rate quoted
+        min_rate: Decimal,
+        // Highest yearly rate quoted
+        max_rate: Decimal,
+    }
+
+    impl LinearRateModel {
+        // The rate grows linearly from `base_rate` at zero utilization to `base_rate + slope` at full
+        // utilization. Returns the component and the updater badge to hand to the pool.
+        pub fn instantiate_linear_rate_model(base_rate: Decimal, slope: Decimal, min_rate: Decimal,
+        max_rate: Decimal) -> (ComponentAddress, Bucket) {
+            assert!(min_rate >= Decimal::zero() && min_rate <= max_rate, "Invalid rate bounds");
+
+            let component = Self { base_rate, slope, min_rate, max_rate }
+                .instantiate()
+                .globalize();
+
+            (component, new_updater_badge())
+        }
+
+        pub fn borrow_rate(&self, utilization: Decimal) -> Decimal {
+            clamp_rate(self.base_rate + self.slope * utilization, self.min_rate, self.max_rate)
+        }
+
+        pub fn rate_bounds(&self) -> (Decimal, Decimal) {
+            (self.min_rate, self.max_rate)
+        }
+
+        // The linear model is stateless
+        pub fn update(&mut self, _utilization: Decimal) {}
+    }
+}
@@ -0,0 +1,796 @@
+use scrypto::prelude::*;
+use transaction::builder::ManifestBuilder;
+use transaction::model::TransactionManifest;
+
+// Typed builders for the manifests calling the blueprints of this package, shared by the tests and the
+// native tools. Every manifest locks its fee from `account`, withdraws the buckets it passes from
+// `account`, and deposits whatever is left on the worktop back into `account`.
+
+// What a queued Multisig transaction does, for `queue`
+pub use crate::multisig::Action as MultisigAction;
+
+// Fee locked by every manifest, as in the `rtm/*.rtm` files
+pub fn fee() -> Decimal {
+    dec!(100)
+}
+
+// Arguments of `ConstantSumAmm::locking_liquidity`
+#[derive(Debug, Clone)]
+pub struct LockingLiquidity {
+    pub token_a: ResourceAddress,
+    pub amount_a: Decimal,
+    pub token_a_name: String,
+    pub token_a_symbol: String,
+    pub token_b: ResourceAddress,
+    pub amount_b: Decimal,
+    pub token_b_name: String,
+    pub token_b_symbol: String,
+    pub maturity: i64,
+    pub required_interest: Decimal,
+    pub strike_rate: Decimal,
+    pub lp_name: String,
+    pub lp_symbol: String,
+    pub amm_address: ComponentAddress,
+    pub price_source: ComponentAddress,
+    pub rate_model: ComponentAddress,
+    pub rate_model_badge: ResourceAddress,
+    pub insurance_fund: ComponentAddress,
+    pub insurance_badge: ResourceAddress,
+}
+
+// Arguments of `MarketFactory::create_market`
+#[derive(Debug, Clone)]
+pub struct CreateMarket {
+    pub token_a: ResourceAddress,
+    pub amount_a: Decimal,
+    pub token_b: ResourceAddress,
+    pub amount_b: Decimal,
+    pub maturity: i64,
+    pub required_interest: Decimal,
+    pub strike_rate: Decimal,
+    pub amm_address: ComponentAddress,
+    pub price_source: ComponentAddress,
+    pub rate_model: ComponentAddress,
+    pub rate_model_badge: ResourceAddress,
+    pub insurance_fund: ComponentAddress,
+    pub insurance_badge: ResourceAddress,
+}
+
+// Takes `amount` of each resource from the worktop, in order, and hands the buckets to `then`
+fn take_buckets<'a, F>(builder: &'a mut ManifestBuilder, resources: &[(ResourceAddress, Decimal)],
+mut buckets: Vec<ManifestBucket>, then: F) -> &'a mut ManifestBuilder
+where
+    F: FnOnce(&mut ManifestBuilder, Vec<ManifestBucket>) -> &mut ManifestBuilder,
+{
+    match resources.split_first() {
+        None => then(builder, buckets),
+        Some((&(resource, amount), rest)) => builder.take_from_worktop_by_amount(amount, resource, move |builder, bucket| {
+            buckets.push(bucket);
+            take_buckets(builder, rest, buckets, then)
+        }),
+    }
+}
+
+// Withdraws the resources from `account`, passes them as buckets to `then`, and deposits the worktop back
+fn with_buckets<F>(account: ComponentAddress, resources: &[(ResourceAddress, Decimal)], then: F) -> TransactionManifest
+where
+    F: FnOnce(&mut ManifestBuilder, Vec<ManifestBucket>) -> &mut ManifestBuilder,
+{
+    let mut builder = ManifestBuilder::new();
+    builder.lock_fee(account, fee());
+    for &(resource, amount) in resources {
+        builder.withdraw_from_account_by_amount(account, amount, resource);
+    }
+    take_buckets(&mut builder, resources, Vec::new(), then);
+    deposit_all(&mut builder, account).build()
+}
+
+fn deposit_all(builder: &mut ManifestBuilder, account: ComponentAddress) -> &mut ManifestBuilder {
+    builder.call_method(account, "deposit_batch", args!(ManifestExpression::EntireWorktop))
+}
+
+// Calls any function of the package which takes no buckets
+pub fn call_function(package: PackageAddress, account: ComponentAddress, blueprint: &str, function: &str,
+args: Vec<u8>) -> TransactionManifest {
+    with_buckets(account, &[], |builder, _| builder.call_function(package, blueprint, function, args))
+}
+
+// Calls any method which takes no buckets, e.g. the read-only ones. The method returns at index 1 of the outputs.
+pub fn call_method(account: ComponentAddress, component: ComponentAddress, method: &str,
+args: Vec<u8>) -> TransactionManifest {
+    with_buckets(account, &[], |builder, _| builder.call_method(component, method, args))
+}
+
+// Calls a method protected by `badge`, with a proof of the badge in the auth zone
+pub fn call_method_with_badge(account: ComponentAddress, component: ComponentAddress, method: &str,
+badge: ResourceAddress, args: Vec<u8>) -> TransactionManifest {
+    with_buckets(account, &[], |builder, _| {
+        builder
+            .create_proof_from_account(account, badge)
+            .call_method(component, method, args)
+    })
+}
+
+// Withdraws the buckets as `with_buckets` does and hands `then` a proof of the non fungible `id` of `badge` as well
+fn with_badge_proof<F>(account: ComponentAddress, badge: ResourceAddress, id: NonFungibleLocalId,
+resources: &[(ResourceAddress, Decimal)], then: F) -> TransactionManifest
+where
+    F: FnOnce(&mut ManifestBuilder, ManifestProof, Vec<ManifestBucket>) -> &mut ManifestBuilder,
+{
+    with_buckets(account, resources, |builder, buckets| {
+        builder
+            .create_proof_from_account_by_ids(account, &BTreeSet::from([id]), badge)
+            .pop_from_auth_zone(|builder, proof| then(builder, proof, buckets))
+    })
+}
+
+// Radiswap
+
+pub fn instantiate_radiswap(package: PackageAddress, account: ComponentAddress, token_a: ResourceAddress,
+amount_a: Decimal, token_b: ResourceAddress, amount_b: Decimal, fee: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(token_a, amount_a), (token_b, amount_b)], |builder, buckets| {
+        builder.call_function(package, "Radiswap", "instantiate_radiswap", args!(buckets[0], buckets[1], fee))
+    })
+}
+
+// Instantiates a pool sending `protocol_fee_share` of the swap fees to `insurance_fund`
+pub fn instantiate_radiswap_with_insurance(package: PackageAddress, account: ComponentAddress,
+token_a: ResourceAddress, amount_a: Decimal, token_b: ResourceAddress, amount_b: Decimal, fee: Decimal,
+insurance_fund: Option<ComponentAddress>, protocol_fee_share: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(token_a, amount_a), (token_b, amount_b)], |builder, buckets| {
+        builder.call_function(package, "Radiswap", "instantiate_radiswap_with_insurance",
+            args!(buckets[0], buckets[1], fee, insurance_fund, protocol_fee_share))
+    })
+}
+
+pub fn swap(account: ComponentAddress, radiswap: ComponentAddress, input: ResourceAddress,
+amount: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(input, amount)], |builder, buckets| {
+        builder.call_method(radiswap, "swap", args!(buckets[0]))
+    })
+}
+
+pub fn add_liquidity(account: ComponentAddress, radiswap: ComponentAddress, token_a: ResourceAddress,
+amount_a: Decimal, token_b: ResourceAddress, amount_b: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(token_a, amount_a), (token_b, amount_b)], |builder, buckets| {
+        builder.call_method(radiswap, "add_liquidity", args!(buckets[0], buckets[1]))
+    })
+}
+
+pub fn remove_liquidity(account: ComponentAddress, radiswap: ComponentAddress, pool_units: ResourceAddress,
+amount: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(pool_units, amount)], |builder, buckets| {
+        builder.call_method(radiswap, "remove_liquidity", args!(buckets[0]))
+    })
+}
+
+pub fn set_fee(account: ComponentAddress, radiswap: ComponentAddress, admin_badge: ResourceAddress,
+fee: Decimal) -> TransactionManifest {
+    call_method_with_badge(account, radiswap, "set_fee", admin_badge, args!(fee))
+}
+
+pub fn set_insurance_fund(account: ComponentAddress, radiswap: ComponentAddress, admin_badge: ResourceAddress,
+insurance_fund: Option<ComponentAddress>, protocol_fee_share: Decimal) -> TransactionManifest {
+    call_method_with_badge(account, radiswap, "set_insurance_fund", admin_badge,
+        args!(insurance_fund, protocol_fee_share))
+}
+
+// Sets the limits of the circuit breaker of a Radiswap pool or a ConstantSumAmm
+pub fn set_circuit_breaker(account: ComponentAddress, component: ComponentAddress, admin_badge: ResourceAddress,
+max_move: Decimal, window: i64, max_reference_deviation: Decimal, cooldown: i64,
+guardian_badge: ResourceAddress) -> TransactionManifest {
+    call_method_with_badge(account, component, "set_circuit_breaker", admin_badge,
+        args!(max_move, window, max_reference_deviation, cooldown, guardian_badge))
+}
+
+// Resumes trading on a Radiswap pool or a ConstantSumAmm halted by its circuit breaker
+pub fn resume_trading(account: ComponentAddress, component: ComponentAddress,
+guardian_badge: ResourceAddress) -> TransactionManifest {
+    with_buckets(account, &[], |builder, _| {
+        builder
+            .create_proof_from_account(account, guardian_badge)
+            .pop_from_auth_zone(|builder, proof| builder.call_method(component, "resume_trading", args!(proof)))
+    })
+}
+
+// ConstantSumAmm
+
+pub fn locking_liquidity(package: PackageAddress, account: ComponentAddress,
+params: &LockingLiquidity) -> TransactionManifest {
+    let resources = [
+        (params.token_a, params.amount_a),
+        (params.token_b, params.amount_b),
+        (params.rate_model_badge, dec!(1)),
+        (params.insurance_badge, dec!(1)),
+    ];
+    with_buckets(account, &resources, |builder, buckets| {
+        builder.call_function(package, "ConstantSumAmm", "locking_liquidity", args!(
+            buckets[0], params.token_a_name.clone(), params.token_a_symbol.clone(),
+            buckets[1], params.token_b_name.clone(), params.token_b_symbol.clone(),
+            params.maturity, params.required_interest, params.strike_rate,
+            params.lp_name.clone(), params.lp_symbol.clone(),
+            params.amm_address, params.price_source,
+            params.rate_model, buckets[2],
+            params.insurance_fund, buckets[3]
+        ))
+    })
+}
+
+pub fn deposit_liquidity_a(account: ComponentAddress, market: ComponentAddress, cctoken_a: ResourceAddress,
+cc_amount: Decimal, bond_token: ResourceAddress, bond_amount: Decimal, strike_rate: Decimal,
+maturity: i64) -> TransactionManifest {
+    with_buckets(account, &[(cctoken_a, cc_amount), (bond_token, bond_amount)], |builder, buckets| {
+        builder.call_method(market, "deposit_liquidity_a", args!(buckets[0], buckets[1], strike_rate, maturity))
+    })
+}
+
+pub fn deposit_liquidity_b(account: ComponentAddress, market: ComponentAddress, cctoken_b: ResourceAddress,
+cc_amount: Decimal, bond_token: ResourceAddress, bond_amount: Decimal, strike_rate: Decimal,
+maturity: i64) -> TransactionManifest {
+    with_buckets(account, &[(cctoken_b, cc_amount), (bond_token, bond_amount)], |builder, buckets| {
+        builder.call_method(market, "deposit_liquidity_b", args!(buckets[0], buckets[1], strike_rate, maturity))
+    })
+}
+
+pub fn withdraw_liquidity(account: ComponentAddress, market: ComponentAddress, lp_token: ResourceAddress,
+amount: Decimal, strike_rate: Decimal, maturity: i64) -> TransactionManifest {
+    with_buckets(account, &[(lp_token, amount)], |builder, buckets| {
+        builder.call_method(market, "withdraw_liquidity", args!(buckets[0], strike_rate, maturity))
+    })
+}
+
+// Lends token_b when the market price is above the strike rate
+pub fn lend_a(account: ComponentAddress, market: ComponentAddress, token_b: ResourceAddress,
+amount: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(token_b, amount)], |builder, buckets| {
+        builder.call_method(market, "lend_a", args!(buckets[0]))
+    })
+}
+
+// Lends token_a when the market price is below the strike rate
+pub fn lend_b(account: ComponentAddress, market: ComponentAddress, token_a: ResourceAddress,
+amount: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(token_a, amount)], |builder, buckets| {
+        builder.call_method(market, "lend_b", args!(buckets[0]))
+    })
+}
+
+// Borrows token_b against token_a collateral, converting cctoken_b
+pub fn borrow_a(account: ComponentAddress, market: ComponentAddress, borrow_amount: Decimal,
+token_a: ResourceAddress, collateral: Decimal, cctoken_b: ResourceAddress, cc_amount: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(token_a, collateral), (cctoken_b, cc_amount)], |builder, buckets| {
+        builder.call_method(market, "borrow_a", args!(borrow_amount, buckets[0], buckets[1]))
+    })
+}
+
+pub fn repay_a(account: ComponentAddress, market: ComponentAddress, loan_badge: ResourceAddress,
+loan_id: NonFungibleLocalId, token_b: ResourceAddress, amount: Decimal) -> TransactionManifest {
+    with_badge_proof(account, loan_badge, loan_id, &[(token_b, amount)], |builder, proof, buckets| {
+        builder.call_method(market, "repay_a", args!(proof, buckets[0]))
+    })
+}
+
+pub fn liquidate(account: ComponentAddress, market: ComponentAddress, loan_id: NonFungibleLocalId,
+token_b: ResourceAddress, amount: Decimal, swap_collateral: bool) -> TransactionManifest {
+    with_buckets(account, &[(token_b, amount)], |builder, buckets| {
+        builder.call_method(market, "liquidate", args!(loan_id, buckets[0], swap_collateral))
+    })
+}
+
+pub fn forfeit_loan(account: ComponentAddress, market: ComponentAddress,
+loan_id: NonFungibleLocalId) -> TransactionManifest {
+    call_method(account, market, "forfeit_loan", args!(loan_id))
+}
+
+pub fn bid(account: ComponentAddress, market: ComponentAddress, lot_id: NonFungibleLocalId,
+token_b: ResourceAddress, amount: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(token_b, amount)], |builder, buckets| {
+        builder.call_method(market, "bid", args!(lot_id, buckets[0]))
+    })
+}
+
+// Rebalances with `amount` of `collateral` (token_a or token_b), failing below `min_output`
+pub fn rebalance_transaction(account: ComponentAddress, market: ComponentAddress, collateral: ResourceAddress,
+amount: Decimal, min_output: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(collateral, amount)], |builder, buckets| {
+        builder.call_method(market, "rebalance_transaction", args!(buckets[0], min_output))
+    })
+}
+
+pub fn option_a_withdraw(account: ComponentAddress, market: ComponentAddress, token_a: ResourceAddress,
+cctoken_a: ResourceAddress, cc_amount: Decimal, bond_token: ResourceAddress, bond_amount: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(cctoken_a, cc_amount), (bond_token, bond_amount)], |builder, buckets| {
+        builder.call_method(market, "option_a_withdraw", args!(token_a, buckets[0], buckets[1]))
+    })
+}
+
+pub fn option_b_withdraw(account: ComponentAddress, market: ComponentAddress, token_b: ResourceAddress,
+cctoken_b: ResourceAddress, cc_amount: Decimal, bond_token: ResourceAddress, bond_amount: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(cctoken_b, cc_amount), (bond_token, bond_amount)], |builder, buckets| {
+        builder.call_method(market, "option_b_withdraw", args!(token_b, buckets[0], buckets[1]))
+    })
+}
+
+// Locks token_a for cctoken_a and bond tokens
+pub fn option_a_deposit(account: ComponentAddress, market: ComponentAddress, token_a: ResourceAddress,
+amount: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(token_a, amount)], |builder, buckets| {
+        builder.call_method(market, "option_a_deposit", args!(buckets[0]))
+    })
+}
+
+// Locks token_b for cctoken_b and bond tokens
+pub fn option_b_deposit(account: ComponentAddress, market: ComponentAddress, token_b: ResourceAddress,
+amount: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(token_b, amount)], |builder, buckets| {
+        builder.call_method(market, "option_b_deposit", args!(buckets[0]))
+    })
+}
+
+// Locks `amount` of `lock_token` and burns the collateral claims on the other token worth as much at the strike
+// rate, for the other token and the claims on `lock_token`
+pub fn convert_option(account: ComponentAddress, market: ComponentAddress, lock_token: ResourceAddress,
+amount: Decimal, cctoken: ResourceAddress, cc_amount: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(lock_token, amount), (cctoken, cc_amount)], |builder, buckets| {
+        builder.call_method(market, "convert_option", args!(buckets[0], buckets[1]))
+    })
+}
+
+// Takes a flash loan of `amount` of `resource` from a ConstantSumAmm or a LendingMarket, runs `then` with the
+// loan on the worktop and repays the loan with all of `resource` left on the worktop, the loan fee withdrawn
+// from `account` included
+pub fn flash_loan<F>(account: ComponentAddress, market: ComponentAddress, resource: ResourceAddress,
+amount: Decimal, loan_fee: Decimal, receipt_resource: ResourceAddress, then: F) -> TransactionManifest
+where
+    F: FnOnce(&mut ManifestBuilder) -> &mut ManifestBuilder,
+{
+    let mut builder = ManifestBuilder::new();
+    builder
+        .lock_fee(account, fee())
+        .withdraw_from_account_by_amount(account, loan_fee, resource)
+        .call_method(market, "flash_loan", args!(resource, amount));
+    then(&mut builder)
+        .take_from_worktop(resource, |builder, repayment| {
+            builder.take_from_worktop(receipt_resource, |builder, receipt| {
+                builder.call_method(market, "repay_flash_loan", args!(repayment, receipt))
+            })
+        });
+    deposit_all(&mut builder, account).build()
+}
+
+// Takes a flash loan and repays it right away, useful to check that flash loans are available
+pub fn flash_loan_roundtrip(account: ComponentAddress, market: ComponentAddress, resource: ResourceAddress,
+amount: Decimal, loan_fee: Decimal, receipt_resource: ResourceAddress) -> TransactionManifest {
+    flash_loan(account, market, resource, amount, loan_fee, receipt_resource, |builder| builder)
+}
+
+pub fn set_strike_rate(account: ComponentAddress, market: ComponentAddress, admin_badge: ResourceAddress,
+strike_rate: Decimal) -> TransactionManifest {
+    call_method_with_badge(account, market, "set_strike_rate", admin_badge, args!(strike_rate))
+}
+
+pub fn set_rebalance_params(account: ComponentAddress, market: ComponentAddress, admin_badge: ResourceAddress,
+max_price_deviation: Decimal, keeper_reward: Decimal) -> TransactionManifest {
+    call_method_with_badge(account, market, "set_rebalance_params", admin_badge,
+        args!(max_price_deviation, keeper_reward))
+}
+
+pub fn set_liquidation_params(account: ComponentAddress, market: ComponentAddress, admin_badge: ResourceAddress,
+liquidation_threshold: Decimal, close_factor: Decimal, liquidation_bonus: Decimal) -> TransactionManifest {
+    call_method_with_badge(account, market, "set_liquidation_params", admin_badge,
+        args!(liquidation_threshold, close_factor, liquidation_bonus))
+}
+
+pub fn set_auction_params(account: ComponentAddress, market: ComponentAddress, admin_badge: ResourceAddress,
+auction_mode: bool, auction_start_multiplier: Decimal, auction_floor_multiplier: Decimal,
+auction_duration: i64) -> TransactionManifest {
+    call_method_with_badge(account, market, "set_auction_params", admin_badge,
+        args!(auction_mode, auction_start_multiplier, auction_floor_multiplier, auction_duration))
+}
+
+pub fn set_fee_params(account: ComponentAddress, market: ComponentAddress, admin_badge: ResourceAddress,
+protocol_fee_share: Decimal, flash_loan_fee: Decimal) -> TransactionManifest {
+    call_method_with_badge(account, market, "set_fee_params", admin_badge, args!(protocol_fee_share, flash_loan_fee))
+}
+
+// Prices the market with `price_source`, a Radiswap pool or a PriceOracle
+pub fn set_price_source(account: ComponentAddress, market: ComponentAddress, admin_badge: ResourceAddress,
+price_source: ComponentAddress) -> TransactionManifest {
+    call_method_with_badge(account, market, "set_price_source", admin_badge, args!(price_source))
+}
+
+// Supporting blueprints
+
+pub fn instantiate_linear_rate_model(package: PackageAddress, account: ComponentAddress, base_rate: Decimal,
+slope: Decimal, min_rate: Decimal, max_rate: Decimal) -> TransactionManifest {
+    call_function(package, account, "LinearRateModel", "instantiate_linear_rate_model",
+        args!(base_rate, slope, min_rate, max_rate))
+}
+
+pub fn instantiate_jump_rate_model(package: PackageAddress, account: ComponentAddress, base_rate: Decimal,
+slope: Decimal, jump_slope: Decimal, kink: Decimal, min_rate: Decimal, max_rate: Decimal) -> TransactionManifest {
+    call_function(package, account, "JumpRateModel", "instantiate_jump_rate_model",
+        args!(base_rate, slope, jump_slope, kink, min_rate, max_rate))
+}
+
+pub fn instantiate_pid_rate_model(package: PackageAddress, account: ComponentAddress, target_utilization: Decimal,
+base_rate: Decimal, kp: Decimal, ki: Decimal, min_rate: Decimal, max_rate: Decimal) -> TransactionManifest {
+    call_function(package, account, "PidRateModel", "instantiate_pid_rate_model",
+        args!(target_utilization, base_rate, kp, ki, min_rate, max_rate))
+}
+
+pub fn instantiate_insurance_fund(package: PackageAddress, account: ComponentAddress) -> TransactionManifest {
+    call_function(package, account, "InsuranceFund", "instantiate_insurance_fund", args!())
+}
+
+pub fn deposit_fees(account: ComponentAddress, insurance_fund: ComponentAddress, resource: ResourceAddress,
+amount: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(resource, amount)], |builder, buckets| {
+        builder.call_method(insurance_fund, "deposit_fees", args!(buckets[0]))
+    })
+}
+
+pub fn issue_claimant_badge(account: ComponentAddress, insurance_fund: ComponentAddress,
+admin_badge: ResourceAddress) -> TransactionManifest {
+    call_method_with_badge(account, insurance_fund, "issue_claimant_badge", admin_badge, args!())
+}
+
+pub fn instantiate_price_oracle(package: PackageAddress, account: ComponentAddress, base: ResourceAddress,
+quote: ResourceAddress, max_staleness: i64, min_feeders: u64) -> TransactionManifest {
+    call_function(package, account, "PriceOracle", "instantiate_price_oracle",
+        args!(base, quote, max_staleness, min_feeders))
+}
+
+pub fn add_feeder(account: ComponentAddress, oracle: ComponentAddress, admin_badge: ResourceAddress,
+name: &str) -> TransactionManifest {
+    call_method_with_badge(account, oracle, "add_feeder", admin_badge, args!(name.to_owned()))
+}
+
+pub fn push_price(account: ComponentAddress, oracle: ComponentAddress, feeder_badge: ResourceAddress,
+feeder_id: NonFungibleLocalId, price: Decimal) -> TransactionManifest {
+    with_badge_proof(account, feeder_badge, feeder_id, &[], |builder, proof, _| {
+        builder.call_method(oracle, "push_price", args!(proof, price))
+    })
+}
+
+pub fn remove_feeder(account: ComponentAddress, oracle: ComponentAddress, admin_badge: ResourceAddress,
+feeder_id: NonFungibleLocalId) -> TransactionManifest {
+    call_method_with_badge(account, oracle, "remove_feeder", admin_badge, args!(feeder_id))
+}
+
+pub fn set_limits(account: ComponentAddress, oracle: ComponentAddress, admin_badge: ResourceAddress,
+max_staleness: i64, min_feeders: u64) -> TransactionManifest {
+    call_method_with_badge(account, oracle, "set_limits", admin_badge, args!(max_staleness, min_feeders))
+}
+
+pub fn instantiate_market_factory(package: PackageAddress, account: ComponentAddress) -> TransactionManifest {
+    call_function(package, account, "MarketFactory", "instantiate_market_factory", args!())
+}
+
+pub fn create_market(account: ComponentAddress, factory: ComponentAddress,
+params: &CreateMarket) -> TransactionManifest {
+    let resources = [
+        (params.token_a, params.amount_a),
+        (params.token_b, params.amount_b),
+        (params.rate_model_badge, dec!(1)),
+        (params.insurance_badge, dec!(1)),
+    ];
+    with_buckets(account, &resources, |builder, buckets| {
+        builder.call_method(factory, "create_market", args!(
+            buckets[0], buckets[1], params.maturity, params.required_interest, params.strike_rate,
+            params.amm_address, params.price_source,
+            params.rate_model, buckets[2],
+            params.insurance_fund, buckets[3]
+        ))
+    })
+}
+
+pub fn rollover_lend(account: ComponentAddress, factory: ComponentAddress, source: ComponentAddress,
+target: ComponentAddress, cctoken: ResourceAddress, cc_amount: Decimal, bond_token: ResourceAddress,
+bond_amount: Decimal, min_rate: Decimal, max_rate: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(cctoken, cc_amount), (bond_token, bond_amount)], |builder, buckets| {
+        builder.call_method(factory, "rollover_lend", args!(source, target, buckets[0], buckets[1], min_rate,
+            max_rate))
+    })
+}
+
+pub fn rollover_liquidity(account: ComponentAddress, factory: ComponentAddress, source: ComponentAddress,
+target: ComponentAddress, lp_token: ResourceAddress, lp_amount: Decimal, min_interest: Decimal,
+max_interest: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(lp_token, lp_amount)], |builder, buckets| {
+        builder.call_method(factory, "rollover_liquidity", args!(source, target, buckets[0], min_interest,
+            max_interest))
+    })
+}
+
+// LendingMarket
+
+pub fn instantiate_lending_market(package: PackageAddress, account: ComponentAddress,
+quote: ResourceAddress) -> TransactionManifest {
+    call_function(package, account, "LendingMarket", "instantiate_lending_market", args!(quote))
+}
+
+pub fn list_asset(account: ComponentAddress, lending_market: ComponentAddress, admin_badge: ResourceAddress,
+asset: ResourceAddress, price_source: ComponentAddress, ltv: Decimal, base_rate: Decimal, slope: Decimal,
+reserve_factor: Decimal) -> TransactionManifest {
+    call_method_with_badge(account, lending_market, "list_asset", admin_badge,
+        args!(asset, price_source, ltv, base_rate, slope, reserve_factor))
+}
+
+// Lends `amount` of `asset` for receipt tokens
+pub fn lending_deposit(account: ComponentAddress, lending_market: ComponentAddress, asset: ResourceAddress,
+amount: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(asset, amount)], |builder, buckets| {
+        builder.call_method(lending_market, "deposit", args!(buckets[0]))
+    })
+}
+
+pub fn open_position(account: ComponentAddress, lending_market: ComponentAddress) -> TransactionManifest {
+    call_method(account, lending_market, "open_position", args!())
+}
+
+pub fn deposit_collateral(account: ComponentAddress, lending_market: ComponentAddress,
+position_badge: ResourceAddress, position_id: NonFungibleLocalId, asset: ResourceAddress,
+amount: Decimal) -> TransactionManifest {
+    with_badge_proof(account, position_badge, position_id, &[(asset, amount)], |builder, proof, buckets| {
+        builder.call_method(lending_market, "deposit_collateral", args!(proof, buckets[0]))
+    })
+}
+
+pub fn lending_withdraw_collateral(account: ComponentAddress, lending_market: ComponentAddress,
+position_badge: ResourceAddress, position_id: NonFungibleLocalId, asset: ResourceAddress,
+amount: Decimal) -> TransactionManifest {
+    with_badge_proof(account, position_badge, position_id, &[], |builder, proof, _| {
+        builder.call_method(lending_market, "withdraw_collateral", args!(proof, asset, amount))
+    })
+}
+
+pub fn lending_borrow(account: ComponentAddress, lending_market: ComponentAddress, position_badge: ResourceAddress,
+position_id: NonFungibleLocalId, asset: ResourceAddress, amount: Decimal) -> TransactionManifest {
+    with_badge_proof(account, position_badge, position_id, &[], |builder, proof, _| {
+        builder.call_method(lending_market, "borrow", args!(proof, asset, amount))
+    })
+}
+
+// Repays up to `amount` of the debt of a position in `asset`, the change is returned
+pub fn lending_repay(account: ComponentAddress, lending_market: ComponentAddress, position_badge: ResourceAddress,
+position_id: NonFungibleLocalId, asset: ResourceAddress, amount: Decimal) -> TransactionManifest {
+    with_badge_proof(account, position_badge, position_id, &[(asset, amount)], |builder, proof, buckets| {
+        builder.call_method(lending_market, "repay", args!(proof, buckets[0]))
+    })
+}
+
+// Repays `amount` of the debt of an unhealthy position in `asset` and seizes its `collateral_asset`
+pub fn lending_liquidate(account: ComponentAddress, lending_market: ComponentAddress, position_id: NonFungibleLocalId,
+asset: ResourceAddress, amount: Decimal, collateral_asset: ResourceAddress) -> TransactionManifest {
+    with_buckets(account, &[(asset, amount)], |builder, buckets| {
+        builder.call_method(lending_market, "liquidate", args!(position_id, buckets[0], collateral_asset))
+    })
+}
+
+// Burns `amount` receipt tokens for the tokens they are worth
+pub fn lending_redeem(account: ComponentAddress, lending_market: ComponentAddress, receipt_token: ResourceAddress,
+amount: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(receipt_token, amount)], |builder, buckets| {
+        builder.call_method(lending_market, "redeem", args!(buckets[0]))
+    })
+}
+
+pub fn collect_reserves(account: ComponentAddress, lending_market: ComponentAddress, admin_badge: ResourceAddress,
+asset: ResourceAddress) -> TransactionManifest {
+    call_method_with_badge(account, lending_market, "collect_reserves", admin_badge, args!(asset))
+}
+
+pub fn sweep_reserves_to_insurance(account: ComponentAddress, lending_market: ComponentAddress,
+asset: ResourceAddress) -> TransactionManifest {
+    call_method(account, lending_market, "sweep_reserves_to_insurance", args!(asset))
+}
+
+pub fn lending_set_insurance_fund(account: ComponentAddress, lending_market: ComponentAddress,
+admin_badge: ResourceAddress, insurance_fund: ComponentAddress) -> TransactionManifest {
+    call_method_with_badge(account, lending_market, "set_insurance_fund", admin_badge, args!(insurance_fund))
+}
+
+pub fn lending_set_liquidation_params(account: ComponentAddress, lending_market: ComponentAddress,
+admin_badge: ResourceAddress, close_factor: Decimal, liquidation_bonus: Decimal) -> TransactionManifest {
+    call_method_with_badge(account, lending_market, "set_liquidation_params", admin_badge,
+        args!(close_factor, liquidation_bonus))
+}
+
+// StakingRewards
+
+pub fn instantiate_staking_rewards(package: PackageAddress, account: ComponentAddress,
+stake_resource: ResourceAddress) -> TransactionManifest {
+    call_function(package, account, "StakingRewards", "instantiate_staking_rewards", args!(stake_resource))
+}
+
+// Streams `amount` of `reward` to the stakers between `start` and `end`
+pub fn add_reward(account: ComponentAddress, staking: ComponentAddress, admin_badge: ResourceAddress,
+reward: ResourceAddress, amount: Decimal, start: i64, end: i64) -> TransactionManifest {
+    with_buckets(account, &[(reward, amount)], |builder, buckets| {
+        builder
+            .create_proof_from_account(account, admin_badge)
+            .call_method(staking, "add_reward", args!(buckets[0], start, end))
+    })
+}
+
+pub fn stake(account: ComponentAddress, staking: ComponentAddress, pool_units: ResourceAddress,
+amount: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(pool_units, amount)], |builder, buckets| {
+        builder.call_method(staking, "stake", args!(buckets[0]))
+    })
+}
+
+// Adds `amount` of `reward` to its stream and moves the end of the stream to `end`
+pub fn top_up(account: ComponentAddress, staking: ComponentAddress, admin_badge: ResourceAddress,
+reward: ResourceAddress, amount: Decimal, end: i64) -> TransactionManifest {
+    with_buckets(account, &[(reward, amount)], |builder, buckets| {
+        builder
+            .create_proof_from_account(account, admin_badge)
+            .call_method(staking, "top_up", args!(buckets[0], end))
+    })
+}
+
+pub fn add_stake(account: ComponentAddress, staking: ComponentAddress, stake_badge: ResourceAddress,
+stake_id: NonFungibleLocalId, pool_units: ResourceAddress, amount: Decimal) -> TransactionManifest {
+    with_badge_proof(account, stake_badge, stake_id, &[(pool_units, amount)], |builder, proof, buckets| {
+        builder.call_method(staking, "add_stake", args!(proof, buckets[0]))
+    })
+}
+
+pub fn unstake(account: ComponentAddress, staking: ComponentAddress, stake_badge: ResourceAddress,
+stake_id: NonFungibleLocalId, amount: Decimal) -> TransactionManifest {
+    with_badge_proof(account, stake_badge, stake_id, &[], |builder, proof, _| {
+        builder.call_method(staking, "unstake", args!(proof, amount))
+    })
+}
+
+// Claims the rewards a stake earned in every stream
+pub fn claim_rewards(account: ComponentAddress, staking: ComponentAddress, stake_badge: ResourceAddress,
+stake_id: NonFungibleLocalId) -> TransactionManifest {
+    with_badge_proof(account, stake_badge, stake_id, &[], |builder, proof, _| {
+        builder.call_method(staking, "claim", args!(proof))
+    })
+}
+
+// VoteEscrow
+
+pub fn instantiate_vote_escrow(package: PackageAddress, account: ComponentAddress,
+governance_token: ResourceAddress, max_lock_years: i64, proposal_threshold: Decimal, quorum: Decimal,
+voting_period: i64) -> TransactionManifest {
+    call_function(package, account, "VoteEscrow", "instantiate_vote_escrow",
+        args!(governance_token, max_lock_years, proposal_threshold, quorum, voting_period))
+}
+
+// Locks `amount` of the governance token until `unlock_time` for a lock NFT
+pub fn lock(account: ComponentAddress, vote_escrow: ComponentAddress, governance_token: ResourceAddress,
+amount: Decimal, unlock_time: i64) -> TransactionManifest {
+    with_buckets(account, &[(governance_token, amount)], |builder, buckets| {
+        builder.call_method(vote_escrow, "lock", args!(buckets[0], unlock_time))
+    })
+}
+
+pub fn extend_lock(account: ComponentAddress, vote_escrow: ComponentAddress, lock_badge: ResourceAddress,
+lock_id: NonFungibleLocalId, unlock_time: i64) -> TransactionManifest {
+    with_badge_proof(account, lock_badge, lock_id, &[], |builder, proof, _| {
+        builder.call_method(vote_escrow, "extend_lock", args!(proof, unlock_time))
+    })
+}
+
+// Takes back the governance tokens of an expired lock
+pub fn withdraw_lock(account: ComponentAddress, vote_escrow: ComponentAddress, lock_badge: ResourceAddress,
+lock_id: NonFungibleLocalId) -> TransactionManifest {
+    with_badge_proof(account, lock_badge, lock_id, &[], |builder, proof, _| {
+        builder.call_method(vote_escrow, "withdraw", args!(proof))
+    })
+}
+
+// Hands the admin badge of a component to the governor, so proposals can call its protected methods
+pub fn deposit_admin_badge(account: ComponentAddress, vote_escrow: ComponentAddress,
+admin_badge: ResourceAddress) -> TransactionManifest {
+    with_buckets(account, &[(admin_badge, dec!(1))], |builder, buckets| {
+        builder.call_method(vote_escrow, "deposit_admin_badge", args!(buckets[0]))
+    })
+}
+
+// Proposes to call `method` of `component` with `args`, authorized by `admin_badge` held by the governor
+pub fn propose(account: ComponentAddress, vote_escrow: ComponentAddress, lock_badge: ResourceAddress,
+lock_id: NonFungibleLocalId, description: &str, component: ComponentAddress, method: &str, args: Vec<u8>,
+admin_badge: ResourceAddress) -> TransactionManifest {
+    with_badge_proof(account, lock_badge, lock_id, &[], |builder, proof, _| {
+        builder.call_method(vote_escrow, "propose", args!(proof, description.to_owned(), component,
+            method.to_owned(), args, admin_badge))
+    })
+}
+
+pub fn vote(account: ComponentAddress, vote_escrow: ComponentAddress, lock_badge: ResourceAddress,
+lock_id: NonFungibleLocalId, proposal_id: usize, support: bool) -> TransactionManifest {
+    with_badge_proof(account, lock_badge, lock_id, &[], |builder, proof, _| {
+        builder.call_method(vote_escrow, "vote", args!(proof, proposal_id, support))
+    })
+}
+
+pub fn execute_proposal(account: ComponentAddress, vote_escrow: ComponentAddress,
+proposal_id: usize) -> TransactionManifest {
+    call_method(account, vote_escrow, "execute", args!(proposal_id))
+}
+
+// Multisig
+
+pub fn instantiate_multisig(package: PackageAddress, account: ComponentAddress, signers: u64, threshold: u64,
+delay: i64) -> TransactionManifest {
+    call_function(package, account, "Multisig", "instantiate_multisig", args!(signers, threshold, delay))
+}
+
+// Hands a badge, e.g. the admin badge of a pool, to the multisig
+pub fn deposit_badge(account: ComponentAddress, multisig: ComponentAddress, badge: ResourceAddress,
+amount: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(badge, amount)], |builder, buckets| {
+        builder.call_method(multisig, "deposit_badge", args!(buckets[0]))
+    })
+}
+
+pub fn queue(account: ComponentAddress, multisig: ComponentAddress, signer_badge: ResourceAddress,
+signer_id: NonFungibleLocalId, action: &MultisigAction) -> TransactionManifest {
+    with_badge_proof(account, signer_badge, signer_id, &[], |builder, proof, _| {
+        builder.call_method(multisig, "queue", args!(proof, action.clone()))
+    })
+}
+
+pub fn approve(account: ComponentAddress, multisig: ComponentAddress, signer_badge: ResourceAddress,
+signer_id: NonFungibleLocalId, transaction_id: usize) -> TransactionManifest {
+    with_badge_proof(account, signer_badge, signer_id, &[], |builder, proof, _| {
+        builder.call_method(multisig, "approve", args!(proof, transaction_id))
+    })
+}
+
+pub fn cancel(account: ComponentAddress, multisig: ComponentAddress, signer_badge: ResourceAddress,
+signer_id: NonFungibleLocalId, transaction_id: usize) -> TransactionManifest {
+    with_badge_proof(account, signer_badge, signer_id, &[], |builder, proof, _| {
+        builder.call_method(multisig, "cancel", args!(proof, transaction_id))
+    })
+}
+
+pub fn execute_transaction(account: ComponentAddress, multisig: ComponentAddress,
+transaction_id: usize) -> TransactionManifest {
+    call_method(account, multisig, "execute", args!(transaction_id))
+}
+
+// EmissionController
+
+pub fn instantiate_emission_controller(package: PackageAddress, account: ComponentAddress,
+reward_token: ResourceAddress, emission_per_epoch: Decimal, epoch_length: i64,
+vote_escrow: ComponentAddress) -> TransactionManifest {
+    call_function(package, account, "EmissionController", "instantiate_emission_controller",
+        args!(reward_token, emission_per_epoch, epoch_length, vote_escrow))
+}
+
+pub fn register_pool(account: ComponentAddress, emission_controller: ComponentAddress, admin_badge: ResourceAddress,
+pool: ComponentAddress, lp_resource: ResourceAddress) -> TransactionManifest {
+    call_method_with_badge(account, emission_controller, "register_pool", admin_badge, args!(pool, lp_resource))
+}
+
+pub fn set_emission_per_epoch(account: ComponentAddress, emission_controller: ComponentAddress,
+admin_badge: ResourceAddress, emission_per_epoch: Decimal) -> TransactionManifest {
+    call_method_with_badge(account, emission_controller, "set_emission_per_epoch", admin_badge,
+        args!(emission_per_epoch))
+}
+
+pub fn fund(account: ComponentAddress, emission_controller: ComponentAddress, reward_token: ResourceAddress,
+amount: Decimal) -> TransactionManifest {
+    with_buckets(account, &[(reward_token, amount)], |builder, buckets| {
+        builder.call_method(emission_controller, "fund", args!(buckets[0]))
+    })
+}
+
+// Allocates the voting power of a lock across gauges, as fractions by gauge id
+pub fn vote_gauges(account: ComponentAddress, emission_controller: ComponentAddress, lock_badge: ResourceAddress,
+lock_id: NonFungibleLocalId, allocation: HashMap<usize, Decimal>) -> TransactionManifest {
+    with_badge_proof(account, lock_badge, lock_id, &[], |builder, proof, _| {
+        builder.call_method(emission_controller, "vote", args!(proof, allocation))
+    })
+}
+
+pub fn distribute(account: ComponentAddress, emission_controller: ComponentAddress) -> TransactionManifest {
+    call_method(account, emission_controller, "distribute", args!())
+}
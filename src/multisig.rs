@@ -0,0 +1,297 @@
+use scrypto::radix_engine_interface::time::*;
+use scrypto::prelude::*;
+
+/// The data held by the NFT of a signer.
+#[derive(NonFungibleData)]
+pub struct SignerBadge {
+    /// Seconds since the unix epoch at which the signer was added.
+    pub added_at: i64,
+}
+
+/// What a queued transaction does once executed.
+#[derive(ScryptoCategorize, ScryptoEncode, ScryptoDecode, LegacyDescribe, Clone, Debug)]
+pub enum Action {
+    /// Calls `method` on `component` with the `badge` held by the multisig,
+    /// e.g. `Radiswap::set_fee` with the admin badge of the pool. `args` are
+    /// SBOR encoded as produced by `args!`.
+    Call {
+        component: ComponentAddress,
+        method: String,
+        args: Vec<u8>,
+        badge: ResourceAddress,
+    },
+    /// Sends `amount` of a held badge to `account`.
+    Withdraw {
+        resource: ResourceAddress,
+        amount: Decimal,
+        account: ComponentAddress,
+    },
+    /// Mints a signer badge and sends it to `account`.
+    AddSigner { account: ComponentAddress },
+    /// Revokes a signer badge. The badge stays in circulation but can no longer
+    /// queue, approve or cancel.
+    RemoveSigner { id: NonFungibleLocalId },
+    /// Changes the number of approvals needed. Pending transactions which
+    /// already have enough approvals under the new threshold start their delay.
+    SetThreshold { threshold: u64 },
+}
+
+/// A queued transaction.
+#[derive(ScryptoCategorize, ScryptoEncode, ScryptoDecode, LegacyDescribe, Clone, Debug)]
+pub struct Transaction {
+    /// What the transaction does.
+    pub action: Action,
+    /// The signers which approved the transaction.
+    pub approvals: HashSet<NonFungibleLocalId>,
+    /// Seconds since the unix epoch from which the transaction can be
+    /// executed, set once it has enough approvals.
+    pub eta: Option<i64>,
+    /// Whether the transaction was executed.
+    pub executed: bool,
+    /// Whether the transaction was canceled.
+    pub canceled: bool,
+}
+
+#[blueprint]
+mod multisig {
+    struct Multisig {
+        /// The resource address of the signer badges.
+        signer_badge_address: ResourceAddress,
+        /// A vault containing the badge which mints the signer badges.
+        signer_minter_badge: Vault,
+        /// The signer badges which weren't revoked.
+        signers: HashSet<NonFungibleLocalId>,
+        /// The id of the next signer badge.
+        next_signer_id: u64,
+        /// The number of approvals a transaction needs.
+        threshold: u64,
+        /// How long an approved transaction waits before it can be executed,
+        /// in seconds.
+        delay: i64,
+        /// The badges held by the multisig, e.g. the admin badges of the pools.
+        badges: HashMap<ResourceAddress, Vault>,
+        /// Every transaction, by id.
+        transactions: Vec<Transaction>,
+    }
+
+    impl Multisig {
+        /// Creates a multisig with `signers` signers of which `threshold` must
+        /// approve a transaction. Approved transactions wait `delay` seconds
+        /// before they can be executed. Returns the signer badges.
+        pub fn instantiate_multisig(signers: u64, threshold: u64, delay: i64) -> (ComponentAddress, Vec<Bucket>) {
+            assert!(threshold > 0 && threshold <= signers, "Invalid threshold");
+            assert!(delay >= 0, "Invalid delay");
+
+            let signer_minter_badge: Bucket = ResourceBuilder::new_fungible()
+                .divisibility(DIVISIBILITY_NONE)
+                .metadata("name", "Multisig Signer Mint Auth")
+                .mint_initial_supply(1);
+
+            let signer_badge_address: ResourceAddress = ResourceBuilder::new_integer_non_fungible()
+                .metadata("name", "Multisig Signer Badge")
+                .mintable(
+                    rule!(require(signer_minter_badge.resource_address())),
+                    LOCKED,
+                )
+                .burnable(
+                    rule!(require(signer_minter_badge.resource_address())),
+                    LOCKED,
+                )
+                .create_with_no_initial_supply();
+
+            let now: i64 = Self::now();
+            let badges: Vec<Bucket> = (1..=signers)
+                .map(|id| {
+                    signer_minter_badge.authorize(|| {
+                        borrow_resource_manager!(signer_badge_address)
+                            .mint_non_fungible(&NonFungibleLocalId::integer(id), SignerBadge { added_at: now })
+                    })
+                })
+                .collect();
+
+            let component: ComponentAddress = Self {
+                signer_badge_address,
+                signer_minter_badge: Vault::with_bucket(signer_minter_badge),
+                signers: badges.iter().map(|badge| badge.non_fungible_local_id()).collect(),
+                next_signer_id: signers + 1,
+                threshold,
+                delay,
+                badges: HashMap::new(),
+                transactions: Vec::new(),
+            }
+            .instantiate()
+            .globalize();
+
+            (component, badges)
+        }
+
+        /// Hands a badge to the multisig. Anyone can deposit.
+        pub fn deposit_badge(&mut self, badge: Bucket) {
+            let resource: ResourceAddress = badge.resource_address();
+            self.badges
+                .entry(resource)
+                .or_insert_with(|| Vault::new(resource))
+                .put(badge);
+        }
+
+        /// Queues a transaction, approved by the signer queuing it. Returns the
+        /// transaction id.
+        pub fn queue(&mut self, signer_badge: Proof, action: Action) -> usize {
+            let signer: NonFungibleLocalId = self.signer_id(signer_badge);
+            if let Action::Call { badge, .. } = &action {
+                assert!(self.badges.contains_key(badge), "The multisig does not hold this badge");
+            }
+
+            self.transactions.push(Transaction {
+                action,
+                approvals: HashSet::new(),
+                eta: None,
+                executed: false,
+                canceled: false,
+            });
+            let id: usize = self.transactions.len() - 1;
+            self.add_approval(id, signer);
+
+            id
+        }
+
+        /// Approves a pending transaction. The delay starts once the threshold
+        /// is reached.
+        pub fn approve(&mut self, signer_badge: Proof, transaction_id: usize) {
+            let signer: NonFungibleLocalId = self.signer_id(signer_badge);
+            self.add_approval(transaction_id, signer);
+        }
+
+        /// Cancels a pending transaction. A single signer can cancel, so any of
+        /// them can stop a transaction during its delay.
+        pub fn cancel(&mut self, signer_badge: Proof, transaction_id: usize) {
+            self.signer_id(signer_badge);
+            let transaction: &mut Transaction = self.pending_transaction(transaction_id);
+            transaction.canceled = true;
+        }
+
+        /// Executes an approved transaction once its delay is over. Approvals of
+        /// signers removed since are not counted. Anyone can call this.
+        pub fn execute(&mut self, transaction_id: usize) {
+            let now: i64 = Self::now();
+            let threshold: u64 = self.threshold;
+            let signers: HashSet<NonFungibleLocalId> = self.signers.clone();
+
+            let transaction: &mut Transaction = self.pending_transaction(transaction_id);
+            let eta: i64 = transaction.eta.expect("Transaction is not approved");
+            assert!(now >= eta, "Transaction is still timelocked");
+            assert!(
+                transaction.approvals.intersection(&signers).count() as u64 >= threshold,
+                "Transaction is not approved"
+            );
+            transaction.executed = true;
+
+            match transaction.action.clone() {
+                Action::Call {
+                    component,
+                    method,
+                    args,
+                    badge,
+                } => {
+                    self.badges[&badge].authorize(|| Runtime::call_method::<_, ()>(component, &method, args));
+                }
+                Action::Withdraw {
+                    resource,
+                    amount,
+                    account,
+                } => {
+                    let badge: Bucket = self.badges.get_mut(&resource).expect("The multisig does not hold this badge").take(amount);
+                    Runtime::call_method::<_, ()>(account, "deposit", args!(badge));
+                }
+                Action::AddSigner { account } => {
+                    let id: NonFungibleLocalId = NonFungibleLocalId::integer(self.next_signer_id);
+                    self.next_signer_id += 1;
+
+                    let badge: Bucket = self.signer_minter_badge.authorize(|| {
+                        borrow_resource_manager!(self.signer_badge_address)
+                            .mint_non_fungible(&id, SignerBadge { added_at: now })
+                    });
+                    self.signers.insert(id);
+                    Runtime::call_method::<_, ()>(account, "deposit", args!(badge));
+                }
+                Action::RemoveSigner { id } => {
+                    assert!(self.signers.remove(&id), "Not a signer");
+                    assert!(
+                        self.signers.len() as u64 >= self.threshold,
+                        "Fewer signers than the threshold"
+                    );
+                }
+                Action::SetThreshold { threshold } => {
+                    assert!(
+                        threshold > 0 && threshold <= self.signers.len() as u64,
+                        "Invalid threshold"
+                    );
+                    self.threshold = threshold;
+
+                    // Pending transactions a lower threshold approves start their delay now
+                    let signers: HashSet<NonFungibleLocalId> = self.signers.clone();
+                    let delay: i64 = self.delay;
+                    for transaction in self.transactions.iter_mut() {
+                        let approved: bool = transaction.approvals.intersection(&signers).count() as u64 >= threshold;
+                        if !transaction.executed && !transaction.canceled && transaction.eta.is_none() && approved {
+                            transaction.eta = Some(now + delay);
+                        }
+                    }
+                }
+            }
+        }
+
+        /// Returns a transaction.
+        pub fn transaction(&self, transaction_id: usize) -> Transaction {
+            self.transactions.get(transaction_id).expect("Unknown transaction").clone()
+        }
+
+        /// Returns the signers, the threshold and the delay.
+        pub fn config(&self) -> (HashSet<NonFungibleLocalId>, u64, i64) {
+            (self.signers.clone(), self.threshold, self.delay)
+        }
+
+        /// Returns the amount held of each badge.
+        pub fn badges(&self) -> HashMap<ResourceAddress, Decimal> {
+            self.badges
+                .iter()
+                .map(|(resource, vault)| (*resource, vault.amount()))
+                .collect()
+        }
+
+        fn add_approval(&mut self, transaction_id: usize, signer: NonFungibleLocalId) {
+            let now: i64 = Self::now();
+            let threshold: u64 = self.threshold;
+            let delay: i64 = self.delay;
+
+            let transaction: &mut Transaction = self.pending_transaction(transaction_id);
+            assert!(transaction.approvals.insert(signer), "Signer already approved");
+            if transaction.eta.is_none() && transaction.approvals.len() as u64 >= threshold {
+                transaction.eta = Some(now + delay);
+            }
+        }
+
+        fn pending_transaction(&mut self, transaction_id: usize) -> &mut Transaction {
+            let transaction: &mut Transaction = self
+                .transactions
+                .get_mut(transaction_id)
+                .expect("Unknown transaction");
+            assert!(!transaction.executed, "Transaction was already executed");
+            assert!(!transaction.canceled, "Transaction was canceled");
+            transaction
+        }
+
+        fn signer_id(&self, signer_badge: Proof) -> NonFungibleLocalId {
+            let id: NonFungibleLocalId = signer_badge
+                .validate_proof(self.signer_badge_address)
+                .expect("Invalid signer badge")
+                .non_fungible_local_id();
+            assert!(self.signers.contains(&id), "Signer was removed");
+            id
+        }
+
+        fn now() -> i64 {
+            Clock::current_time_rounded_to_minutes().seconds_since_unix_epoch
+        }
+    }
+}
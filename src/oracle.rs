@@ -0,0 +1,198 @@
+use scrypto::radix_engine_interface::time::*;
+use scrypto::prelude::*;
+
+/// The data held by the badge of an allowlisted price feeder.
+#[derive(NonFungibleData)]
+pub struct FeederBadge {
+    /// A human readable name of the feeder.
+    pub name: String,
+}
+
+/// The last price pushed by a feeder.
+#[derive(ScryptoCategorize, ScryptoEncode, ScryptoDecode, LegacyDescribe, Clone, Copy, Debug)]
+pub struct PricePoint {
+    /// The price of the base token in terms of the quote token.
+    pub price: Decimal,
+    /// Seconds since the unix epoch at which the price was pushed.
+    pub timestamp: i64,
+}
+
+#[blueprint]
+mod price_oracle {
+    struct PriceOracle {
+        /// The token whose price is reported.
+        base: ResourceAddress,
+        /// The token the price is quoted in.
+        quote: ResourceAddress,
+        /// The resource address of the feeder badges.
+        feeder_badge_address: ResourceAddress,
+        /// A vault containing the badge which mints and burns feeder badges.
+        feeder_minter_badge: Vault,
+        /// The id which will be given to the next feeder badge.
+        next_feeder_id: u64,
+        /// The last price pushed by each allowlisted feeder. Feeders which are
+        /// removed from the allowlist are removed from this map.
+        feeders: HashMap<NonFungibleLocalId, PricePoint>,
+        /// The maximum age in seconds of a price for it to be used.
+        max_staleness: i64,
+        /// The minimum number of fresh prices needed to report a price.
+        min_feeders: u64,
+    }
+
+    impl PriceOracle {
+        /// Creates a new oracle for the price of `base` in terms of `quote`. The
+        /// returned admin badge is needed to manage the feeder allowlist.
+        pub fn instantiate_price_oracle(
+            base: ResourceAddress,
+            quote: ResourceAddress,
+            max_staleness: i64,
+            min_feeders: u64,
+        ) -> (ComponentAddress, Bucket) {
+            assert!(base != quote, "Base and quote tokens must differ");
+            assert!(max_staleness > 0, "Staleness limit must be positive");
+            assert!(min_feeders > 0, "At least one feeder is required");
+
+            let admin_badge: Bucket = ResourceBuilder::new_fungible()
+                .divisibility(DIVISIBILITY_NONE)
+                .metadata("name", "Price Oracle Admin Badge")
+                .mint_initial_supply(1);
+
+            let feeder_minter_badge: Bucket = ResourceBuilder::new_fungible()
+                .divisibility(DIVISIBILITY_NONE)
+                .metadata("name", "Feeder Badge Mint Auth")
+                .mint_initial_supply(1);
+
+            let feeder_badge_address: ResourceAddress = ResourceBuilder::new_integer_non_fungible()
+                .metadata("name", "Price Feeder Badge")
+                .mintable(
+                    rule!(require(feeder_minter_badge.resource_address())),
+                    LOCKED,
+                )
+                .burnable(
+                    rule!(require(feeder_minter_badge.resource_address())),
+                    LOCKED,
+                )
+                .create_with_no_initial_supply();
+
+            let access_rules: AccessRules = AccessRules::new()
+                .method("add_feeder", rule!(require(admin_badge.resource_address())), LOCKED)
+                .method("remove_feeder", rule!(require(admin_badge.resource_address())), LOCKED)
+                .method("set_limits", rule!(require(admin_badge.resource_address())), LOCKED)
+                .default(rule!(allow_all), LOCKED);
+
+            let mut component = Self {
+                base,
+                quote,
+                feeder_badge_address,
+                feeder_minter_badge: Vault::with_bucket(feeder_minter_badge),
+                next_feeder_id: 1,
+                feeders: HashMap::new(),
+                max_staleness,
+                min_feeders,
+            }
+            .instantiate();
+            component.add_access_check(access_rules);
+
+            (component.globalize(), admin_badge)
+        }
+
+        /// Mints a badge for a new feeder and adds it to the allowlist.
+        pub fn add_feeder(&mut self, name: String) -> Bucket {
+            let id: NonFungibleLocalId = NonFungibleLocalId::integer(self.next_feeder_id);
+            self.next_feeder_id += 1;
+
+            let badge: Bucket = self.feeder_minter_badge.authorize(|| {
+                borrow_resource_manager!(self.feeder_badge_address)
+                    .mint_non_fungible(&id, FeederBadge { name })
+            });
+
+            self.feeders.insert(
+                id,
+                PricePoint {
+                    price: Decimal::zero(),
+                    timestamp: 0,
+                },
+            );
+
+            badge
+        }
+
+        /// Removes a feeder from the allowlist. Its badge stays in circulation but
+        /// can no longer push prices.
+        pub fn remove_feeder(&mut self, id: NonFungibleLocalId) {
+            assert!(
+                self.feeders.remove(&id).is_some(),
+                "The feeder is not on the allowlist"
+            );
+        }
+
+        /// Changes the staleness limit and the minimum number of feeders.
+        pub fn set_limits(&mut self, max_staleness: i64, min_feeders: u64) {
+            assert!(max_staleness > 0, "Staleness limit must be positive");
+            assert!(min_feeders > 0, "At least one feeder is required");
+            self.max_staleness = max_staleness;
+            self.min_feeders = min_feeders;
+        }
+
+        /// Pushes a new price of the base token in terms of the quote token. The
+        /// proof must be of an allowlisted feeder badge.
+        pub fn push_price(&mut self, feeder_badge: Proof, price: Decimal) {
+            let feeder_badge: ValidatedProof = feeder_badge
+                .validate_proof(self.feeder_badge_address)
+                .expect("Invalid feeder badge");
+            assert!(price > Decimal::zero(), "Price must be positive");
+
+            let id: NonFungibleLocalId = feeder_badge.non_fungible_local_id();
+            let point: &mut PricePoint = self
+                .feeders
+                .get_mut(&id)
+                .expect("The feeder is not on the allowlist");
+            point.price = price;
+            point.timestamp = Self::now();
+        }
+
+        /// Returns the median of the fresh prices of `base` in terms of the other
+        /// token. Fails if fewer than `min_feeders` prices are fresh.
+        pub fn price(&self, base: ResourceAddress) -> Decimal {
+            let median: Decimal = self.median_price();
+            if base == self.base {
+                median
+            } else if base == self.quote {
+                Decimal::one() / median
+            } else {
+                panic!("The given resource is not priced by this oracle")
+            }
+        }
+
+        /// Returns the last price pushed by every allowlisted feeder.
+        pub fn feeder_prices(&self) -> HashMap<NonFungibleLocalId, PricePoint> {
+            self.feeders.clone()
+        }
+
+        fn median_price(&self) -> Decimal {
+            let now: i64 = Self::now();
+            let mut prices: Vec<Decimal> = self
+                .feeders
+                .values()
+                .filter(|point| now - point.timestamp <= self.max_staleness)
+                .map(|point| point.price)
+                .collect();
+            assert!(
+                prices.len() as u64 >= self.min_feeders,
+                "Not enough fresh prices"
+            );
+
+            prices.sort();
+            let middle: usize = prices.len() / 2;
+            if prices.len() % 2 == 0 {
+                (prices[middle - 1] + prices[middle]) / dec!("2")
+            } else {
+                prices[middle]
+            }
+        }
+
+        fn now() -> i64 {
+            Clock::current_time_rounded_to_minutes().seconds_since_unix_epoch
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use scrypto::radix_engine_interface::time::*;
+use scrypto::prelude::*;
+use crate::rate_model::*;
+
+#[blueprint]
+mod pid_rate_model {
+    struct PidRateModel {
+        // Utilization the controller steers the pool towards
+        target_utilization: Decimal,
+        // Yearly rate when utilization sits at the target and no error has accumulated
+        base_rate: Decimal,
+        // Proportional gain, yearly rate per unit of utilization error
+        kp: Decimal,
+        // Integral gain, yearly rate per unit of utilization error held for a year
+        ki: Decimal,
+        // Utilization error accumulated over time, in years
+        integral: Decimal,
+        // Utilization reported by the last update
+        last_utilization: Decimal,
+        // Seconds since the unix epoch of the last update
+        last_update: i64,
+        // Lowest yearly rate quoted
+        min_rate: Decimal,
+        // Highest yearly rate quoted
+        max_rate: Decimal,
+    }
+
+    impl PidRateModel {
+        // Adaptive model: the rate moves with the distance between the utilization and its target and
+        // with how long that distance has persisted. Only the holder of the returned updater badge, the
+        // pool, can feed utilization to the controller.
+        pub fn instantiate_pid_rate_model(target_utilization: Decimal, base_rate: Decimal, kp: Decimal, ki: Decimal,
+        min_rate: Decimal, max_rate: Decimal) -> (ComponentAddress, Bucket) {
+            assert!(target_utilization > Decimal::zero() && target_utilization < Decimal::one(),
+            "Target utilization must be between 0 and 1");
+            assert!(min_rate >= Decimal::zero() && min_rate <= max_rate, "Invalid rate bounds");
+
+            let updater_badge = new_updater_badge();
+
+            let access_rules = AccessRules::new()
+                .method("update", rule!(require(updater_badge.resource_address())), LOCKED)
+                .default(rule!(allow_all), LOCKED);
+
+            let mut component = Self {
+                target_utilization,
+                base_rate,
+                kp,
+                ki,
+                integral: Decimal::zero(),
+                last_utilization: Decimal::zero(),
+                last_update: Clock::current_time_rounded_to_minutes().seconds_since_unix_epoch,
+                min_rate,
+                max_rate,
+            }
+            .instantiate();
+            component.add_access_check(access_rules);
+
+            (component.globalize(), updater_badge)
+        }
+
+        pub fn borrow_rate(&self, utilization: Decimal) -> Decimal {
+            let error = utilization - self.target_utilization;
+            clamp_rate(self.base_rate + self.kp * error + self.ki * self.integral, self.min_rate, self.max_rate)
+        }
+
+        pub fn rate_bounds(&self) -> (Decimal, Decimal) {
+            (self.min_rate, self.max_rate)
+        }
+
+        // Integrates the error of the previous utilization over the time it was in effect
+        pub fn update(&mut self, utilization: Decimal) {
+            let now = Clock::current_time_rounded_to_minutes().seconds_since_unix_epoch;
+            let elapsed = Decimal::from(now - self.last_update) / Decimal::from(SECONDS_PER_YEAR);
+
+            if elapsed > Decimal::zero() {
+                self.integral += (self.last_utilization - self.target_utilization) * elapsed;
+
+                // Anti-windup, the integral term alone never pushes the rate past the bounds
+                if self.ki > Decimal::zero() {
+                    let max_integral = (self.max_rate - self.base_rate) / self.ki;
+                    let min_integral = (self.min_rate - self.base_rate) / self.ki;
+                    self.integral = clamp_rate(self.integral, min_integral, max_integral);
+                }
+                self.last_update = now;
+            }
+
+            self.last_utilization = utilization;
+        }
+
+        pub fn integral(&self) -> Decimal {
+            self.integral
+        }
+    }
+}
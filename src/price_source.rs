@@ -0,0 +1,11 @@
+use scrypto::prelude::*;
+
+// Interface shared by every component that can act as a price source for the pools. Both
+// `Radiswap` (time weighted average price) and `PriceOracle` (median of pushed prices) implement
+// `price`, so a pool only stores the address of its source and doesn't care which one it is.
+external_component! {
+    PriceSourceComponentTarget {
+        // Price of one unit of `base` in terms of the other token of the pair
+        fn price(&self, base: ResourceAddress) -> Decimal;
+    }
+}
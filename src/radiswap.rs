@@ -1,4 +1,31 @@
+use scrypto::radix_engine_interface::time::*;
 use scrypto::prelude::*;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::insurance_fund::InsuranceFundComponentTarget;
+
+/// The maximum number of price observations kept for computing time weighted 
+/// average prices.
+const MAX_OBSERVATIONS: usize = 64;
+
+/// The window in seconds of the time weighted average price reported by `price`.
+const PRICE_TWAP_WINDOW: i64 = 600;
+
+/// The shortest price history in seconds a time weighted average price is 
+/// computed over. A younger pool has no average price to report.
+const MIN_TWAP_HISTORY: i64 = 60;
+
+/// A snapshot of the cumulative prices of the pool at a point in time. The 
+/// difference between two observations divided by the time between them is the 
+/// time weighted average price over that period.
+#[derive(ScryptoCategorize, ScryptoEncode, ScryptoDecode, LegacyDescribe, Clone, Copy, Debug)]
+pub struct Observation {
+    /// Seconds since the unix epoch at which the observation was taken.
+    pub timestamp: i64,
+    /// Sum over time of the price of token A in terms of token B.
+    pub price_a_cumulative: Decimal,
+    /// Sum over time of the price of token B in terms of token A.
+    pub price_b_cumulative: Decimal,
+}
 
 #[blueprint]
 mod radiswap_module {
@@ -14,15 +41,45 @@ mod radiswap_module {
         pool_units_minter_badge: Vault,
         /// The amount of fees imposed by the pool on swaps where 0 <= fee <= 1.
         fee: Decimal,
+        /// Cumulative price observations, oldest first, used for the TWAP.
+        observations: Vec<Observation>,
+        /// The insurance fund receiving a share of the swap fees, if any.
+        insurance_fund: Option<ComponentAddress>,
+        /// The share of the swap fees sent to the insurance fund where 
+        /// 0 <= protocol_fee_share <= 1.
+        protocol_fee_share: Decimal,
+        /// Halts swaps when a swap would move the price too far, guarded by the 
+        /// admin badge by default.
+        circuit_breaker: CircuitBreaker,
     }
 
     impl Radiswap {
-        /// Creates a new liquidity pool of the two tokens sent to the pool
+        /// Creates a new liquidity pool of the two tokens sent to the pool. 
+        /// Returns the component address, the initial pool units and the admin 
+        /// badge allowed to change the pool parameters.
         pub fn instantiate_radiswap(
             bucket_a: Bucket,
             bucket_b: Bucket,
             fee: Decimal,
-        ) -> (ComponentAddress, Bucket) {
+        ) -> (ComponentAddress, Bucket, Bucket) {
+            Self::instantiate_radiswap_with_insurance(
+                bucket_a,
+                bucket_b,
+                fee,
+                None,
+                Decimal::zero(),
+            )
+        }
+
+        /// Creates a new liquidity pool of the two tokens sent to the pool which 
+        /// sends `protocol_fee_share` of the swap fees to an insurance fund.
+        pub fn instantiate_radiswap_with_insurance(
+            bucket_a: Bucket,
+            bucket_b: Bucket,
+            fee: Decimal,
+            insurance_fund: Option<ComponentAddress>,
+            protocol_fee_share: Decimal,
+        ) -> (ComponentAddress, Bucket, Bucket) {
             // Ensure that none of the buckets are empty and that an appropriate 
             // fee is set.
             assert!(
@@ -33,6 +90,10 @@ mod radiswap_module {
                 fee >= dec!("0") && fee <= dec!("1"),
                 "Invalid fee in thousandths"
             );
+            assert!(
+                protocol_fee_share >= dec!("0") && protocol_fee_share <= dec!("1"),
+                "Invalid protocol fee share"
+            );
 
             // Create a badge which will be given the authority to mint the pool  
             // unit tokens.
@@ -41,6 +102,13 @@ mod radiswap_module {
                 .metadata("name", "LP Token Mint Auth")
                 .mint_initial_supply(1);
 
+            // Create the admin badge which can change the pool parameters, meant to 
+            // be handed to a governor.
+            let admin_badge: Bucket = ResourceBuilder::new_fungible()
+                .divisibility(DIVISIBILITY_NONE)
+                .metadata("name", "Radiswap Admin Badge")
+                .mint_initial_supply(1);
+
             // Create the pool units token along with the initial supply specified  
             // by the user.
             let pool_units: Bucket = ResourceBuilder::new_fungible()
@@ -57,49 +125,116 @@ mod radiswap_module {
                 )
                 .mint_initial_supply(100);
 
+            let access_rules: AccessRules = AccessRules::new()
+                .method("set_fee", rule!(require(admin_badge.resource_address())), LOCKED)
+                .method(
+                    "set_insurance_fund",
+                    rule!(require(admin_badge.resource_address())),
+                    LOCKED,
+                )
+                .method(
+                    "set_circuit_breaker",
+                    rule!(require(admin_badge.resource_address())),
+                    LOCKED,
+                )
+                .default(rule!(allow_all), LOCKED);
+
             // Create the Radiswap component and globalize it
-            let radiswap: ComponentAddress = Self {
+            let mut radiswap = Self {
                 vault_a: Vault::with_bucket(bucket_a),
                 vault_b: Vault::with_bucket(bucket_b),
                 pool_units_resource_address: pool_units.resource_address(),
                 pool_units_minter_badge: Vault::with_bucket(pool_units_minter_badge),
                 fee: fee,
+                observations: vec![Observation {
+                    timestamp: Self::now(),
+                    price_a_cumulative: Decimal::zero(),
+                    price_b_cumulative: Decimal::zero(),
+                }],
+                insurance_fund: insurance_fund,
+                protocol_fee_share: protocol_fee_share,
+                circuit_breaker: CircuitBreaker::new(admin_badge.resource_address()),
             }
-            .instantiate()
-            .globalize();
-
-            // Return the component address as well as the pool units tokens
-            (radiswap, pool_units)
-        }
-
-        /// Swaps token A for B, or vice versa.
-        pub fn swap(&mut self, input_tokens: Bucket) -> Bucket {
-            // Getting the vault corresponding to the input tokens and the vault 
-            // corresponding to the output tokens based on what the input is.
-            let (input_tokens_vault, output_tokens_vault): (&mut Vault, &mut Vault) =
-                if input_tokens.resource_address() == 
-                self.vault_a.resource_address() {
-                    (&mut self.vault_a, &mut self.vault_b)
-                } else if input_tokens.resource_address() == 
-                self.vault_b.resource_address() {
-                    (&mut self.vault_b, &mut self.vault_a)
-                } else {
-                    panic!(
-                    "The given input tokens do not belong to this liquidity pool"
-                    )
-                };
+            .instantiate();
+            radiswap.add_access_check(access_rules);
+
+            // Return the component address as well as the pool units tokens and 
+            // the admin badge
+            (radiswap.globalize(), pool_units, admin_badge)
+        }
+
+        /// Swaps token A for B, or vice versa. When the swap would trip the 
+        /// circuit breaker it is not executed, trading halts and the input tokens 
+        /// are returned untouched.
+        pub fn swap(&mut self, mut input_tokens: Bucket) -> Bucket {
+            let now: i64 = Self::now();
+            self.circuit_breaker.assert_open(now);
+            self.record_observation();
+
+            let token_a: ResourceAddress = self.vault_a.resource_address();
+            let input_is_a: bool = if input_tokens.resource_address() == token_a {
+                true
+            } else if input_tokens.resource_address() == 
+            self.vault_b.resource_address() {
+                false
+            } else {
+                panic!(
+                "The given input tokens do not belong to this liquidity pool"
+                )
+            };
+
+            // The protocol share of the fees goes to the insurance fund and is 
+            // not part of the swapped amount.
+            let protocol_fee: Decimal = match self.insurance_fund {
+                Some(_) => input_tokens.amount() * self.fee * self.protocol_fee_share,
+                None => Decimal::zero(),
+            };
+            let input_amount: Decimal = input_tokens.amount() - protocol_fee;
 
             // Calculate the output amount of tokens based on the input amount 
             // and the pool fees
-            let output_amount: Decimal = (output_tokens_vault.amount()
+            let (input_reserve, output_reserve): (Decimal, Decimal) = if input_is_a {
+                (self.vault_a.amount(), self.vault_b.amount())
+            } else {
+                (self.vault_b.amount(), self.vault_a.amount())
+            };
+            let output_amount: Decimal = (output_reserve
                 * (dec!("1") - self.fee)
-                * input_tokens.amount())
-                / (input_tokens_vault.amount() + input_tokens.amount() 
-                * (dec!("1") - self.fee));
+                * input_amount)
+                / (input_reserve + input_amount * (dec!("1") - self.fee));
+
+            // Check the price of token A the swap would leave the pool at against 
+            // the circuit breaker.
+            let price_before: Decimal = self.spot_price(token_a);
+            let price_after: Decimal = if input_is_a {
+                (output_reserve - output_amount) / (input_reserve + input_amount)
+            } else {
+                (input_reserve + input_amount) / (output_reserve - output_amount)
+            };
+            // Until the pool has enough history the price before the swap stands 
+            // in for the average.
+            let twap: Decimal = self
+                .observed_twap(true, PRICE_TWAP_WINDOW)
+                .unwrap_or(price_before);
+            if self.circuit_breaker.trip(now, price_before, price_after, twap) {
+                return input_tokens;
+            }
+
+            if let Some(insurance_fund) = self.insurance_fund {
+                if protocol_fee > Decimal::zero() {
+                    InsuranceFundComponentTarget::at(insurance_fund)
+                        .deposit_fees(input_tokens.take(protocol_fee));
+                }
+            }
 
             // Perform the swapping operation
-            input_tokens_vault.put(input_tokens);
-            output_tokens_vault.take(output_amount)
+            if input_is_a {
+                self.vault_a.put(input_tokens);
+                self.vault_b.take(output_amount)
+            } else {
+                self.vault_b.put(input_tokens);
+                self.vault_a.take(output_amount)
+            }
         }
         
         /// Adds liquidity to the liquidity pool
@@ -108,6 +243,8 @@ mod radiswap_module {
             bucket_a: Bucket,
             bucket_b: Bucket,
         ) -> (Bucket, Bucket, Bucket) {
+            self.record_observation();
+
             // Give the buckets the same names as the vaults
             let (mut bucket_a, mut bucket_b): (Bucket, Bucket) = 
             if bucket_a.resource_address()
@@ -176,6 +313,8 @@ mod radiswap_module {
                 "Wrong token type passed in"
             );
 
+            self.record_observation();
+
             // Get the resource manager of the lp tokens
             let pool_units_resource_manager: &ResourceManager =
                 borrow_resource_manager!(self.pool_units_resource_address);
@@ -195,5 +334,175 @@ mod radiswap_module {
                 self.vault_b.take(self.vault_b.amount() * share),
             )
         }
+
+        /// Returns the current price of `base` in terms of the other token of the 
+        /// pool, computed from the vault reserves.
+        pub fn spot_price(&self, base: ResourceAddress) -> Decimal {
+            if base == self.vault_a.resource_address() {
+                self.vault_b.amount() / self.vault_a.amount()
+            } else if base == self.vault_b.resource_address() {
+                self.vault_a.amount() / self.vault_b.amount()
+            } else {
+                panic!("The given resource does not belong to this liquidity pool")
+            }
+        }
+
+        /// Returns the time weighted average price of `base` in terms of the other 
+        /// token over the last `window` seconds. When the recorded history is 
+        /// shorter than the window the average over the whole history is used. 
+        /// Fails when the pool has less than `MIN_TWAP_HISTORY` seconds of history, 
+        /// rather than falling back to the manipulable spot price.
+        pub fn twap(&self, base: ResourceAddress, window: i64) -> Decimal {
+            let is_a: bool = if base == self.vault_a.resource_address() {
+                true
+            } else if base == self.vault_b.resource_address() {
+                false
+            } else {
+                panic!("The given resource does not belong to this liquidity pool")
+            };
+            self.observed_twap(is_a, window)
+                .expect("Not enough price history for a time weighted average price")
+        }
+
+        /// Changes the fee imposed by the pool on swaps.
+        pub fn set_fee(&mut self, fee: Decimal) {
+            assert!(
+                fee >= dec!("0") && fee <= dec!("1"),
+                "Invalid fee in thousandths"
+            );
+            self.fee = fee;
+        }
+
+        /// Changes the insurance fund receiving a share of the swap fees and the 
+        /// size of that share.
+        pub fn set_insurance_fund(
+            &mut self,
+            insurance_fund: Option<ComponentAddress>,
+            protocol_fee_share: Decimal,
+        ) {
+            assert!(
+                protocol_fee_share >= dec!("0") && protocol_fee_share <= dec!("1"),
+                "Invalid protocol fee share"
+            );
+            self.insurance_fund = insurance_fund;
+            self.protocol_fee_share = protocol_fee_share;
+        }
+
+        /// Changes the limits of the circuit breaker and the badge of the 
+        /// guardians allowed to resume trading.
+        pub fn set_circuit_breaker(
+            &mut self,
+            max_move: Decimal,
+            window: i64,
+            max_twap_deviation: Decimal,
+            cooldown: i64,
+            guardian_badge: ResourceAddress,
+        ) {
+            self.circuit_breaker.configure(
+                max_move,
+                window,
+                max_twap_deviation,
+                cooldown,
+                guardian_badge,
+            );
+        }
+
+        /// Resumes swaps halted by the circuit breaker before the end of the 
+        /// cooldown. Requires a guardian badge.
+        pub fn resume_trading(&mut self, guardian: Proof) {
+            self.circuit_breaker.resume(guardian, Self::now());
+        }
+
+        /// Returns whether swaps are halted by the circuit breaker.
+        pub fn is_halted(&self) -> bool {
+            self.circuit_breaker.is_halted(Self::now())
+        }
+
+        /// Returns the state of the circuit breaker, including every halt.
+        pub fn circuit_breaker(&self) -> CircuitBreaker {
+            self.circuit_breaker.clone()
+        }
+
+        /// Returns the time weighted average price of `base` over the default 
+        /// window. This makes the pool usable as a price source for other 
+        /// components, interchangeably with a `PriceOracle`.
+        pub fn price(&self, base: ResourceAddress) -> Decimal {
+            self.twap(base, PRICE_TWAP_WINDOW)
+        }
+
+        /// Returns the address of the token A and token B of the pool.
+        pub fn token_addresses(&self) -> (ResourceAddress, ResourceAddress) {
+            (self.vault_a.resource_address(), self.vault_b.resource_address())
+        }
+
+        /// Accumulates the prices which held since the last observation and stores 
+        /// a new observation. Must be called before the reserves change.
+        fn record_observation(&mut self) {
+            let now: i64 = Self::now();
+            if now <= self.observations[self.observations.len() - 1].timestamp {
+                return;
+            }
+
+            let observation: Observation = self.accumulate(now);
+            self.observations.push(observation);
+            if self.observations.len() > MAX_OBSERVATIONS {
+                self.observations.remove(0);
+            }
+        }
+
+        /// Returns the time weighted average price of token A, or of token B when 
+        /// `is_a` is false, over the last `window` seconds, or `None` when the pool 
+        /// has less than `MIN_TWAP_HISTORY` seconds of history.
+        fn observed_twap(&self, is_a: bool, window: i64) -> Option<Decimal> {
+            let now: i64 = Self::now();
+            let latest: Observation = self.accumulate(now);
+
+            // The most recent observation which is at least `window` seconds old, 
+            // or the oldest one if the history does not reach that far back.
+            let start: Observation = self
+                .observations
+                .iter()
+                .rev()
+                .find(|observation| observation.timestamp <= now - window)
+                .copied()
+                .unwrap_or(self.observations[0]);
+
+            let elapsed: i64 = now - start.timestamp;
+            if now - self.observations[0].timestamp < MIN_TWAP_HISTORY || elapsed <= 0 {
+                return None;
+            }
+
+            let cumulative_delta: Decimal = if is_a {
+                latest.price_a_cumulative - start.price_a_cumulative
+            } else {
+                latest.price_b_cumulative - start.price_b_cumulative
+            };
+            Some(cumulative_delta / Decimal::from(elapsed))
+        }
+
+        /// Computes what the cumulative prices would be at `now` given the current 
+        /// reserves, without storing anything.
+        fn accumulate(&self, now: i64) -> Observation {
+            let last: Observation = self.observations[self.observations.len() - 1];
+            let elapsed: Decimal = Decimal::from(now - last.timestamp);
+            if elapsed <= Decimal::zero() 
+                || self.vault_a.is_empty() 
+                || self.vault_b.is_empty() 
+            {
+                return last;
+            }
+
+            Observation {
+                timestamp: now,
+                price_a_cumulative: last.price_a_cumulative
+                    + self.vault_b.amount() / self.vault_a.amount() * elapsed,
+                price_b_cumulative: last.price_b_cumulative
+                    + self.vault_a.amount() / self.vault_b.amount() * elapsed,
+            }
+        }
+
+        fn now() -> i64 {
+            Clock::current_time_rounded_to_minutes().seconds_since_unix_epoch
+        }
     }
 }
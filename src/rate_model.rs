@@ -0,0 +1,31 @@
+use scrypto::prelude::*;
+
+// The number of seconds in a (non leap) year, rate models quote yearly rates
+pub const SECONDS_PER_YEAR: i64 = 31_536_000;
+
+// Interface shared by `LinearRateModel`, `JumpRateModel` and `PidRateModel`. A pool stores the
+// address of its model together with the updater badge returned when the model was instantiated,
+// and calls `update` with its utilization whenever that changes. Stateless models ignore `update`.
+external_component! {
+    RateModelComponentTarget {
+        // Yearly borrow rate at the given utilization, always within `rate_bounds`
+        fn borrow_rate(&self, utilization: Decimal) -> Decimal;
+        // Lowest and highest yearly rate the model will ever quote
+        fn rate_bounds(&self) -> (Decimal, Decimal);
+        // Feeds the current utilization of the pool to the model
+        fn update(&mut self, utilization: Decimal);
+    }
+}
+
+// Restricts a rate to the bounds of a model
+pub fn clamp_rate(rate: Decimal, min_rate: Decimal, max_rate: Decimal) -> Decimal {
+    std::cmp::max(min_rate, std::cmp::min(rate, max_rate))
+}
+
+// Creates the badge returned with every rate model, which the pool uses to call `update`
+pub fn new_updater_badge() -> Bucket {
+    ResourceBuilder::new_fungible()
+        .divisibility(DIVISIBILITY_NONE)
+        .metadata("name", "Rate Model Updater Badge")
+        .mint_initial_supply(1)
+}
@@ -0,0 +1,322 @@
+use scrypto::radix_engine_interface::time::*;
+use scrypto::prelude::*;
+
+// Interface used by the emission controller to fund the reward streams
+external_component! {
+    StakingRewardsComponentTarget {
+        fn add_reward(&mut self, rewards: Bucket, start: i64, end: i64);
+        fn top_up(&mut self, rewards: Bucket, end: i64);
+    }
+}
+
+/// The data held by the NFT representing a stake.
+#[derive(NonFungibleData)]
+pub struct StakeBadge {
+    /// Seconds since the unix epoch at which the stake was opened.
+    pub staked_at: i64,
+}
+
+/// A reward token streamed linearly to the stakers between `start` and `end`.
+#[derive(ScryptoCategorize, ScryptoEncode, ScryptoDecode, LegacyDescribe, Clone, Debug)]
+pub struct RewardStream {
+    /// The amount of reward tokens distributed per second.
+    pub rate: Decimal,
+    /// Seconds since the unix epoch at which the stream starts.
+    pub start: i64,
+    /// Seconds since the unix epoch at which the stream ends.
+    pub end: i64,
+    /// The amount of reward tokens earned by one staked unit since the stream
+    /// was created, up to `last_update`.
+    pub reward_per_token: Decimal,
+    /// Seconds since the unix epoch up to which `reward_per_token` is accrued.
+    pub last_update: i64,
+    /// The reward tokens which accrued while nothing was staked after the
+    /// stream ended, handed out again by the next `top_up`.
+    pub idle: Decimal,
+}
+
+/// The staked amount and the rewards of a stake.
+#[derive(ScryptoCategorize, ScryptoEncode, ScryptoDecode, LegacyDescribe, Clone, Debug, Default)]
+pub struct Stake {
+    /// The amount of pool units staked.
+    pub amount: Decimal,
+    /// The `reward_per_token` of each stream when the rewards of the stake were
+    /// last settled.
+    pub reward_per_token_paid: HashMap<ResourceAddress, Decimal>,
+    /// The rewards settled but not claimed yet.
+    pub owed: HashMap<ResourceAddress, Decimal>,
+}
+
+#[blueprint]
+mod staking_rewards {
+    struct StakingRewards {
+        /// The pool unit resource which can be staked.
+        stake_resource: ResourceAddress,
+        /// A vault containing all the staked pool units.
+        staked: Vault,
+        /// The resource address of the stake NFTs.
+        stake_badge_address: ResourceAddress,
+        /// A vault containing the badge which mints the stake NFTs.
+        minter_badge: Vault,
+        /// The reward streams, per reward token.
+        streams: HashMap<ResourceAddress, RewardStream>,
+        /// The reward tokens not yet claimed, per reward token.
+        reward_vaults: HashMap<ResourceAddress, Vault>,
+        /// Every open stake.
+        stakes: HashMap<NonFungibleLocalId, Stake>,
+    }
+
+    impl StakingRewards {
+        /// Creates a staking contract for a pool unit resource such as the pool
+        /// units of a `Radiswap` pool or the LP token of a `ConstantSumAmm`. The
+        /// returned admin badge is needed to fund reward streams.
+        pub fn instantiate_staking_rewards(stake_resource: ResourceAddress) -> (ComponentAddress, Bucket) {
+            let admin_badge: Bucket = ResourceBuilder::new_fungible()
+                .divisibility(DIVISIBILITY_NONE)
+                .metadata("name", "Staking Rewards Admin Badge")
+                .mint_initial_supply(1);
+
+            let minter_badge: Bucket = ResourceBuilder::new_fungible()
+                .divisibility(DIVISIBILITY_NONE)
+                .metadata("name", "Stake Mint Auth")
+                .mint_initial_supply(1);
+
+            let stake_badge_address: ResourceAddress = ResourceBuilder::new_uuid_non_fungible()
+                .metadata("name", "Stake")
+                .mintable(rule!(require(minter_badge.resource_address())), LOCKED)
+                .burnable(rule!(require(minter_badge.resource_address())), LOCKED)
+                .create_with_no_initial_supply();
+
+            let access_rules: AccessRules = AccessRules::new()
+                .method("add_reward", rule!(require(admin_badge.resource_address())), LOCKED)
+                .method("top_up", rule!(require(admin_badge.resource_address())), LOCKED)
+                .default(rule!(allow_all), LOCKED);
+
+            let mut component = Self {
+                stake_resource,
+                staked: Vault::new(stake_resource),
+                stake_badge_address,
+                minter_badge: Vault::with_bucket(minter_badge),
+                streams: HashMap::new(),
+                reward_vaults: HashMap::new(),
+                stakes: HashMap::new(),
+            }
+            .instantiate();
+            component.add_access_check(access_rules);
+
+            (component.globalize(), admin_badge)
+        }
+
+        /// Starts streaming a new reward token. The whole bucket is distributed
+        /// evenly over the seconds between `start` and `end`, a `start` in the
+        /// past streams from now on.
+        pub fn add_reward(&mut self, rewards: Bucket, start: i64, end: i64) {
+            let resource: ResourceAddress = rewards.resource_address();
+            assert!(!self.streams.contains_key(&resource), "Reward token is already streamed");
+            let start: i64 = std::cmp::max(start, Self::now());
+            assert!(end > start, "Invalid reward period");
+
+            self.streams.insert(
+                resource,
+                RewardStream {
+                    rate: rewards.amount() / Decimal::from(end - start),
+                    start,
+                    end,
+                    reward_per_token: Decimal::zero(),
+                    last_update: start,
+                    idle: Decimal::zero(),
+                },
+            );
+            self.reward_vaults.insert(resource, Vault::with_bucket(rewards));
+        }
+
+        /// Adds reward tokens to a stream and moves its end to `end`. The tokens
+        /// not distributed yet and the new ones are spread over the rest of the
+        /// stream.
+        pub fn top_up(&mut self, rewards: Bucket, end: i64) {
+            let resource: ResourceAddress = rewards.resource_address();
+            self.update_stream(resource);
+
+            let now: i64 = Self::now();
+            let stream: &mut RewardStream = self.streams.get_mut(&resource).expect("Reward token is not streamed");
+            let from: i64 = std::cmp::max(now, stream.start);
+            assert!(end > from && end >= stream.end, "Invalid reward period");
+
+            let undistributed: Decimal = stream.idle
+                + if stream.end > from {
+                    stream.rate * Decimal::from(stream.end - from)
+                } else {
+                    Decimal::zero()
+                };
+            stream.idle = Decimal::zero();
+            stream.rate = (undistributed + rewards.amount()) / Decimal::from(end - from);
+            stream.end = end;
+            // A stream which had ended restarts now
+            if stream.last_update < from {
+                stream.last_update = from;
+            }
+
+            self.reward_vaults.get_mut(&resource).unwrap().put(rewards);
+        }
+
+        /// Stakes pool units and returns the NFT representing the stake.
+        pub fn stake(&mut self, pool_units: Bucket) -> Bucket {
+            assert!(pool_units.resource_address() == self.stake_resource, "Wrong token type passed in");
+            self.update_streams();
+
+            let badge: Bucket = self.minter_badge.authorize(|| {
+                borrow_resource_manager!(self.stake_badge_address)
+                    .mint_uuid_non_fungible(StakeBadge { staked_at: Self::now() })
+            });
+
+            let mut stake: Stake = Stake::default();
+            for (resource, stream) in self.streams.iter() {
+                stake.reward_per_token_paid.insert(*resource, stream.reward_per_token);
+            }
+            stake.amount = pool_units.amount();
+
+            self.stakes.insert(badge.non_fungible_local_id(), stake);
+            self.staked.put(pool_units);
+            badge
+        }
+
+        /// Adds pool units to an existing stake.
+        pub fn add_stake(&mut self, stake_badge: Proof, pool_units: Bucket) {
+            assert!(pool_units.resource_address() == self.stake_resource, "Wrong token type passed in");
+            let id: NonFungibleLocalId = self.stake_id(stake_badge);
+            self.settle(&id);
+
+            self.stakes.get_mut(&id).unwrap().amount += pool_units.amount();
+            self.staked.put(pool_units);
+        }
+
+        /// Removes pool units from a stake. The rewards earned so far stay
+        /// claimable.
+        pub fn unstake(&mut self, stake_badge: Proof, amount: Decimal) -> Bucket {
+            let id: NonFungibleLocalId = self.stake_id(stake_badge);
+            self.settle(&id);
+
+            let stake: &mut Stake = self.stakes.get_mut(&id).unwrap();
+            assert!(amount <= stake.amount, "Not enough pool units staked");
+            stake.amount -= amount;
+
+            self.staked.take(amount)
+        }
+
+        /// Claims every reward earned by a stake.
+        pub fn claim(&mut self, stake_badge: Proof) -> Vec<Bucket> {
+            let id: NonFungibleLocalId = self.stake_id(stake_badge);
+            self.settle(&id);
+
+            let owed: HashMap<ResourceAddress, Decimal> =
+                std::mem::take(&mut self.stakes.get_mut(&id).unwrap().owed);
+
+            owed.into_iter()
+                .map(|(resource, amount)| self.reward_vaults.get_mut(&resource).unwrap().take(amount))
+                .collect()
+        }
+
+        /// Returns the rewards a stake could claim right now.
+        pub fn earned(&self, id: NonFungibleLocalId) -> HashMap<ResourceAddress, Decimal> {
+            let stake: &Stake = self.stakes.get(&id).expect("Unknown stake");
+            let now: i64 = Self::now();
+
+            self.streams
+                .iter()
+                .map(|(resource, stream)| {
+                    let reward_per_token: Decimal = self.reward_per_token(stream, now);
+                    let paid: Decimal = stake
+                        .reward_per_token_paid
+                        .get(resource)
+                        .copied()
+                        .unwrap_or(Decimal::zero());
+                    let owed: Decimal = stake.owed.get(resource).copied().unwrap_or(Decimal::zero());
+                    (*resource, owed + stake.amount * (reward_per_token - paid))
+                })
+                .collect()
+        }
+
+        /// Returns every reward stream.
+        pub fn streams(&self) -> HashMap<ResourceAddress, RewardStream> {
+            self.streams.clone()
+        }
+
+        /// Returns the total amount of pool units staked.
+        pub fn total_staked(&self) -> Decimal {
+            self.staked.amount()
+        }
+
+        /// Returns what `reward_per_token` of a stream would be at `now`.
+        fn reward_per_token(&self, stream: &RewardStream, now: i64) -> Decimal {
+            let applicable: i64 = std::cmp::min(std::cmp::max(now, stream.start), stream.end);
+            if self.staked.is_empty() || applicable <= stream.last_update {
+                return stream.reward_per_token;
+            }
+            stream.reward_per_token
+                + stream.rate * Decimal::from(applicable - stream.last_update) / self.staked.amount()
+        }
+
+        /// Accrues `reward_per_token` of a stream up to now. Must be called
+        /// before the staked amount changes.
+        fn update_stream(&mut self, resource: ResourceAddress) {
+            let now: i64 = Self::now();
+            let stream: RewardStream = self.streams.get(&resource).expect("Reward token is not streamed").clone();
+            let reward_per_token: Decimal = self.reward_per_token(&stream, now);
+            let nothing_staked: bool = self.staked.is_empty();
+
+            let stream: &mut RewardStream = self.streams.get_mut(&resource).unwrap();
+            let applicable: i64 = std::cmp::min(std::cmp::max(now, stream.start), stream.end);
+
+            // Nobody earned the rewards of the seconds nothing was staked. They
+            // are spread over the rest of the stream, or kept for the next top
+            // up once it ended.
+            if nothing_staked && applicable > stream.last_update {
+                let idle: Decimal = stream.rate * Decimal::from(applicable - stream.last_update);
+                if stream.end > applicable {
+                    stream.rate += idle / Decimal::from(stream.end - applicable);
+                } else {
+                    stream.idle += idle;
+                }
+            }
+
+            stream.reward_per_token = reward_per_token;
+            stream.last_update = std::cmp::max(stream.last_update, applicable);
+        }
+
+        fn update_streams(&mut self) {
+            let resources: Vec<ResourceAddress> = self.streams.keys().copied().collect();
+            for resource in resources {
+                self.update_stream(resource);
+            }
+        }
+
+        /// Moves the rewards earned by a stake since its last settlement to its
+        /// owed rewards.
+        fn settle(&mut self, id: &NonFungibleLocalId) {
+            self.update_streams();
+
+            let stake: &mut Stake = self.stakes.get_mut(id).expect("Unknown stake");
+            for (resource, stream) in self.streams.iter() {
+                let paid: Decimal = stake
+                    .reward_per_token_paid
+                    .get(resource)
+                    .copied()
+                    .unwrap_or(Decimal::zero());
+                *stake.owed.entry(*resource).or_insert(Decimal::zero()) +=
+                    stake.amount * (stream.reward_per_token - paid);
+                stake.reward_per_token_paid.insert(*resource, stream.reward_per_token);
+            }
+        }
+
+        fn stake_id(&self, stake_badge: Proof) -> NonFungibleLocalId {
+            stake_badge
+                .validate_proof(self.stake_badge_address)
+                .expect("Invalid stake badge")
+                .non_fungible_local_id()
+        }
+
+        fn now() -> i64 {
+            Clock::current_time_rounded_to_minutes().seconds_since_unix_epoch
+        }
+    }
+}
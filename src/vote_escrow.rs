@@ -0,0 +1,303 @@
+use scrypto::radix_engine_interface::time::*;
+use scrypto::prelude::*;
+
+// Interface used by other blueprints weighting votes by locked governance tokens
+external_component! {
+    VoteEscrowComponentTarget {
+        fn voting_power(&self, id: NonFungibleLocalId, time: i64) -> Decimal;
+        fn lock_badge_address(&self) -> ResourceAddress;
+    }
+}
+
+/// The data held by the NFT representing a lock. It can't be withdrawn from the
+/// account it was deposited to, so voting power can't be transferred.
+#[derive(NonFungibleData)]
+pub struct LockBadge {
+    /// Seconds since the unix epoch at which the lock was created.
+    pub created_at: i64,
+}
+
+/// Governance tokens locked until `unlock_time`.
+#[derive(ScryptoCategorize, ScryptoEncode, ScryptoDecode, LegacyDescribe, Clone, Debug)]
+pub struct Lock {
+    /// The amount of governance tokens locked.
+    pub amount: Decimal,
+    /// Seconds since the unix epoch at which the tokens can be withdrawn.
+    pub unlock_time: i64,
+    /// Seconds since the unix epoch at which the lock was created.
+    pub created_at: i64,
+}
+
+/// A call to a pool method made with one of the admin badges held by the
+/// governor, e.g. `Radiswap::set_fee` or `ConstantSumAmm::set_strike_rate`.
+#[derive(ScryptoCategorize, ScryptoEncode, ScryptoDecode, LegacyDescribe, Clone, Debug)]
+pub struct Proposal {
+    /// What the proposal does, for voters.
+    pub description: String,
+    /// The component whose method is called.
+    pub component: ComponentAddress,
+    /// The method which is called.
+    pub method: String,
+    /// The SBOR encoded arguments of the method, as produced by `args!`.
+    pub args: Vec<u8>,
+    /// The admin badge presented when calling the method.
+    pub admin_badge: ResourceAddress,
+    /// Seconds since the unix epoch at which voting started. Voting power is
+    /// measured at this time.
+    pub start: i64,
+    /// Seconds since the unix epoch at which voting ends.
+    pub end: i64,
+    /// The voting power in favour.
+    pub votes_for: Decimal,
+    /// The voting power against.
+    pub votes_against: Decimal,
+    /// The locks which already voted.
+    pub voters: HashSet<NonFungibleLocalId>,
+    /// Whether the proposal was executed.
+    pub executed: bool,
+}
+
+#[blueprint]
+mod vote_escrow {
+    struct VoteEscrow {
+        /// The governance token which can be locked.
+        governance_token: ResourceAddress,
+        /// A vault containing all the locked governance tokens.
+        locked: Vault,
+        /// The resource address of the lock NFTs.
+        lock_badge_address: ResourceAddress,
+        /// A vault containing the badge which mints the lock NFTs.
+        minter_badge: Vault,
+        /// Every lock which wasn't withdrawn yet.
+        locks: HashMap<NonFungibleLocalId, Lock>,
+        /// The longest a lock can last, in seconds. A lock of that length has a
+        /// voting power equal to its amount.
+        max_lock: i64,
+        /// The admin badges of the pools, handed to the governor.
+        admin_badges: HashMap<ResourceAddress, Vault>,
+        /// Every proposal, by id.
+        proposals: Vec<Proposal>,
+        /// The voting power needed to create a proposal.
+        proposal_threshold: Decimal,
+        /// The voting power which must take part in a vote for it to pass.
+        quorum: Decimal,
+        /// How long voting on a proposal lasts, in seconds.
+        voting_period: i64,
+    }
+
+    impl VoteEscrow {
+        /// Creates a governor for `governance_token` where locks last at most
+        /// `max_lock_years` years.
+        pub fn instantiate_vote_escrow(
+            governance_token: ResourceAddress,
+            max_lock_years: i64,
+            proposal_threshold: Decimal,
+            quorum: Decimal,
+            voting_period: i64,
+        ) -> ComponentAddress {
+            assert!(max_lock_years > 0, "Locks must be allowed to last at least a year");
+            assert!(voting_period > 0, "Voting period must be positive");
+
+            let minter_badge: Bucket = ResourceBuilder::new_fungible()
+                .divisibility(DIVISIBILITY_NONE)
+                .metadata("name", "Vote Escrow Mint Auth")
+                .mint_initial_supply(1);
+
+            let lock_badge_address: ResourceAddress = ResourceBuilder::new_uuid_non_fungible()
+                .metadata("name", "Vote Escrow Lock")
+                .mintable(rule!(require(minter_badge.resource_address())), LOCKED)
+                .burnable(rule!(require(minter_badge.resource_address())), LOCKED)
+                .restrict_withdraw(rule!(deny_all), LOCKED)
+                .create_with_no_initial_supply();
+
+            Self {
+                governance_token,
+                locked: Vault::new(governance_token),
+                lock_badge_address,
+                minter_badge: Vault::with_bucket(minter_badge),
+                locks: HashMap::new(),
+                max_lock: max_lock_years * 365 * 24 * 60 * 60,
+                admin_badges: HashMap::new(),
+                proposals: Vec::new(),
+                proposal_threshold,
+                quorum,
+                voting_period,
+            }
+            .instantiate()
+            .globalize()
+        }
+
+        /// Locks governance tokens until `unlock_time` and returns the lock NFT.
+        pub fn lock(&mut self, tokens: Bucket, unlock_time: i64) -> Bucket {
+            assert!(tokens.resource_address() == self.governance_token, "Wrong token type passed in");
+            assert!(!tokens.is_empty(), "Nothing to lock");
+            let now: i64 = Self::now();
+            assert!(
+                unlock_time > now && unlock_time - now <= self.max_lock,
+                "Invalid unlock time"
+            );
+
+            let badge: Bucket = self.minter_badge.authorize(|| {
+                borrow_resource_manager!(self.lock_badge_address)
+                    .mint_uuid_non_fungible(LockBadge { created_at: now })
+            });
+            self.locks.insert(
+                badge.non_fungible_local_id(),
+                Lock {
+                    amount: tokens.amount(),
+                    unlock_time,
+                    created_at: now,
+                },
+            );
+            self.locked.put(tokens);
+
+            badge
+        }
+
+        /// Moves the unlock time of a lock further away.
+        pub fn extend_lock(&mut self, lock_badge: Proof, unlock_time: i64) {
+            let id: NonFungibleLocalId = self.lock_id(lock_badge);
+            let now: i64 = Self::now();
+            let lock: &mut Lock = self.locks.get_mut(&id).expect("Lock was withdrawn");
+            assert!(
+                unlock_time > lock.unlock_time && unlock_time - now <= self.max_lock,
+                "Invalid unlock time"
+            );
+            lock.unlock_time = unlock_time;
+        }
+
+        /// Returns the governance tokens of an expired lock.
+        pub fn withdraw(&mut self, lock_badge: Proof) -> Bucket {
+            let id: NonFungibleLocalId = self.lock_id(lock_badge);
+            let lock: &Lock = self.locks.get(&id).expect("Lock was withdrawn");
+            assert!(Self::now() >= lock.unlock_time, "Lock has not expired yet");
+
+            let lock: Lock = self.locks.remove(&id).unwrap();
+            self.locked.take(lock.amount)
+        }
+
+        /// Returns the voting power of a lock at `time`. It decays linearly from
+        /// `amount * remaining / max_lock` to zero at the unlock time.
+        pub fn voting_power(&self, id: NonFungibleLocalId, time: i64) -> Decimal {
+            match self.locks.get(&id) {
+                Some(lock) if lock.unlock_time > time && lock.created_at <= time => {
+                    lock.amount * Decimal::from(lock.unlock_time - time) / Decimal::from(self.max_lock)
+                }
+                _ => Decimal::zero(),
+            }
+        }
+
+        /// Takes custody of a pool admin badge so proposals can use it.
+        pub fn deposit_admin_badge(&mut self, admin_badge: Bucket) {
+            let resource: ResourceAddress = admin_badge.resource_address();
+            self.admin_badges
+                .entry(resource)
+                .or_insert_with(|| Vault::new(resource))
+                .put(admin_badge);
+        }
+
+        /// Creates a proposal to call `method` on `component` with `admin_badge`.
+        /// The proposer needs at least `proposal_threshold` voting power.
+        pub fn propose(
+            &mut self,
+            lock_badge: Proof,
+            description: String,
+            component: ComponentAddress,
+            method: String,
+            args: Vec<u8>,
+            admin_badge: ResourceAddress,
+        ) -> usize {
+            let id: NonFungibleLocalId = self.lock_id(lock_badge);
+            let now: i64 = Self::now();
+            assert!(
+                self.voting_power(id, now) >= self.proposal_threshold,
+                "Not enough voting power to propose"
+            );
+            assert!(
+                self.admin_badges.contains_key(&admin_badge),
+                "The governor does not hold this admin badge"
+            );
+
+            self.proposals.push(Proposal {
+                description,
+                component,
+                method,
+                args,
+                admin_badge,
+                start: now,
+                end: now + self.voting_period,
+                votes_for: Decimal::zero(),
+                votes_against: Decimal::zero(),
+                voters: HashSet::new(),
+                executed: false,
+            });
+
+            self.proposals.len() - 1
+        }
+
+        /// Votes on a proposal with the voting power the lock had when voting
+        /// started. Each lock votes once per proposal.
+        pub fn vote(&mut self, lock_badge: Proof, proposal_id: usize, support: bool) {
+            let id: NonFungibleLocalId = self.lock_id(lock_badge);
+            let now: i64 = Self::now();
+            let start: i64 = self.proposals.get(proposal_id).expect("Unknown proposal").start;
+            let power: Decimal = self.voting_power(id.clone(), start);
+            assert!(power > Decimal::zero(), "No voting power");
+
+            let proposal: &mut Proposal = &mut self.proposals[proposal_id];
+            assert!(now < proposal.end, "Voting is over");
+            assert!(proposal.voters.insert(id), "This lock already voted");
+
+            if support {
+                proposal.votes_for += power;
+            } else {
+                proposal.votes_against += power;
+            }
+        }
+
+        /// Executes a proposal which passed once its voting period is over.
+        pub fn execute(&mut self, proposal_id: usize) {
+            let now: i64 = Self::now();
+            let proposal: &mut Proposal = self.proposals.get_mut(proposal_id).expect("Unknown proposal");
+            assert!(now >= proposal.end, "Voting is not over");
+            assert!(!proposal.executed, "Proposal was already executed");
+            assert!(
+                proposal.votes_for + proposal.votes_against >= self.quorum,
+                "Quorum was not reached"
+            );
+            assert!(proposal.votes_for > proposal.votes_against, "Proposal was rejected");
+            proposal.executed = true;
+
+            let proposal: Proposal = proposal.clone();
+            self.admin_badges[&proposal.admin_badge].authorize(|| {
+                Runtime::call_method::<_, ()>(proposal.component, &proposal.method, proposal.args)
+            });
+        }
+
+        /// Returns a proposal.
+        pub fn proposal(&self, proposal_id: usize) -> Proposal {
+            self.proposals.get(proposal_id).expect("Unknown proposal").clone()
+        }
+
+        /// Returns the resource address of the lock NFTs.
+        pub fn lock_badge_address(&self) -> ResourceAddress {
+            self.lock_badge_address
+        }
+
+        /// Returns a lock.
+        pub fn lock_info(&self, id: NonFungibleLocalId) -> Lock {
+            self.locks.get(&id).expect("Lock was withdrawn").clone()
+        }
+
+        fn lock_id(&self, lock_badge: Proof) -> NonFungibleLocalId {
+            lock_badge
+                .validate_proof(self.lock_badge_address)
+                .expect("Invalid lock badge")
+                .non_fungible_local_id()
+        }
+
+        fn now() -> i64 {
+            Clock::current_time_rounded_to_minutes().seconds_since_unix_epoch
+        }
+    }
+}
@@ -0,0 +1,513 @@
+mod fixtures;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use defi_tools::client::{Client, ConstantSumAmmState, RadiswapState};
+use fixtures::*;
+use lending::manifests;
+use lending::manifests::LockingLiquidity;
+use radix_engine::transaction::TransactionReceipt;
+use scrypto::prelude::*;
+use transaction::model::TransactionManifest;
+
+// Runs the public methods of Radiswap and ConstantSumAmm at several pool sizes, and those of LendingMarket,
+// PriceOracle and StakingRewards with their amounts scaled the same way, and compares what they cost with
+// `tests/costs.csv`. A method fails the test when any of its costs grows more than `TOLERANCE_PERCENT` above
+// the baseline, or when it writes more substates. The test fails without a baseline: run it with
+// `UPDATE_COST_BASELINE=1` to record one, also after an intended change, and commit it. Every run also writes
+// its measurements to `target/tmp/costs.csv`.
+//
+// Not measured: `instantiate_radiswap_with_insurance`, ConstantSumAmm's `set_strike_rate`, which needs every
+// collateral claim redeemed, and `resume_trading`, LendingMarket's liquidations, flash loans and admin methods,
+// and the methods of MarketFactory, InsuranceFund, the rate models, VoteEscrow, Multisig and EmissionController.
+
+const TOLERANCE_PERCENT: u64 = 2;
+
+const RADISWAP: &str = "Radiswap";
+const MARKET: &str = "ConstantSumAmm";
+const ORACLE: &str = "PriceOracle";
+const LENDING: &str = "LendingMarket";
+const STAKING: &str = "StakingRewards";
+
+const BASELINE_HEADER: &str = "size,method,cost_units,read_cost_units,write_cost_units,substates_written";
+
+// Pool sizes, as the token A locked in the market. The Radiswap pool holds ten times as much, and the amount
+// of every call is scaled with it.
+const SIZES: [(&str, i64); 3] = [("small", 10), ("medium", 1000), ("large", 100000)];
+
+// What a transaction cost. Reads and writes are the cost units charged for accessing substates, as the engine
+// doesn't count them, and the substates written are those the commit updated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Cost {
+    cost_units: u64,
+    read_cost_units: u64,
+    write_cost_units: u64,
+    substates_written: u64,
+}
+
+impl Cost {
+    fn of(receipt: &TransactionReceipt) -> Self {
+        let fee_summary = &receipt.execution.fee_summary;
+        let breakdown = |kind: &str| -> u64 {
+            fee_summary
+                .cost_breakdown
+                .iter()
+                .filter(|(name, _)| name.contains(kind))
+                .map(|(_, units)| u64::from(*units))
+                .sum()
+        };
+        Self {
+            cost_units: u64::from(fee_summary.cost_unit_consumed),
+            read_cost_units: breakdown("read_substate"),
+            write_cost_units: breakdown("write_substate"),
+            substates_written: receipt.expect_commit().state_updates.up_substates.len() as u64,
+        }
+    }
+
+    fn columns(&self) -> [(&'static str, u64); 4] {
+        [
+            ("cost units", self.cost_units),
+            ("read cost units", self.read_cost_units),
+            ("write cost units", self.write_cost_units),
+            ("substates written", self.substates_written),
+        ]
+    }
+}
+
+type Costs = BTreeMap<(String, String), Cost>;
+
+// Lines starting with `#` are notes and are skipped
+fn parse_baseline(text: &str) -> Result<Costs, String> {
+    let mut costs = Costs::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line == BASELINE_HEADER {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').collect();
+        let numbers: Result<Vec<u64>, _> = fields.iter().skip(2).map(|field| field.parse::<u64>()).collect();
+        match (fields.len(), numbers) {
+            (6, Ok(numbers)) => {
+                let cost = Cost {
+                    cost_units: numbers[0],
+                    read_cost_units: numbers[1],
+                    write_cost_units: numbers[2],
+                    substates_written: numbers[3],
+                };
+                costs.insert((fields[0].to_owned(), fields[1].to_owned()), cost);
+            }
+            _ => return Err(format!("line {}: expected {}", index + 1, BASELINE_HEADER)),
+        }
+    }
+    Ok(costs)
+}
+
+fn format_costs(costs: &Costs) -> String {
+    let mut text = format!("{}\n", BASELINE_HEADER);
+    for ((size, method), cost) in costs {
+        text += &format!("{},{},{},{},{},{}\n", size, method, cost.cost_units, cost.read_cost_units,
+            cost.write_cost_units, cost.substates_written);
+    }
+    text
+}
+
+// Differences with the baseline which fail the test
+fn regressions(baseline: &Costs, costs: &Costs) -> Vec<String> {
+    let mut errors = Vec::new();
+    for ((size, method), cost) in costs {
+        let expected = match baseline.get(&(size.clone(), method.clone())) {
+            Some(expected) => expected,
+            None => {
+                errors.push(format!("{} ({}): not in the baseline", method, size));
+                continue;
+            }
+        };
+        for ((name, found), (_, limit)) in cost.columns().iter().zip(expected.columns()) {
+            let allowed = if *name == "substates written" { limit } else { limit * (100 + TOLERANCE_PERCENT) / 100 };
+            if *found > allowed {
+                errors.push(format!("{} ({}): {} {} above the baseline of {}", method, size, found, name, limit));
+            }
+        }
+    }
+    for (size, method) in baseline.keys().filter(|key| !costs.contains_key(*key)) {
+        errors.push(format!("{} ({}): in the baseline but not measured anymore", method, size));
+    }
+    errors
+}
+
+// A market at one of the sizes and the costs measured on it so far
+struct Bench {
+    env: TestEnv,
+    market: Market,
+    size: &'static str,
+    scale: Decimal,
+    costs: Costs,
+}
+
+impl Bench {
+    fn new(size: &'static str, locked_a: Decimal) -> Self {
+        let mut env = TestEnv::new();
+        let market = env.new_market(MarketParams {
+            locked_a,
+            pool_depth_a: locked_a * 10,
+            market_premium: dec!("1.03"),
+            ..Default::default()
+        });
+        Self { env, market, size, scale: locked_a / 1000, costs: Costs::new() }
+    }
+
+    // Amounts of the calls are given for the medium size
+    fn amount(&self, medium: Decimal) -> Decimal {
+        medium * self.scale
+    }
+
+    // Executes a manifest which must succeed and records its costs as those of `method`
+    fn measure(&mut self, blueprint: &str, method: &str, manifest: TransactionManifest) -> TransactionReceipt {
+        let name = format!("{}::{}", blueprint, method);
+        println!("{} ({})", name, self.size);
+        let receipt = self.env.execute_success(manifest);
+        self.costs.insert((self.size.to_owned(), name), Cost::of(&receipt));
+        receipt
+    }
+
+    fn radiswap_state(&self) -> RadiswapState {
+        Client::new(self.env.test_runner.substate_store()).component_state(self.market.pool.component).unwrap()
+    }
+
+    fn market_state(&self) -> ConstantSumAmmState {
+        Client::new(self.env.test_runner.substate_store()).component_state(self.market.component).unwrap()
+    }
+
+    fn radiswap(&mut self) {
+        let (account, package) = (self.env.account, self.env.package);
+        let pool = self.market.pool.component;
+        let (token_a, token_b, pool_units) = (self.market.token_a, self.market.token_b, self.market.pool.pool_units);
+        let depth_a = self.amount(dec!(10000));
+        let price = self.market.strike_rate * dec!("1.03");
+
+        let (fresh_a, fresh_b) = (self.env.new_token(depth_a), self.env.new_token(depth_a * price));
+        let manifest = manifests::instantiate_radiswap(package, account, fresh_a, depth_a, fresh_b, depth_a * price,
+            dec!("0.003"));
+        self.measure(RADISWAP, "instantiate_radiswap", manifest);
+
+        self.measure(RADISWAP, "swap", manifests::swap(account, pool, token_a, self.amount(dec!(10))));
+        self.measure(RADISWAP, "swap/token_b", manifests::swap(account, pool, token_b, self.amount(dec!(10)) * price));
+        let (amount_a, amount_b) = (self.amount(dec!(100)), self.amount(dec!(100)) * price);
+        let manifest = manifests::add_liquidity(account, pool, token_a, amount_a, token_b, amount_b);
+        self.measure(RADISWAP, "add_liquidity", manifest);
+        let amount = self.env.balance(pool_units) / 100;
+        self.measure(RADISWAP, "remove_liquidity", manifests::remove_liquidity(account, pool, pool_units, amount));
+
+        let getters: [(&str, Vec<u8>); 6] = [
+            ("spot_price", args!(token_a)),
+            ("twap", args!(token_a, 600i64)),
+            ("price", args!(token_a)),
+            ("is_halted", args!()),
+            ("token_addresses", args!()),
+            ("circuit_breaker", args!()),
+        ];
+        for (method, args) in getters {
+            self.measure(RADISWAP, method, manifests::call_method(account, pool, method, args));
+        }
+
+        // Setters, called with the current settings so the measurements which follow are not affected
+        let admin_badge = self.market.pool.admin_badge;
+        let state = self.radiswap_state();
+        let breaker = state.circuit_breaker;
+        self.measure(RADISWAP, "set_fee", manifests::set_fee(account, pool, admin_badge, state.fee));
+        let manifest = manifests::set_insurance_fund(account, pool, admin_badge, state.insurance_fund,
+            state.protocol_fee_share);
+        self.measure(RADISWAP, "set_insurance_fund", manifest);
+        let manifest = manifests::set_circuit_breaker(account, pool, admin_badge, breaker.max_move, breaker.window,
+            breaker.max_reference_deviation, breaker.cooldown, breaker.guardian_badge);
+        self.measure(RADISWAP, "set_circuit_breaker", manifest);
+    }
+
+    fn market(&mut self) {
+        let (account, package) = (self.env.account, self.env.package);
+        let market = self.market.component;
+        let (token_a, token_b) = (self.market.token_a, self.market.token_b);
+        let (cctoken_a, cctoken_b, bond_token) = (self.market.cctoken_a, self.market.cctoken_b, self.market.bond_token);
+        let strike_rate = self.market.strike_rate;
+
+        // A second market over the same pool, to measure its instantiation
+        let (rate_model, rate_model_badge) = self.env.new_rate_model();
+        let (insurance_fund, insurance_badge) = self.env.new_insurance_fund();
+        let locking = LockingLiquidity {
+            token_a,
+            amount_a: self.amount(dec!(1000)),
+            token_a_name: "Collateral Claim A".to_owned(),
+            token_a_symbol: "ccA".to_owned(),
+            token_b,
+            amount_b: self.amount(dec!(1000)) * strike_rate,
+            token_b_name: "Collateral Claim B".to_owned(),
+            token_b_symbol: "ccB".to_owned(),
+            maturity: self.market.maturity,
+            required_interest: dec!("0.1"),
+            strike_rate,
+            lp_name: "A/B LP".to_owned(),
+            lp_symbol: "LP-AB".to_owned(),
+            amm_address: self.market.pool.component,
+            price_source: self.market.pool.component,
+            rate_model,
+            rate_model_badge,
+            insurance_fund,
+            insurance_badge,
+        };
+        self.measure(MARKET, "locking_liquidity", manifests::locking_liquidity(package, account, &locking));
+
+        // Lend on both sides so the market holds collateral claims to rebalance with
+        self.measure(MARKET, "lend_a", manifests::lend_a(account, market, token_b, self.amount(dec!(10000))));
+        self.measure(MARKET, "lend_b", manifests::lend_b(account, market, token_a, self.amount(dec!(10))));
+        let manifest = manifests::option_a_deposit(account, market, token_a, self.amount(dec!(1)));
+        self.measure(MARKET, "option_a_deposit", manifest);
+        let manifest = manifests::option_b_deposit(account, market, token_b, self.amount(dec!(100)));
+        self.measure(MARKET, "option_b_deposit", manifest);
+        let (amount, cc_amount) = (self.amount(dec!(1)), self.amount(dec!(100)));
+        let manifest = manifests::convert_option(account, market, token_a, amount, cctoken_b, cc_amount);
+        self.measure(MARKET, "convert_option", manifest);
+
+        let (borrow_amount, collateral) = (self.amount(dec!(100)), self.amount(dec!(1)));
+        let borrow = manifests::borrow_a(account, market, borrow_amount, token_a, collateral, cctoken_b, borrow_amount);
+        self.measure(MARKET, "borrow_a", borrow.clone());
+        let loan = self.market_state().loans.keys().next().unwrap().clone();
+        let getters: [(&str, Vec<u8>); 12] = [
+            ("health_factor", args!(loan.clone())),
+            ("loan", args!(loan.clone())),
+            ("utilization", args!()),
+            ("borrow_rate", args!()),
+            ("supply_rate", args!()),
+            ("redemption_rate", args!()),
+            ("haircuts", args!()),
+            ("auction_lots", args!()),
+            ("interest", args!()),
+            ("resource_addresses", args!()),
+            ("lp_resource_address", args!()),
+            ("circuit_breaker", args!()),
+        ];
+        for (method, args) in getters {
+            self.measure(MARKET, method, manifests::call_method(account, market, method, args));
+        }
+
+        // Token A trades above the strike rate, so rebalancing into token B pays the keeper
+        let manifest = manifests::rebalance_transaction(account, market, token_b, self.amount(dec!(500)), dec!(0));
+        self.measure(MARKET, "rebalance_transaction", manifest);
+
+        let state = self.market_state();
+        let amount = self.amount(dec!(10)) * strike_rate;
+        let manifest = manifests::flash_loan_roundtrip(account, market, token_b, amount, amount * state.flash_loan_fee,
+            state.flash_loan_receipt_address);
+        self.measure(MARKET, "flash_loan", manifest);
+        let manifest = manifests::repay_a(account, market, self.market.loan_badge, loan, token_b, borrow_amount);
+        self.measure(MARKET, "repay_a", manifest);
+
+        // Setters, called with the current settings except for a liquidation threshold making loans unhealthy
+        let admin_badge = self.market.admin_badge;
+        let breaker = state.circuit_breaker;
+        let manifest = manifests::set_rebalance_params(account, market, admin_badge, state.max_price_deviation,
+            state.keeper_reward);
+        self.measure(MARKET, "set_rebalance_params", manifest);
+        let manifest = manifests::set_fee_params(account, market, admin_badge, state.protocol_fee_share,
+            state.flash_loan_fee);
+        self.measure(MARKET, "set_fee_params", manifest);
+        let manifest = manifests::set_price_source(account, market, admin_badge, state.price_source);
+        self.measure(MARKET, "set_price_source", manifest);
+        let manifest = manifests::set_auction_params(account, market, admin_badge, state.auction_mode,
+            state.auction_start_multiplier, state.auction_floor_multiplier, state.auction_duration);
+        self.measure(MARKET, "set_auction_params", manifest);
+        let manifest = manifests::set_circuit_breaker(account, market, admin_badge, breaker.max_move, breaker.window,
+            breaker.max_reference_deviation, breaker.cooldown, breaker.guardian_badge);
+        self.measure(MARKET, "set_circuit_breaker", manifest);
+        let manifest = manifests::set_liquidation_params(account, market, admin_badge, dec!("0.5"), state.close_factor,
+            state.liquidation_bonus);
+        self.measure(MARKET, "set_liquidation_params", manifest);
+
+        // A second loan, left open as the first one was repaid. It is partly liquidated, then forfeited at
+        // maturity and auctioned.
+        self.measure(MARKET, "borrow_a/second_loan", borrow);
+        let loan = self.market_state().loans.keys().next().unwrap().clone();
+        let manifest = manifests::liquidate(account, market, loan.clone(), token_b, self.amount(dec!(50)), false);
+        self.measure(MARKET, "liquidate", manifest);
+
+        // Liquidity on both sides with the bonds the interest of the pool requires, then half of it back
+        let (lp_token, maturity) = (self.market.lp_token, self.market.maturity);
+        let cc_amount = self.amount(dec!(10));
+        let interest: Decimal = self.env.call(market, "interest", args!());
+        let manifest = manifests::deposit_liquidity_a(account, market, cctoken_a, cc_amount, bond_token,
+            cc_amount * interest, strike_rate, maturity);
+        self.measure(MARKET, "deposit_liquidity_a", manifest);
+        let cc_amount = self.amount(dec!(10)) * strike_rate;
+        let interest: Decimal = self.env.call(market, "interest", args!());
+        let manifest = manifests::deposit_liquidity_b(account, market, cctoken_b, cc_amount, bond_token,
+            (cc_amount / strike_rate) * interest, strike_rate, maturity);
+        self.measure(MARKET, "deposit_liquidity_b", manifest);
+        let amount = self.env.balance(lp_token) / 2;
+        let manifest = manifests::withdraw_liquidity(account, market, lp_token, amount, strike_rate, maturity);
+        self.measure(MARKET, "withdraw_liquidity", manifest);
+
+        self.env.set_time(self.market.maturity + 60);
+        let (cc_amount, bond_amount) = (self.amount(dec!(1000)), self.amount(dec!(1)));
+        let manifest = manifests::option_b_withdraw(account, market, token_b, cctoken_b, cc_amount, bond_token,
+            bond_amount);
+        self.measure(MARKET, "option_b_withdraw", manifest);
+        let manifest = manifests::option_a_withdraw(account, market, token_a, cctoken_a, bond_amount, bond_token,
+            bond_amount);
+        self.measure(MARKET, "option_a_withdraw", manifest);
+        self.measure(MARKET, "forfeit_loan", manifests::forfeit_loan(account, market, loan));
+        let lot = self.market_state().auction_lots.keys().next().unwrap().clone();
+        for method in ["auction_price", "auction_lot"] {
+            self.measure(MARKET, method, manifests::call_method(account, market, method, args!(lot.clone())));
+        }
+        let manifest = manifests::bid(account, market, lot, token_b, self.amount(dec!(2)) * strike_rate);
+        self.measure(MARKET, "bid", manifest);
+    }
+
+    // A swap of a fifth of the pool trips its circuit breaker and hands the input back, then the admin resumes
+    // trading
+    fn circuit_breaker(&mut self) {
+        let account = self.env.account;
+        let (pool, admin_badge) = (self.market.pool.component, self.market.pool.admin_badge);
+        let manifest = manifests::swap(account, pool, self.market.token_a, self.amount(dec!(2000)));
+        self.measure(RADISWAP, "swap/tripping_breaker", manifest);
+        self.measure(RADISWAP, "resume_trading", manifests::resume_trading(account, pool, admin_badge));
+    }
+
+    // A lending market priced by an oracle with a single feeder, on fresh tokens. Resources are created in order:
+    // the admin badge, the minter badge and the feeder, position or stake badge.
+    fn lending_market(&mut self) {
+        let (account, package) = (self.env.account, self.env.package);
+        let quote = self.env.new_token(self.amount(dec!(100000)));
+        let collateral = self.env.new_token(self.amount(dec!(1000)));
+
+        let manifest = manifests::instantiate_price_oracle(package, account, collateral, quote, 3600, 1);
+        let receipt = self.measure(ORACLE, "instantiate_price_oracle", manifest);
+        let commit = receipt.expect_commit();
+        let oracle = commit.entity_changes.new_component_addresses[0];
+        let (oracle_admin_badge, feeder_badge) =
+            (commit.entity_changes.new_resource_addresses[0], commit.entity_changes.new_resource_addresses[2]);
+        self.measure(ORACLE, "add_feeder", manifests::add_feeder(account, oracle, oracle_admin_badge, "feeder"));
+        let feeder_id = NonFungibleLocalId::integer(1);
+        let manifest = manifests::push_price(account, oracle, feeder_badge, feeder_id, dec!(100));
+        self.measure(ORACLE, "push_price", manifest);
+        self.measure(ORACLE, "price", manifests::call_method(account, oracle, "price", args!(collateral)));
+
+        let receipt = self.measure(LENDING, "instantiate_lending_market",
+            manifests::instantiate_lending_market(package, account, quote));
+        let commit = receipt.expect_commit();
+        let lending_market = commit.entity_changes.new_component_addresses[0];
+        let (admin_badge, position_badge) =
+            (commit.entity_changes.new_resource_addresses[0], commit.entity_changes.new_resource_addresses[2]);
+        let manifest = manifests::list_asset(account, lending_market, admin_badge, quote, oracle, dec!("0.8"),
+            dec!("0.02"), dec!("0.1"), dec!("0.1"));
+        let receipt = self.measure(LENDING, "list_asset", manifest);
+        let receipt_token = receipt.expect_commit().entity_changes.new_resource_addresses[0];
+        self.env.execute_success(manifests::list_asset(account, lending_market, admin_badge, collateral, oracle,
+            dec!("0.8"), dec!(0), dec!(0), dec!(0)));
+
+        // 10 collateral at 100 allow borrowing up to 800
+        let manifest = manifests::lending_deposit(account, lending_market, quote, self.amount(dec!(10000)));
+        self.measure(LENDING, "deposit", manifest);
+        self.measure(LENDING, "open_position", manifests::open_position(account, lending_market));
+        let position_id = self.env.non_fungible_ids(account, position_badge)[0].clone();
+        let manifest = manifests::deposit_collateral(account, lending_market, position_badge, position_id.clone(),
+            collateral, self.amount(dec!(10)));
+        self.measure(LENDING, "deposit_collateral", manifest);
+        let manifest = manifests::lending_borrow(account, lending_market, position_badge, position_id.clone(), quote,
+            self.amount(dec!(500)));
+        self.measure(LENDING, "borrow", manifest);
+
+        let getters: [(&str, Vec<u8>); 5] = [
+            ("exchange_rate", args!(quote)),
+            ("utilization", args!(quote)),
+            ("borrow_rate", args!(quote)),
+            ("supply_rate", args!(quote)),
+            ("position_values", args!(position_id.clone())),
+        ];
+        for (method, args) in getters {
+            self.measure(LENDING, method, manifests::call_method(account, lending_market, method, args));
+        }
+
+        // Repaying with some extra for the interest, the change is handed back
+        self.env.advance_time(3600);
+        let manifest = manifests::lending_repay(account, lending_market, position_badge, position_id.clone(), quote,
+            self.amount(dec!(501)));
+        self.measure(LENDING, "repay", manifest);
+        let manifest = manifests::lending_withdraw_collateral(account, lending_market, position_badge, position_id,
+            collateral, self.amount(dec!(5)));
+        self.measure(LENDING, "withdraw_collateral", manifest);
+        let manifest = manifests::lending_redeem(account, lending_market, receipt_token, self.amount(dec!(1000)));
+        self.measure(LENDING, "redeem", manifest);
+    }
+
+    // A reward stream on fresh tokens, staked into halfway through and topped up
+    fn staking_rewards(&mut self) {
+        let (account, package) = (self.env.account, self.env.package);
+        let stake_token = self.env.new_token(self.amount(dec!(100)));
+        let reward = self.env.new_token(self.amount(dec!(1200)));
+
+        let manifest = manifests::instantiate_staking_rewards(package, account, stake_token);
+        let receipt = self.measure(STAKING, "instantiate_staking_rewards", manifest);
+        let commit = receipt.expect_commit();
+        let staking = commit.entity_changes.new_component_addresses[0];
+        let (admin_badge, stake_badge) =
+            (commit.entity_changes.new_resource_addresses[0], commit.entity_changes.new_resource_addresses[2]);
+
+        let (start, end) = (self.env.now, self.env.now + 1200);
+        let manifest = manifests::add_reward(account, staking, admin_badge, reward, self.amount(dec!(600)), start, end);
+        self.measure(STAKING, "add_reward", manifest);
+        self.measure(STAKING, "stake", manifests::stake(account, staking, stake_token, self.amount(dec!(50))));
+        let stake_id = self.env.non_fungible_ids(account, stake_badge)[0].clone();
+
+        self.env.advance_time(600);
+        let manifest = manifests::add_stake(account, staking, stake_badge, stake_id.clone(), stake_token,
+            self.amount(dec!(50)));
+        self.measure(STAKING, "add_stake", manifest);
+        let manifest = manifests::top_up(account, staking, admin_badge, reward, self.amount(dec!(600)), end + 600);
+        self.measure(STAKING, "top_up", manifest);
+        let getters: [(&str, Vec<u8>); 3] = [
+            ("earned", args!(stake_id.clone())),
+            ("streams", args!()),
+            ("total_staked", args!()),
+        ];
+        for (method, args) in getters {
+            self.measure(STAKING, method, manifests::call_method(account, staking, method, args));
+        }
+
+        self.env.advance_time(600);
+        self.measure(STAKING, "claim", manifests::claim_rewards(account, staking, stake_badge, stake_id.clone()));
+        let manifest = manifests::unstake(account, staking, stake_badge, stake_id, self.amount(dec!(100)));
+        self.measure(STAKING, "unstake", manifest);
+    }
+}
+
+#[test]
+fn test_costs_within_baseline() {
+    let mut costs = Costs::new();
+    for (size, locked_a) in SIZES {
+        let mut bench = Bench::new(size, Decimal::from(locked_a));
+        bench.radiswap();
+        bench.market();
+        bench.circuit_breaker();
+        bench.lending_market();
+        bench.staking_rewards();
+        costs.append(&mut bench.costs);
+    }
+
+    let measured = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("costs.csv");
+    std::fs::write(&measured, format_costs(&costs)).unwrap();
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("costs.csv");
+    if std::env::var_os("UPDATE_COST_BASELINE").is_some() {
+        std::fs::write(&path, format_costs(&costs)).unwrap();
+        return;
+    }
+    let text = std::fs::read_to_string(&path).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+    let baseline = parse_baseline(&text).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+    assert!(!baseline.is_empty(), "No baseline recorded in {}, run with UPDATE_COST_BASELINE=1 and commit it",
+        path.display());
+
+    let errors = regressions(&baseline, &costs);
+    assert!(errors.is_empty(), "Costs regressed, see {} for all measurements:\n{}\nRun with \
+        UPDATE_COST_BASELINE=1 if the change is intended", measured.display(), errors.join("\n"));
+}
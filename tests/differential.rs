@@ -0,0 +1,343 @@
+mod fixtures;
+
+use std::fs;
+use std::path::PathBuf;
+
+use defi_tools::model::{MarketModel, RadiswapModel, Side};
+use fixtures::*;
+use lending::manifests;
+use proptest::prelude::*;
+use scrypto::prelude::*;
+use scrypto::radix_engine_interface::node::NetworkDefinition;
+use transaction::manifest::decompile;
+use transaction::model::TransactionManifest;
+
+// Runs the same random actions through the blueprints on a TestRunner ledger and through the native reference
+// models of `defi_tools::model`, which must agree on whether each call succeeds, on every amount handed back
+// and on every vault balance, to the last digit. On a mismatch the manifests of the actions are written to
+// `differential/` under the target directory; proptest shrinks the actions first, so the last one written is
+// the smallest sequence which still disagrees. The models leave out deposits and withdrawals of options and
+// market liquidity, which `tests/invariants.rs` covers.
+fn modeled_action() -> impl Strategy<Value = Action> {
+    action().prop_filter("not modeled", |action| !action.is_deposit_or_withdrawal())
+}
+
+// Vault balances of the pool, the market and its insurance fund, and the account balances the outputs go to
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Balances {
+    reserve_a: Decimal,
+    reserve_b: Decimal,
+    market_a: Decimal,
+    market_b: Decimal,
+    market_cctoken_a: Decimal,
+    market_cctoken_b: Decimal,
+    market_bonds: Decimal,
+    insurance_a: Decimal,
+    insurance_b: Decimal,
+    account_a: Decimal,
+    account_b: Decimal,
+    account_pool_units: Decimal,
+    account_cctoken_a: Decimal,
+    account_cctoken_b: Decimal,
+    account_bonds: Decimal,
+    account_loans: Decimal,
+}
+
+impl Balances {
+    fn of_ledger(env: &mut TestEnv, market: &Market) -> Self {
+        let (pool, component, insurance_fund) = (market.pool.component, market.component, market.insurance_fund);
+        Self {
+            reserve_a: env.component_balance(pool, market.token_a),
+            reserve_b: env.component_balance(pool, market.token_b),
+            market_a: env.component_balance(component, market.token_a),
+            market_b: env.component_balance(component, market.token_b),
+            market_cctoken_a: env.component_balance(component, market.cctoken_a),
+            market_cctoken_b: env.component_balance(component, market.cctoken_b),
+            market_bonds: env.component_balance(component, market.bond_token),
+            insurance_a: env.component_balance(insurance_fund, market.token_a),
+            insurance_b: env.component_balance(insurance_fund, market.token_b),
+            account_a: env.balance(market.token_a),
+            account_b: env.balance(market.token_b),
+            account_pool_units: env.balance(market.pool.pool_units),
+            account_cctoken_a: env.balance(market.cctoken_a),
+            account_cctoken_b: env.balance(market.cctoken_b),
+            account_bonds: env.balance(market.bond_token),
+            account_loans: env.balance(market.loan_badge),
+        }
+    }
+
+    // The vaults as the models see them, with the account balances carried over from `account`
+    fn of_models(pool: &RadiswapModel, market: &MarketModel, account: &Balances) -> Self {
+        Self {
+            reserve_a: pool.reserve_a,
+            reserve_b: pool.reserve_b,
+            market_a: market.token_a,
+            market_b: market.token_b,
+            market_cctoken_a: market.cct_a + market.loan_claims,
+            market_cctoken_b: market.cct_b,
+            market_bonds: market.bonds,
+            insurance_a: market.insurance_a,
+            insurance_b: market.insurance_b,
+            ..account.clone()
+        }
+    }
+}
+
+// The reference models of a market created by `TestEnv::new_market` with `params`, at the same time
+fn models(env: &TestEnv, market: &Market, params: &MarketParams) -> (RadiswapModel, MarketModel) {
+    let pool_depth_b = params.pool_depth_a * params.strike_rate * params.market_premium;
+    let pool = RadiswapModel::instantiate(env.now, params.pool_depth_a, pool_depth_b, params.pool_fee).unwrap();
+    // The LinearRateModel of the fixtures has no minimum rate and a maximum of 100%
+    let (model, _) = MarketModel::locking_liquidity(
+        env.now,
+        params.locked_a,
+        params.locked_a * params.strike_rate,
+        market.maturity,
+        params.required_interest,
+        params.strike_rate,
+        dec!(0),
+        dec!(1),
+    )
+    .unwrap();
+    (pool, model)
+}
+
+// An action as a manifest, with the tokens it takes from the account
+struct Step {
+    manifest: TransactionManifest,
+    // Tokens the account must hold for the manifest to be worth running
+    inputs: Vec<(ResourceAddress, Decimal)>,
+}
+
+fn step(env: &mut TestEnv, market: &Market, action: &Action) -> Option<Step> {
+    let (account, pool) = (env.account, &market.pool);
+    let strike_rate = market.strike_rate;
+    let step = match *action {
+        Action::Swap { a_to_b, amount } => {
+            let (resource, amount) = if a_to_b {
+                (market.token_a, Decimal::from(amount) / 10)
+            } else {
+                (market.token_b, Decimal::from(amount) * 10)
+            };
+            Step {
+                manifest: manifests::swap(account, pool.component, resource, amount),
+                inputs: vec![(resource, amount)],
+            }
+        }
+        Action::AddLiquidity { amount_a, ratio_percent } => {
+            let amount_a = Decimal::from(amount_a) / 10;
+            let amount_b = amount_a * strike_rate * Decimal::from(ratio_percent) / 100;
+            Step {
+                manifest: manifests::add_liquidity(account, pool.component, pool.token_a, amount_a, pool.token_b,
+                    amount_b),
+                inputs: vec![(pool.token_a, amount_a), (pool.token_b, amount_b)],
+            }
+        }
+        Action::RemoveLiquidity { percent } => {
+            let units = env.balance(pool.pool_units) * Decimal::from(percent) / 100;
+            Step {
+                manifest: manifests::remove_liquidity(account, pool.component, pool.pool_units, units),
+                inputs: vec![(pool.pool_units, units)],
+            }
+        }
+        Action::LendA { amount } => {
+            let amount = Decimal::from(amount);
+            Step {
+                manifest: manifests::lend_a(account, market.component, market.token_b, amount),
+                inputs: vec![(market.token_b, amount)],
+            }
+        }
+        Action::LendB { amount } => {
+            let amount = Decimal::from(amount);
+            Step {
+                manifest: manifests::lend_b(account, market.component, market.token_a, amount),
+                inputs: vec![(market.token_a, amount)],
+            }
+        }
+        Action::BorrowA { collateral } => {
+            let collateral = Decimal::from(collateral);
+            let amount = collateral * strike_rate;
+            Step {
+                manifest: manifests::borrow_a(account, market.component, amount, market.token_a, collateral,
+                    market.cctoken_b, amount),
+                inputs: vec![(market.token_a, collateral), (market.cctoken_b, amount)],
+            }
+        }
+        Action::Rebalance { into_b, amount } => {
+            let (resource, amount) = if into_b {
+                (market.token_b, Decimal::from(amount))
+            } else {
+                (market.token_a, Decimal::from(amount) / 100)
+            };
+            Step {
+                manifest: manifests::rebalance_transaction(account, market.component, resource, amount, dec!(0)),
+                inputs: vec![(resource, amount)],
+            }
+        }
+        Action::AdvanceTime { .. } => return None,
+        Action::OptionDeposit { .. }
+        | Action::OptionWithdraw { .. }
+        | Action::DepositLiquidityA { .. }
+        | Action::DepositLiquidityB { .. }
+        | Action::WithdrawLiquidity { .. } => unreachable!("not modeled"),
+    };
+    Some(step)
+}
+
+// Runs an action on the models, updating the account balances with what it takes and hands back
+fn run_models(pool: &mut RadiswapModel, market: &mut MarketModel, now: i64, action: &Action,
+account: &mut Balances) -> Result<(), String> {
+    let strike_rate = market.strike_rate;
+    match *action {
+        Action::Swap { a_to_b, amount } => {
+            let (input, amount) = if a_to_b {
+                (Side::A, Decimal::from(amount) / 10)
+            } else {
+                (Side::B, Decimal::from(amount) * 10)
+            };
+            let (output_side, output) = pool.swap(now, input, amount)?;
+            debit(account, input, amount);
+            credit(account, output_side, output);
+        }
+        Action::AddLiquidity { amount_a, ratio_percent } => {
+            let amount_a = Decimal::from(amount_a) / 10;
+            let amount_b = amount_a * strike_rate * Decimal::from(ratio_percent) / 100;
+            let (change_a, change_b, units) = pool.add_liquidity(now, amount_a, amount_b)?;
+            account.account_a += change_a - amount_a;
+            account.account_b += change_b - amount_b;
+            account.account_pool_units += units;
+        }
+        Action::RemoveLiquidity { percent } => {
+            let units = account.account_pool_units * Decimal::from(percent) / 100;
+            let (amount_a, amount_b) = pool.remove_liquidity(now, units)?;
+            account.account_pool_units -= units;
+            account.account_a += amount_a;
+            account.account_b += amount_b;
+        }
+        Action::LendA { amount } => {
+            let amount = Decimal::from(amount);
+            let (minted, quoted) = market.lend_a(now, amount)?;
+            account.account_b -= amount;
+            account.account_bonds += minted + quoted;
+        }
+        Action::LendB { amount } => {
+            let amount = Decimal::from(amount);
+            let (minted, quoted) = market.lend_b(now, amount)?;
+            account.account_a -= amount;
+            account.account_bonds += minted + quoted;
+        }
+        Action::BorrowA { collateral } => {
+            let collateral = Decimal::from(collateral);
+            let amount = collateral * strike_rate;
+            let borrowed = market.borrow_a(now, amount, collateral, amount)?;
+            account.account_a -= collateral;
+            account.account_cctoken_b -= amount - borrowed.cctoken_b;
+            account.account_b += borrowed.token_b;
+            account.account_loans += dec!(1);
+        }
+        Action::Rebalance { into_b, amount } => {
+            let (collateral, amount) = if into_b {
+                (Side::B, Decimal::from(amount))
+            } else {
+                (Side::A, Decimal::from(amount) / 100)
+            };
+            let (output_side, output) = market.rebalance(pool, now, collateral, amount, dec!(0))?;
+            debit(account, collateral, amount);
+            credit(account, output_side, output);
+        }
+        Action::AdvanceTime { .. } => {}
+        Action::OptionDeposit { .. }
+        | Action::OptionWithdraw { .. }
+        | Action::DepositLiquidityA { .. }
+        | Action::DepositLiquidityB { .. }
+        | Action::WithdrawLiquidity { .. } => unreachable!("not modeled"),
+    }
+    Ok(())
+}
+
+fn debit(account: &mut Balances, side: Side, amount: Decimal) {
+    credit(account, side, -amount);
+}
+
+fn credit(account: &mut Balances, side: Side, amount: Decimal) {
+    match side {
+        Side::A => account.account_a += amount,
+        Side::B => account.account_b += amount,
+    }
+}
+
+// Writes the manifests run so far, one file per transaction, and a summary of the actions
+fn write_reproducer(market: &Market, run: &[(i64, Action, Option<String>)], mismatch: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("differential");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let mut summary = format!(
+        "Ledger set up by TestEnv::new_market, market {:?}, Radiswap {:?}\n\n",
+        market.component, market.pool.component
+    );
+    for (index, (now, action, manifest)) in run.iter().enumerate() {
+        summary.push_str(&format!("{:02} at {}: {:?}\n", index, now, action));
+        if let Some(manifest) = manifest {
+            fs::write(dir.join(format!("{:02}.rtm", index)), manifest).unwrap();
+        }
+    }
+    summary.push_str(&format!("\n{}\n", mismatch));
+    fs::write(dir.join("actions.txt"), summary).unwrap();
+    dir
+}
+
+fn params() -> MarketParams {
+    MarketParams { market_premium: dec!("1.02"), ..Default::default() }
+}
+
+proptest! {
+    // Every case publishes the package on a fresh ledger, so keep the number of cases low
+    #![proptest_config(ProptestConfig::with_cases(16))]
+
+    #[test]
+    fn test_blueprints_match_the_reference_models(actions in prop::collection::vec(modeled_action(), 1..25)) {
+        let mut env = TestEnv::new();
+        let market = env.new_market(params());
+        let (mut pool, mut model) = models(&env, &market, &params());
+        let network = NetworkDefinition::simulator();
+
+        let mut expected = Balances::of_ledger(&mut env, &market);
+        let mut run: Vec<(i64, Action, Option<String>)> = Vec::new();
+        for action in actions.iter() {
+            if let Action::AdvanceTime { minutes } = *action {
+                env.advance_time(minutes * 60);
+                run.push((env.now, action.clone(), None));
+                continue;
+            }
+
+            let step = step(&mut env, &market, action).unwrap();
+            if step.inputs.iter().any(|(resource, amount)| env.balance(*resource) < *amount) {
+                // The account can't fund the action, which says nothing about the blueprints
+                continue;
+            }
+            let text = decompile(&step.manifest.instructions, &network).unwrap();
+            run.push((env.now, action.clone(), Some(text)));
+
+            let succeeded = env.execute(step.manifest).is_commit_success();
+            let mut account = expected.clone();
+            let modeled = run_models(&mut pool, &mut model, env.now, action, &mut account);
+            if modeled.is_ok() {
+                expected = Balances::of_models(&pool, &model, &account);
+            }
+            let actual = Balances::of_ledger(&mut env, &market);
+
+            let mismatch = if succeeded != modeled.is_ok() {
+                Some(format!("{:?} succeeded on the ledger: {}, in the models: {:?}", action, succeeded, modeled))
+            } else if actual != expected {
+                Some(format!("{:?} left\n{:#?}\non the ledger and\n{:#?}\nin the models", action, actual, expected))
+            } else {
+                None
+            };
+            if let Some(mismatch) = mismatch {
+                let dir = write_reproducer(&market, &run, &mismatch);
+                panic!("{}\nreproducer written to {}", mismatch, dir.display());
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+mod fixtures;
+
+use fixtures::*;
+use lending::manifests;
+use scrypto::prelude::*;
+
+#[test]
+fn test_create_market_rejects_the_reversed_pair() {
+    let mut env = TestEnv::new();
+    let pool = env.new_pool(dec!(10000), dec!(1000000), dec!("0.003"));
+    let (token_a, token_b) = (pool.token_a, pool.token_b);
+    let factory = env.new_factory();
+    let maturity = env.now + 30 * 24 * 60 * 60;
+
+    env.create_market(factory, &pool, token_a, token_b, dec!(100), dec!(100), maturity).expect_commit_success();
+
+    // The same market again, and quoted as token A per token B, is a duplicate
+    env.create_market(factory, &pool, token_a, token_b, dec!(100), dec!(100), maturity).expect_commit_failure();
+    env.create_market(factory, &pool, token_b, token_a, dec!(10000), dec!("0.01"), maturity)
+        .expect_commit_failure();
+
+    // Another strike or maturity of the reversed pair is a different market
+    env.create_market(factory, &pool, token_b, token_a, dec!(10000), dec!("0.02"), maturity)
+        .expect_commit_success();
+    env.create_market(factory, &pool, token_b, token_a, dec!(10000), dec!("0.01"), maturity + 60)
+        .expect_commit_success();
+}
+
+// Creates a market through the factory and returns it with its cctoken A, bond token and LP token
+fn create_market(env: &mut TestEnv, factory: ComponentAddress, pool: &Pool,
+maturity: i64) -> (ComponentAddress, ResourceAddress, ResourceAddress, ResourceAddress) {
+    let receipt = env.create_market(factory, pool, pool.token_a, pool.token_b, dec!(1000), dec!(100), maturity);
+    let market = receipt.expect_commit_success().entity_changes.new_component_addresses[0];
+    let (cctoken_a, _, bond_token, lp_token, _): (ResourceAddress, ResourceAddress, ResourceAddress,
+        ResourceAddress, ResourceAddress) = env.call(market, "resource_addresses", args!());
+    (market, cctoken_a, bond_token, lp_token)
+}
+
+#[test]
+fn test_rollover_liquidity_deposits_at_the_target_interest() {
+    let mut env = TestEnv::new();
+    let pool = env.new_pool(dec!(10000), dec!(1000000), dec!("0.003"));
+    let factory = env.new_factory();
+    let (source_maturity, target_maturity) = (env.now + 24 * 60 * 60, env.now + 30 * 24 * 60 * 60);
+    let (source, source_cctoken_a, source_bond_token, source_lp_token) =
+        create_market(&mut env, factory, &pool, source_maturity);
+    let (target, target_cctoken_a, target_bond_token, target_lp_token) =
+        create_market(&mut env, factory, &pool, target_maturity);
+
+    // Providing 1000 ccA of liquidity to the source market at its 10% interest
+    env.execute_success(manifests::deposit_liquidity_a(env.account, source, source_cctoken_a, dec!(1000),
+        source_bond_token, dec!(100), dec!(100), source_maturity));
+    let lp_amount = env.balance(source_lp_token);
+    let (source_bonds, target_bonds) = (env.balance(source_bond_token), env.balance(target_bond_token));
+
+    // Bounds the 10% interest of the target market is outside of fail the rollover
+    env.execute(manifests::rollover_liquidity(env.account, factory, source, target, source_lp_token, lp_amount,
+        dec!("0.11"), dec!("0.2"))).expect_commit_failure();
+
+    // The 1000 token A redeemed from the source mint 1000 ccA and 1000 bond tokens in the target, of which
+    // 100 bond tokens go into the pool with the claims and 900 are left over. The 100 source bond tokens
+    // withdrawn with the liquidity are handed back.
+    env.execute_success(manifests::rollover_liquidity(env.account, factory, source, target, source_lp_token,
+        lp_amount, dec!("0.09"), dec!("0.11")));
+    assert_eq!(env.balance(source_lp_token), dec!(0));
+    assert!(env.balance(target_lp_token) > dec!(0));
+    assert_eq!(env.component_balance(target, target_cctoken_a), dec!(1000));
+    assert_eq!(env.component_balance(target, target_bond_token), dec!(100));
+    assert_eq!(env.balance(target_bond_token), target_bonds + dec!(900));
+    assert_eq!(env.balance(source_bond_token), source_bonds + dec!(100));
+}
@@ -0,0 +1,53 @@
+use proptest::prelude::*;
+
+// Random actions run against a market and the Radiswap pool it rebalances through. Amounts are small
+// relative to the pools so that most actions go through, large ones exercise the circuit breakers.
+#[derive(Debug, Clone)]
+pub enum Action {
+    Swap { a_to_b: bool, amount: u64 },
+    AddLiquidity { amount_a: u64, ratio_percent: u64 },
+    RemoveLiquidity { percent: u64 },
+    LendA { amount: u64 },
+    LendB { amount: u64 },
+    BorrowA { collateral: u64 },
+    Rebalance { into_b: bool, amount: u64 },
+    OptionDeposit { a: bool, amount: u64 },
+    OptionWithdraw { a: bool, amount: u64 },
+    DepositLiquidityA { amount: u64 },
+    DepositLiquidityB { amount: u64 },
+    WithdrawLiquidity { percent: u64 },
+    AdvanceTime { minutes: i64 },
+}
+
+impl Action {
+    // Whether the action moves options or market liquidity in or out directly, rather than trading, lending or
+    // borrowing
+    pub fn is_deposit_or_withdrawal(&self) -> bool {
+        matches!(
+            self,
+            Action::OptionDeposit { .. }
+                | Action::OptionWithdraw { .. }
+                | Action::DepositLiquidityA { .. }
+                | Action::DepositLiquidityB { .. }
+                | Action::WithdrawLiquidity { .. }
+        )
+    }
+}
+
+pub fn action() -> impl Strategy<Value = Action> {
+    prop_oneof![
+        (any::<bool>(), 1u64..300).prop_map(|(a_to_b, amount)| Action::Swap { a_to_b, amount }),
+        (1u64..200, 50u64..150).prop_map(|(amount_a, ratio_percent)| Action::AddLiquidity { amount_a, ratio_percent }),
+        (1u64..50).prop_map(|percent| Action::RemoveLiquidity { percent }),
+        (1u64..5000).prop_map(|amount| Action::LendA { amount }),
+        (1u64..50).prop_map(|amount| Action::LendB { amount }),
+        (1u64..5).prop_map(|collateral| Action::BorrowA { collateral }),
+        (any::<bool>(), 1u64..1000).prop_map(|(into_b, amount)| Action::Rebalance { into_b, amount }),
+        (any::<bool>(), 1u64..100).prop_map(|(a, amount)| Action::OptionDeposit { a, amount }),
+        (any::<bool>(), 1u64..100).prop_map(|(a, amount)| Action::OptionWithdraw { a, amount }),
+        (1u64..200).prop_map(|amount| Action::DepositLiquidityA { amount }),
+        (1u64..200).prop_map(|amount| Action::DepositLiquidityB { amount }),
+        (1u64..100).prop_map(|percent| Action::WithdrawLiquidity { percent }),
+        (1i64..120).prop_map(|minutes| Action::AdvanceTime { minutes }),
+    ]
+}
@@ -0,0 +1,446 @@
+// Shared by every scenario, not all of them use every helper
+#![allow(dead_code)]
+
+use lending::manifests;
+use lending::manifests::{CreateMarket, LockingLiquidity};
+use radix_engine::transaction::TransactionReceipt;
+use scrypto::radix_engine_interface::model::FromPublicKey;
+use scrypto::prelude::*;
+use scrypto_unit::*;
+use transaction::builder::ManifestBuilder;
+use transaction::model::TransactionManifest;
+
+mod actions;
+
+pub use actions::{action, Action};
+
+// Seconds since the unix epoch at which every scenario starts
+pub const START_TIME: i64 = 1_680_000_000;
+
+// A published package with a funded account, and a clock the scenarios move forward
+pub struct TestEnv {
+    pub test_runner: TestRunner,
+    pub public_key: EcdsaSecp256k1PublicKey,
+    pub account: ComponentAddress,
+    pub package: PackageAddress,
+    pub now: i64,
+}
+
+// A Radiswap pool and the badges returned when it was instantiated
+pub struct Pool {
+    pub component: ComponentAddress,
+    pub token_a: ResourceAddress,
+    pub token_b: ResourceAddress,
+    pub pool_units: ResourceAddress,
+    pub admin_badge: ResourceAddress,
+}
+
+// Parameters of a ConstantSumAmm market created by `TestEnv::new_market`
+pub struct MarketParams {
+    // Token B per token A at which the options convert
+    pub strike_rate: Decimal,
+    // Seconds from now until maturity
+    pub duration: i64,
+    pub required_interest: Decimal,
+    // Token A locked at instantiation, token B is locked at the strike rate
+    pub locked_a: Decimal,
+    // Radiswap price of token A relative to the strike rate, e.g. 1.03 for 3% above it
+    pub market_premium: Decimal,
+    // Token A in the Radiswap pool
+    pub pool_depth_a: Decimal,
+    pub pool_fee: Decimal,
+}
+
+impl Default for MarketParams {
+    fn default() -> Self {
+        Self {
+            strike_rate: dec!(100),
+            duration: 30 * 24 * 60 * 60,
+            required_interest: dec!("0.1"),
+            locked_a: dec!(1000),
+            market_premium: dec!(1),
+            pool_depth_a: dec!(10000),
+            pool_fee: dec!("0.003"),
+        }
+    }
+}
+
+// A ConstantSumAmm market together with the Radiswap pool it rebalances through, which also acts as its
+// price source
+pub struct Market {
+    pub component: ComponentAddress,
+    pub pool: Pool,
+    pub token_a: ResourceAddress,
+    pub token_b: ResourceAddress,
+    pub cctoken_a: ResourceAddress,
+    pub cctoken_b: ResourceAddress,
+    pub bond_token: ResourceAddress,
+    pub lp_token: ResourceAddress,
+    pub loan_badge: ResourceAddress,
+    pub admin_badge: ResourceAddress,
+    pub insurance_fund: ComponentAddress,
+    pub strike_rate: Decimal,
+    pub maturity: i64,
+}
+
+impl TestEnv {
+    pub fn new() -> Self {
+        let mut test_runner = TestRunner::builder().build();
+        let (public_key, _private_key, account) = test_runner.new_allocated_account();
+        let package = test_runner.compile_and_publish(this_package!());
+
+        let mut env = Self { test_runner, public_key, account, package, now: 0 };
+        env.set_time(START_TIME);
+        env
+    }
+
+    // Moves the ledger clock to `now`, in seconds since the unix epoch
+    pub fn set_time(&mut self, now: i64) {
+        self.test_runner.set_current_time(now * 1000);
+        self.now = now;
+    }
+
+    pub fn advance_time(&mut self, seconds: i64) {
+        self.set_time(self.now + seconds);
+    }
+
+    // Creates a fungible token and deposits `amount` of it in the test account
+    pub fn new_token(&mut self, amount: Decimal) -> ResourceAddress {
+        self.test_runner.create_fungible_resource(amount, DIVISIBILITY_MAXIMUM, self.account)
+    }
+
+    pub fn balance(&mut self, resource: ResourceAddress) -> Decimal {
+        self.test_runner.account_balance(self.account, resource).unwrap_or_default()
+    }
+
+    // Amount of `resource` held in the vaults of a component
+    pub fn component_balance(&mut self, component: ComponentAddress, resource: ResourceAddress) -> Decimal {
+        self.test_runner.get_component_resources(component).get(&resource).copied().unwrap_or_default()
+    }
+
+    // Ids of the non fungibles of `resource` held by a component
+    pub fn non_fungible_ids(&mut self, component: ComponentAddress,
+    resource: ResourceAddress) -> Vec<NonFungibleLocalId> {
+        let vaults = self.test_runner.get_component_vaults(component, resource);
+        vaults
+            .into_iter()
+            .flat_map(|vault| self.test_runner.inspect_nft_vault(vault).unwrap_or_default())
+            .collect()
+    }
+
+    // Executes a manifest signed by the test account, without checking the outcome
+    pub fn execute(&mut self, manifest: TransactionManifest) -> TransactionReceipt {
+        self.test_runner.execute_manifest_ignoring_fee(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&self.public_key)],
+        )
+    }
+
+    // Executes a manifest which must succeed
+    pub fn execute_success(&mut self, manifest: TransactionManifest) -> TransactionReceipt {
+        let receipt = self.execute(manifest);
+        receipt.expect_commit_success();
+        receipt
+    }
+
+    // Calls a method and decodes what it returns, for read-only methods
+    pub fn call<T: ScryptoDecode>(&mut self, component: ComponentAddress, method: &str, args: Vec<u8>) -> T {
+        let manifest = manifests::call_method(self.account, component, method, args);
+        // The fee is locked by the first instruction
+        self.execute_success(manifest).output(1)
+    }
+
+    // Calls a method passing `amount` of `resource` from the test account as its only argument, and deposits
+    // everything it returns
+    pub fn call_with_bucket(&mut self, component: ComponentAddress, method: &str, resource: ResourceAddress,
+    amount: Decimal) -> TransactionReceipt {
+        let account = self.account;
+        let manifest = ManifestBuilder::new()
+            .withdraw_from_account_by_amount(account, amount, resource)
+            .take_from_worktop_by_amount(amount, resource, |builder, bucket| {
+                builder.call_method(component, method, args!(bucket))
+            })
+            .call_method(account, "deposit_batch", args!(ManifestExpression::EntireWorktop))
+            .build();
+        self.execute(manifest)
+    }
+
+    // Calls an admin-protected method with the badge present in the auth zone
+    pub fn call_as_admin(&mut self, component: ComponentAddress, method: &str, admin_badge: ResourceAddress,
+    args: Vec<u8>) -> TransactionReceipt {
+        let manifest = manifests::call_method_with_badge(self.account, component, method, admin_badge, args);
+        self.execute(manifest)
+    }
+
+    // Creates two fresh tokens and a Radiswap pool of `amount_a` token A and `amount_b` token B
+    pub fn new_pool(&mut self, amount_a: Decimal, amount_b: Decimal, fee: Decimal) -> Pool {
+        let token_a = self.new_token(amount_a * 100);
+        let token_b = self.new_token(amount_b * 100);
+        self.new_pool_of(token_a, amount_a, token_b, amount_b, fee)
+    }
+
+    pub fn new_pool_of(&mut self, token_a: ResourceAddress, amount_a: Decimal, token_b: ResourceAddress,
+    amount_b: Decimal, fee: Decimal) -> Pool {
+        let manifest = manifests::instantiate_radiswap(self.package, self.account, token_a, amount_a, token_b,
+            amount_b, fee);
+
+        // The pool is created a TWAP window in the past, so it has the price history `price` needs right away
+        let now = self.now;
+        self.set_time(now - 600);
+        let receipt = self.execute_success(manifest);
+        self.set_time(now);
+        let commit = receipt.expect_commit();
+
+        // Resources are created in order: the pool unit minter badge, the admin badge and the pool units
+        Pool {
+            component: commit.entity_changes.new_component_addresses[0],
+            token_a,
+            token_b,
+            pool_units: commit.entity_changes.new_resource_addresses[2],
+            admin_badge: commit.entity_changes.new_resource_addresses[1],
+        }
+    }
+
+    // Adds liquidity to a pool, the tokens the pool doesn't take are returned
+    pub fn add_liquidity(&mut self, pool: &Pool, amount_a: Decimal, amount_b: Decimal) -> TransactionReceipt {
+        let manifest = manifests::add_liquidity(self.account, pool.component, pool.token_a, amount_a, pool.token_b,
+            amount_b);
+        self.execute(manifest)
+    }
+
+    // Swaps `amount` of `resource` through a pool
+    pub fn swap(&mut self, pool: &Pool, resource: ResourceAddress, amount: Decimal) -> TransactionReceipt {
+        let manifest = manifests::swap(self.account, pool.component, resource, amount);
+        self.execute(manifest)
+    }
+
+    // Instantiates a LinearRateModel with a 2% base rate and no minimum. Returns the model and its updater badge.
+    pub fn new_rate_model(&mut self) -> (ComponentAddress, ResourceAddress) {
+        let manifest = manifests::instantiate_linear_rate_model(self.package, self.account, dec!("0.02"), dec!("0.2"),
+            dec!(0), dec!(1));
+        let receipt = self.execute_success(manifest);
+        let commit = receipt.expect_commit();
+        (commit.entity_changes.new_component_addresses[0], commit.entity_changes.new_resource_addresses[0])
+    }
+
+    // Instantiates an InsuranceFund and issues a claimant badge to the test account. Returns the fund and the
+    // claimant badge.
+    pub fn new_insurance_fund(&mut self) -> (ComponentAddress, ResourceAddress) {
+        let manifest = manifests::instantiate_insurance_fund(self.package, self.account);
+        let receipt = self.execute_success(manifest);
+        let commit = receipt.expect_commit();
+
+        // Resources are created in order: the admin badge, the claimant minter badge and the claimant badge
+        let insurance_fund = commit.entity_changes.new_component_addresses[0];
+        let admin_badge = commit.entity_changes.new_resource_addresses[0];
+        let claimant_badge = commit.entity_changes.new_resource_addresses[2];
+
+        self.execute_success(manifests::issue_claimant_badge(self.account, insurance_fund, admin_badge));
+        (insurance_fund, claimant_badge)
+    }
+
+    // Instantiates a PriceOracle of `base` in `quote` with a single feeder, which pushes `price`. Returns the oracle
+    // and the feeder badge, the feeder's id is 1.
+    pub fn new_oracle(&mut self, base: ResourceAddress, quote: ResourceAddress,
+    price: Decimal) -> (ComponentAddress, ResourceAddress) {
+        let manifest = manifests::instantiate_price_oracle(self.package, self.account, base, quote, 3600, 1);
+        let receipt = self.execute_success(manifest);
+        let commit = receipt.expect_commit();
+
+        // Resources are created in order: the admin badge, the feeder minter badge and the feeder badge
+        let oracle = commit.entity_changes.new_component_addresses[0];
+        let admin_badge = commit.entity_changes.new_resource_addresses[0];
+        let feeder_badge = commit.entity_changes.new_resource_addresses[2];
+
+        self.execute_success(manifests::add_feeder(self.account, oracle, admin_badge, "feeder"));
+        self.execute_success(manifests::push_price(self.account, oracle, feeder_badge, NonFungibleLocalId::integer(1),
+            price));
+        (oracle, feeder_badge)
+    }
+
+    pub fn new_factory(&mut self) -> ComponentAddress {
+        let receipt = self.execute_success(manifests::instantiate_market_factory(self.package, self.account));
+        receipt.expect_commit().entity_changes.new_component_addresses[0]
+    }
+
+    // Creates a market through a factory, locking `locked_a` token A and the matching token B. The pool is
+    // used both to rebalance and as the price source, and every market gets its own rate model and fund.
+    pub fn create_market(&mut self, factory: ComponentAddress, pool: &Pool, token_a: ResourceAddress,
+    token_b: ResourceAddress, locked_a: Decimal, strike_rate: Decimal, maturity: i64) -> TransactionReceipt {
+        let (rate_model, rate_model_badge) = self.new_rate_model();
+        let (insurance_fund, insurance_badge) = self.new_insurance_fund();
+        let manifest = manifests::create_market(self.account, factory, &CreateMarket {
+            token_a,
+            amount_a: locked_a,
+            token_b,
+            amount_b: locked_a * strike_rate,
+            maturity,
+            required_interest: dec!("0.1"),
+            strike_rate,
+            amm_address: pool.component,
+            price_source: pool.component,
+            rate_model,
+            rate_model_badge,
+            insurance_fund,
+            insurance_badge,
+        });
+        self.execute(manifest)
+    }
+
+    // Creates two fresh tokens, a Radiswap pool priced at the strike rate times `market_premium`, and a
+    // ConstantSumAmm market on top of it. The test account holds the cctokens and bond tokens minted when
+    // the liquidity was locked, and the admin badge of the market.
+    pub fn new_market(&mut self, params: MarketParams) -> Market {
+        let locked_b = params.locked_a * params.strike_rate;
+        let pool_depth_b = params.pool_depth_a * params.strike_rate * params.market_premium;
+        let token_a = self.new_token((params.locked_a + params.pool_depth_a) * 10);
+        let token_b = self.new_token((locked_b + pool_depth_b) * 10);
+        let pool = self.new_pool_of(token_a, params.pool_depth_a, token_b, pool_depth_b, params.pool_fee);
+
+        let (rate_model, rate_model_badge) = self.new_rate_model();
+        let (insurance_fund, claimant_badge) = self.new_insurance_fund();
+
+        let maturity = self.now + params.duration;
+        let strike_rate = params.strike_rate;
+        let manifest = manifests::locking_liquidity(self.package, self.account, &LockingLiquidity {
+            token_a,
+            amount_a: params.locked_a,
+            token_a_name: "Collateral Claim A".to_owned(),
+            token_a_symbol: "ccA".to_owned(),
+            token_b,
+            amount_b: locked_b,
+            token_b_name: "Collateral Claim B".to_owned(),
+            token_b_symbol: "ccB".to_owned(),
+            maturity,
+            required_interest: params.required_interest,
+            strike_rate,
+            lp_name: "A/B LP".to_owned(),
+            lp_symbol: "LP-AB".to_owned(),
+            amm_address: pool.component,
+            price_source: pool.component,
+            rate_model,
+            rate_model_badge,
+            insurance_fund,
+            insurance_badge: claimant_badge,
+        });
+        let receipt = self.execute_success(manifest);
+        let commit = receipt.expect_commit();
+        let component = commit.entity_changes.new_component_addresses[0];
+
+        // The mint badge is created first, then the admin badge
+        let admin_badge = commit.entity_changes.new_resource_addresses[1];
+        let (cctoken_a, cctoken_b, bond_token, lp_token, loan_badge): (ResourceAddress, ResourceAddress,
+            ResourceAddress, ResourceAddress, ResourceAddress) = self.call(component, "resource_addresses", args!());
+
+        Market {
+            component,
+            pool,
+            token_a,
+            token_b,
+            cctoken_a,
+            cctoken_b,
+            bond_token,
+            lp_token,
+            loan_badge,
+            admin_badge,
+            insurance_fund,
+            strike_rate,
+            maturity,
+        }
+    }
+
+    // Lends token B to a market when the market price is above the strike rate
+    pub fn lend_a(&mut self, market: &Market, amount: Decimal) -> TransactionReceipt {
+        self.execute(manifests::lend_a(self.account, market.component, market.token_b, amount))
+    }
+
+    // Lends token A to a market when the market price is below the strike rate
+    pub fn lend_b(&mut self, market: &Market, amount: Decimal) -> TransactionReceipt {
+        self.execute(manifests::lend_b(self.account, market.component, market.token_a, amount))
+    }
+
+    // Borrows token B against `collateral` token A, converting `cc_amount` cctoken B
+    pub fn borrow_a(&mut self, market: &Market, borrow_amount: Decimal, collateral: Decimal,
+    cc_amount: Decimal) -> TransactionReceipt {
+        let manifest = manifests::borrow_a(self.account, market.component, borrow_amount, market.token_a, collateral,
+            market.cctoken_b, cc_amount);
+        self.execute(manifest)
+    }
+
+    // Repays a loan with token B. The test account must hold a single loan badge of the market, which saves
+    // looking up its id as `manifests::repay_a` needs.
+    pub fn repay_a(&mut self, market: &Market, amount: Decimal) -> TransactionReceipt {
+        let (account, component) = (self.account, market.component);
+        let (loan_badge, token_b) = (market.loan_badge, market.token_b);
+        let manifest = ManifestBuilder::new()
+            .create_proof_from_account(account, loan_badge)
+            .withdraw_from_account_by_amount(account, amount, token_b)
+            .pop_from_auth_zone(|builder, proof| {
+                builder.take_from_worktop(token_b, |builder, repayment| {
+                    builder.call_method(component, "repay_a", args!(proof, repayment))
+                })
+            })
+            .call_method(account, "deposit_batch", args!(ManifestExpression::EntireWorktop))
+            .build();
+        self.execute(manifest)
+    }
+
+    // Rebalances a market with `amount` of `collateral`, accepting any output
+    pub fn rebalance(&mut self, market: &Market, collateral: ResourceAddress, amount: Decimal) -> TransactionReceipt {
+        let manifest = manifests::rebalance_transaction(self.account, market.component, collateral, amount, dec!(0));
+        self.execute(manifest)
+    }
+
+    // Redeems cctoken B and bond tokens for token B
+    pub fn claim_b(&mut self, market: &Market, cc_amount: Decimal, bond_amount: Decimal) -> TransactionReceipt {
+        let manifest = manifests::option_b_withdraw(self.account, market.component, market.token_b, market.cctoken_b,
+            cc_amount, market.bond_token, bond_amount);
+        self.execute(manifest)
+    }
+
+    // Locks token A or token B in a market for its collateral claims and bond tokens
+    pub fn option_deposit(&mut self, market: &Market, resource: ResourceAddress,
+    amount: Decimal) -> TransactionReceipt {
+        let manifest = if resource == market.token_a {
+            manifests::option_a_deposit(self.account, market.component, resource, amount)
+        } else {
+            manifests::option_b_deposit(self.account, market.component, resource, amount)
+        };
+        self.execute(manifest)
+    }
+
+    // Unlocks the token behind `cc_amount` cctoken A or cctoken B, with the bond tokens its deposit minted
+    pub fn option_withdraw(&mut self, market: &Market, cctoken: ResourceAddress,
+    cc_amount: Decimal) -> TransactionReceipt {
+        let manifest = if cctoken == market.cctoken_a {
+            manifests::option_a_withdraw(self.account, market.component, market.token_a, cctoken, cc_amount,
+                market.bond_token, cc_amount)
+        } else {
+            manifests::option_b_withdraw(self.account, market.component, market.token_b, cctoken, cc_amount,
+                market.bond_token, cc_amount / market.strike_rate)
+        };
+        self.execute(manifest)
+    }
+
+    // Provides `cc_amount` cctoken A of liquidity with the bond tokens the current interest of the pool requires
+    pub fn deposit_liquidity_a(&mut self, market: &Market, cc_amount: Decimal) -> TransactionReceipt {
+        let interest: Decimal = self.call(market.component, "interest", args!());
+        let manifest = manifests::deposit_liquidity_a(self.account, market.component, market.cctoken_a, cc_amount,
+            market.bond_token, cc_amount * interest, market.strike_rate, market.maturity);
+        self.execute(manifest)
+    }
+
+    // Provides `cc_amount` cctoken B of liquidity, worth `cc_amount` / strike rate in token A
+    pub fn deposit_liquidity_b(&mut self, market: &Market, cc_amount: Decimal) -> TransactionReceipt {
+        let interest: Decimal = self.call(market.component, "interest", args!());
+        let manifest = manifests::deposit_liquidity_b(self.account, market.component, market.cctoken_b, cc_amount,
+            market.bond_token, (cc_amount / market.strike_rate) * interest, market.strike_rate, market.maturity);
+        self.execute(manifest)
+    }
+
+    pub fn withdraw_liquidity(&mut self, market: &Market, amount: Decimal) -> TransactionReceipt {
+        let manifest = manifests::withdraw_liquidity(self.account, market.component, market.lp_token, amount,
+            market.strike_rate, market.maturity);
+        self.execute(manifest)
+    }
+}
@@ -0,0 +1,242 @@
+mod fixtures;
+
+use fixtures::*;
+use proptest::prelude::*;
+use scrypto::prelude::*;
+
+// Relative tolerance for comparisons affected by the rounding of `Decimal` divisions
+fn tolerance() -> Decimal {
+    dec!("0.000000001")
+}
+
+// Balances of the tokens of the market, per holder. The test account and the market hold every cctoken, bond
+// token and LP token, so their supplies are the sum of the two.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    reserve_a: Decimal,
+    reserve_b: Decimal,
+    pool_units: Decimal,
+    account_a: Decimal,
+    account_b: Decimal,
+    market_a: Decimal,
+    market_b: Decimal,
+    insurance_a: Decimal,
+    insurance_b: Decimal,
+    cctoken_a_supply: Decimal,
+    cctoken_b_supply: Decimal,
+    bond_supply: Decimal,
+    lp_supply: Decimal,
+    market_bonds: Decimal,
+    redemption_rate: Decimal,
+}
+
+impl Snapshot {
+    fn take(env: &mut TestEnv, market: &Market) -> Self {
+        let (pool, insurance_fund, component) = (market.pool.component, market.insurance_fund, market.component);
+        Self {
+            reserve_a: env.component_balance(pool, market.token_a),
+            reserve_b: env.component_balance(pool, market.token_b),
+            pool_units: env.balance(market.pool.pool_units),
+            account_a: env.balance(market.token_a),
+            account_b: env.balance(market.token_b),
+            market_a: env.component_balance(component, market.token_a),
+            market_b: env.component_balance(component, market.token_b),
+            insurance_a: env.component_balance(insurance_fund, market.token_a),
+            insurance_b: env.component_balance(insurance_fund, market.token_b),
+            cctoken_a_supply: env.balance(market.cctoken_a) + env.component_balance(component, market.cctoken_a),
+            cctoken_b_supply: env.balance(market.cctoken_b) + env.component_balance(component, market.cctoken_b),
+            bond_supply: env.balance(market.bond_token) + env.component_balance(component, market.bond_token),
+            lp_supply: env.balance(market.lp_token) + env.component_balance(component, market.lp_token),
+            market_bonds: env.component_balance(component, market.bond_token),
+            redemption_rate: env.call(component, "redemption_rate", args!()),
+        }
+    }
+
+    fn total_a(&self) -> Decimal {
+        self.reserve_a + self.account_a + self.market_a + self.insurance_a
+    }
+
+    fn total_b(&self) -> Decimal {
+        self.reserve_b + self.account_b + self.market_b + self.insurance_b
+    }
+
+    // Constant product per pool unit squared, which swaps and liquidity changes must never lower. The test
+    // account holds every pool unit.
+    fn k_per_unit(&self) -> Decimal {
+        self.reserve_a * self.reserve_b / (self.pool_units * self.pool_units)
+    }
+}
+
+fn run(env: &mut TestEnv, market: &Market, action: &Action) -> bool {
+    let pool = &market.pool;
+    let receipt = match *action {
+        Action::Swap { a_to_b, amount } => {
+            let (resource, amount) = if a_to_b {
+                (market.token_a, Decimal::from(amount) / 10)
+            } else {
+                (market.token_b, Decimal::from(amount) * 10)
+            };
+            env.swap(pool, resource, amount)
+        }
+        Action::AddLiquidity { amount_a, ratio_percent } => {
+            let amount_a = Decimal::from(amount_a) / 10;
+            let amount_b = amount_a * market.strike_rate * Decimal::from(ratio_percent) / 100;
+            env.add_liquidity(pool, amount_a, amount_b)
+        }
+        Action::RemoveLiquidity { percent } => {
+            let units = env.balance(pool.pool_units) * Decimal::from(percent) / 100;
+            env.call_with_bucket(pool.component, "remove_liquidity", pool.pool_units, units)
+        }
+        Action::LendA { amount } => env.lend_a(market, Decimal::from(amount)),
+        Action::LendB { amount } => env.lend_b(market, Decimal::from(amount)),
+        Action::BorrowA { collateral } => {
+            let collateral = Decimal::from(collateral);
+            env.borrow_a(market, collateral * market.strike_rate, collateral, collateral * market.strike_rate)
+        }
+        Action::Rebalance { into_b, amount } => {
+            if into_b {
+                env.rebalance(market, market.token_b, Decimal::from(amount))
+            } else {
+                env.rebalance(market, market.token_a, Decimal::from(amount) / 100)
+            }
+        }
+        Action::OptionDeposit { a, amount } => {
+            if a {
+                env.option_deposit(market, market.token_a, Decimal::from(amount) / 10)
+            } else {
+                env.option_deposit(market, market.token_b, Decimal::from(amount) * 10)
+            }
+        }
+        Action::OptionWithdraw { a, amount } => {
+            if a {
+                env.option_withdraw(market, market.cctoken_a, Decimal::from(amount) / 10)
+            } else {
+                env.option_withdraw(market, market.cctoken_b, Decimal::from(amount) * 10)
+            }
+        }
+        Action::DepositLiquidityA { amount } => env.deposit_liquidity_a(market, Decimal::from(amount)),
+        Action::DepositLiquidityB { amount } => {
+            env.deposit_liquidity_b(market, Decimal::from(amount) * market.strike_rate)
+        }
+        Action::WithdrawLiquidity { percent } => {
+            let amount = env.balance(market.lp_token) * Decimal::from(percent) / 100;
+            env.withdraw_liquidity(market, amount)
+        }
+        Action::AdvanceTime { minutes } => {
+            env.advance_time(minutes * 60);
+            return true;
+        }
+    };
+    receipt.is_commit_success()
+}
+
+fn check(action: &Action, before: &Snapshot, after: &Snapshot) {
+    // Tokens are neither created nor destroyed, only moved between holders
+    assert_eq!(after.total_a(), before.total_a(), "token A supply changed after {:?}", action);
+    assert_eq!(after.total_b(), before.total_b(), "token B supply changed after {:?}", action);
+
+    // Every collateral claim is backed by the token it claims, net of socialized losses for cctoken B, and no
+    // bond tokens are held by the pool beyond the supply
+    assert!(after.cctoken_a_supply <= after.market_a, "cctoken A is not backed after {:?}", action);
+    assert!(
+        after.cctoken_b_supply * after.redemption_rate <= after.market_b,
+        "cctoken B is not backed after {:?}",
+        action
+    );
+    assert!(after.market_bonds <= after.bond_supply, "bond tokens not in the supply after {:?}", action);
+
+    // The pool holds bond tokens as long as LP tokens claim them
+    assert!(
+        after.lp_supply == Decimal::zero() || after.market_bonds > Decimal::zero(),
+        "LP tokens outstanding over an empty pool after {:?}",
+        action
+    );
+
+    // The constant product per pool unit never decreases
+    assert!(
+        after.k_per_unit() >= before.k_per_unit() * (Decimal::one() - tolerance()),
+        "k per pool unit decreased after {:?}: {} -> {}",
+        action,
+        before.k_per_unit(),
+        after.k_per_unit()
+    );
+
+    match *action {
+        // No free value: the output is worth at most the input at the price before the swap
+        Action::Swap { .. } => {
+            let spent_a = before.account_a - after.account_a;
+            let spent_b = before.account_b - after.account_b;
+            let price = before.reserve_b / before.reserve_a;
+            assert!(
+                spent_a * price + spent_b >= Decimal::zero(),
+                "swap produced free value after {:?}",
+                action
+            );
+        }
+        // LP share conservation: the pool units minted are at most the share of the reserves deposited, and the
+        // reserves withdrawn are at most the share of the pool units burned
+        Action::AddLiquidity { .. } => {
+            let minted = after.pool_units - before.pool_units;
+            let deposited_a = after.reserve_a - before.reserve_a;
+            assert!(
+                minted / before.pool_units <= deposited_a / before.reserve_a * (Decimal::one() + tolerance()),
+                "too many pool units minted after {:?}",
+                action
+            );
+        }
+        Action::RemoveLiquidity { .. } => {
+            let share = (before.pool_units - after.pool_units) / before.pool_units;
+            assert!(
+                before.reserve_a - after.reserve_a <= before.reserve_a * share * (Decimal::one() + tolerance())
+                    && before.reserve_b - after.reserve_b <= before.reserve_b * share * (Decimal::one() + tolerance()),
+                "more than the share of the reserves withdrawn after {:?}",
+                action
+            );
+        }
+        // The LP tokens minted are at most the share of the pool's bond tokens deposited, and the bond tokens
+        // withdrawn are at most the share of the LP tokens burned
+        Action::DepositLiquidityA { .. } | Action::DepositLiquidityB { .. } if before.lp_supply > Decimal::zero() => {
+            let minted = after.lp_supply - before.lp_supply;
+            let deposited = after.market_bonds - before.market_bonds;
+            assert!(
+                minted / before.lp_supply <= deposited / before.market_bonds * (Decimal::one() + tolerance()),
+                "too many LP tokens minted after {:?}",
+                action
+            );
+        }
+        Action::WithdrawLiquidity { .. } => {
+            let share = (before.lp_supply - after.lp_supply) / before.lp_supply;
+            let withdrawn = before.market_bonds - after.market_bonds;
+            assert!(
+                withdrawn <= before.market_bonds * share * (Decimal::one() + tolerance()),
+                "more than the share of the bond tokens withdrawn after {:?}",
+                action
+            );
+        }
+        _ => {}
+    }
+}
+
+proptest! {
+    // Every case publishes the package on a fresh ledger, so keep the number of cases low
+    #![proptest_config(ProptestConfig::with_cases(16))]
+
+    #[test]
+    fn test_pool_invariants(actions in prop::collection::vec(action(), 1..25)) {
+        let mut env = TestEnv::new();
+        let market = env.new_market(MarketParams { market_premium: dec!("1.02"), ..Default::default() });
+
+        let mut before = Snapshot::take(&mut env, &market);
+        for action in actions.iter() {
+            if !run(&mut env, &market, action) {
+                // A failed transaction must leave every balance untouched
+                let after = Snapshot::take(&mut env, &market);
+                assert_eq!(format!("{:?}", after), format!("{:?}", before), "failed {:?} changed state", action);
+                continue;
+            }
+            let after = Snapshot::take(&mut env, &market);
+            check(action, &before, &after);
+            before = after;
+        }
+    }
+}
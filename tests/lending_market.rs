@@ -0,0 +1,66 @@
+mod fixtures;
+
+use fixtures::*;
+use lending::manifests;
+use scrypto::prelude::*;
+
+#[test]
+fn test_liquidate_an_unhealthy_position() {
+    let mut env = TestEnv::new();
+    let account = env.account;
+    let quote = env.new_token(dec!(100000));
+    let collateral = env.new_token(dec!(1000));
+
+    // The oracle prices the collateral in quote tokens, through a single feeder
+    let receipt = env.execute_success(manifests::instantiate_price_oracle(env.package, account, collateral, quote,
+        3600, 1));
+    let commit = receipt.expect_commit();
+    let oracle = commit.entity_changes.new_component_addresses[0];
+    let (oracle_admin_badge, feeder_badge) =
+        (commit.entity_changes.new_resource_addresses[0], commit.entity_changes.new_resource_addresses[2]);
+    env.execute_success(manifests::add_feeder(account, oracle, oracle_admin_badge, "feeder"));
+    env.execute_success(manifests::push_price(account, oracle, feeder_badge, NonFungibleLocalId::integer(1),
+        dec!(100)));
+
+    // Resources are created in order: the admin badge, the minter badge and the position badge
+    let receipt = env.execute_success(manifests::instantiate_lending_market(env.package, account, quote));
+    let commit = receipt.expect_commit();
+    let lending_market = commit.entity_changes.new_component_addresses[0];
+    let (admin_badge, position_badge) =
+        (commit.entity_changes.new_resource_addresses[0], commit.entity_changes.new_resource_addresses[2]);
+    for asset in [quote, collateral] {
+        env.execute_success(manifests::list_asset(account, lending_market, admin_badge, asset, oracle, dec!("0.8"),
+            dec!(0), dec!(0), dec!(0)));
+    }
+    env.execute_success(manifests::lending_deposit(account, lending_market, quote, dec!(1000)));
+
+    // 10 collateral at 100 allow borrowing up to 800
+    env.execute_success(manifests::open_position(account, lending_market));
+    let position_id = env.non_fungible_ids(account, position_badge)[0].clone();
+    env.execute_success(manifests::deposit_collateral(account, lending_market, position_badge, position_id.clone(),
+        collateral, dec!(10)));
+    env.execute_success(manifests::lending_borrow(account, lending_market, position_badge, position_id.clone(),
+        quote, dec!(700)));
+
+    // A healthy position can't be liquidated
+    env.execute(manifests::lending_liquidate(account, lending_market, position_id.clone(), quote, dec!(700),
+        collateral)).expect_commit_failure();
+
+    // At 80 the limit drops to 640, half the debt is repaid for collateral worth 5% more
+    env.execute_success(manifests::push_price(account, oracle, feeder_badge, NonFungibleLocalId::integer(1),
+        dec!(80)));
+    let (balance_quote, balance_collateral) = (env.balance(quote), env.balance(collateral));
+    env.execute_success(manifests::lending_liquidate(account, lending_market, position_id.clone(), quote, dec!(700),
+        collateral));
+    assert_eq!(env.balance(quote), balance_quote - dec!(350));
+    assert_eq!(env.balance(collateral), balance_collateral + dec!(350) * dec!("1.05") / dec!(80));
+    let remaining = dec!(10) - dec!(350) * dec!("1.05") / dec!(80);
+    assert_eq!(env.component_balance(lending_market, collateral), remaining);
+
+    // Repaying the rest of the debt frees the remaining collateral
+    env.execute_success(manifests::lending_repay(account, lending_market, position_badge, position_id.clone(), quote,
+        dec!(350)));
+    env.execute_success(manifests::lending_withdraw_collateral(account, lending_market, position_badge, position_id,
+        collateral, remaining));
+    assert_eq!(env.component_balance(lending_market, collateral), dec!(0));
+}
@@ -1,47 +1,274 @@
-use radix_engine_interface::model::FromPublicKey;
+mod fixtures;
+
+use fixtures::*;
+use lending::manifests;
 use scrypto::prelude::*;
-use scrypto_unit::*;
-use transaction::builder::ManifestBuilder;
-
-#[test]
-fn test_hello() {
-    // Setup the environment
-    let mut test_runner = TestRunner::builder().build();
-
-    // Create an account
-    let (public_key, _private_key, account_component) = test_runner.new_allocated_account();
-
-    // Publish package
-    let package_address = test_runner.compile_and_publish(this_package!());
-
-    // Test the `instantiate_hello` function.
-    let manifest = ManifestBuilder::new()
-        .call_function(package_address, "Hello", "instantiate_hello", args!())
-        .build();
-    let receipt = test_runner.execute_manifest_ignoring_fee(
-        manifest,
-        vec![NonFungibleGlobalId::from_public_key(&public_key)],
-    );
-    println!("{:?}\n", receipt);
-    receipt.expect_commit_success();
-    let component = receipt
-        .expect_commit()
-        .entity_changes
-        .new_component_addresses[0];
-
-    // Test the `free_token` method.
-    let manifest = ManifestBuilder::new()
-        .call_method(component, "free_token", args!())
-        .call_method(
-            account_component,
-            "deposit_batch",
-            args!(ManifestExpression::EntireWorktop),
-        )
-        .build();
-    let receipt = test_runner.execute_manifest_ignoring_fee(
-        manifest,
-        vec![NonFungibleGlobalId::from_public_key(&public_key)],
-    );
-    println!("{:?}\n", receipt);
-    receipt.expect_commit_success();
+
+#[test]
+fn test_radiswap_swap_and_liquidity() {
+    let mut env = TestEnv::new();
+    let pool = env.new_pool(dec!(1000), dec!(100000), dec!("0.003"));
+
+    // Swapping token A returns token B
+    let balance_a = env.balance(pool.token_a);
+    let balance_b = env.balance(pool.token_b);
+    env.swap(&pool, pool.token_a, dec!(1)).expect_commit_success();
+    assert_eq!(env.balance(pool.token_a), balance_a - dec!(1));
+    assert!(env.balance(pool.token_b) > balance_b);
+
+    // Removing half the pool units returns about half the reserves
+    let pool_units = env.balance(pool.pool_units);
+    let balance_a = env.balance(pool.token_a);
+    env.call_with_bucket(pool.component, "remove_liquidity", pool.pool_units, pool_units / 2)
+        .expect_commit_success();
+    assert_eq!(env.balance(pool.pool_units), pool_units / 2);
+    assert!(env.balance(pool.token_a) > balance_a + dec!(500));
+}
+
+#[test]
+fn test_market_lifecycle() {
+    let mut env = TestEnv::new();
+    let market = env.new_market(MarketParams { market_premium: dec!("1.03"), ..Default::default() });
+
+    // Locking liquidity minted the collateral claim tokens at the strike rate
+    assert_eq!(env.balance(market.cctoken_a), dec!(1000));
+    assert_eq!(env.balance(market.cctoken_b), dec!(100000));
+
+    // Lend on both sides so the pool holds collateral claims to rebalance with
+    env.lend_a(&market, dec!(10000)).expect_commit_success();
+    env.lend_b(&market, dec!(10)).expect_commit_success();
+
+    // Borrow token B against token A, the claims minted for the collateral stay in the pool
+    let balance_b = env.balance(market.token_b);
+    let claims_a = env.balance(market.cctoken_a);
+    let pool_claims_a = env.component_balance(market.component, market.cctoken_a);
+    env.borrow_a(&market, dec!(100), dec!(1), dec!(100)).expect_commit_success();
+    assert_eq!(env.balance(market.loan_badge), dec!(1));
+    assert_eq!(env.balance(market.token_b), balance_b + dec!(100));
+    assert_eq!(env.balance(market.cctoken_a), claims_a);
+    assert_eq!(env.component_balance(market.component, market.cctoken_a), pool_claims_a + dec!(1));
+
+    // Token A trades above the strike rate, so rebalancing into token B pays the keeper
+    let balance_b = env.balance(market.token_b);
+    env.rebalance(&market, market.token_b, dec!(500)).expect_commit_success();
+    assert!(env.balance(market.token_b) > balance_b);
+
+    // Repay the loan and get the collateral back, its claims are burned so it can't be claimed twice
+    let balance_a = env.balance(market.token_a);
+    env.repay_a(&market, dec!(100)).expect_commit_success();
+    assert_eq!(env.balance(market.token_a), balance_a + dec!(1));
+    assert_eq!(env.balance(market.cctoken_a), claims_a);
+    assert_eq!(env.component_balance(market.component, market.cctoken_a), pool_claims_a);
+
+    // Claim token B with collateral claims once the market matured, each bond token covers a token A worth of
+    // claims at the strike rate
+    env.set_time(market.maturity + 60);
+    let balance_b = env.balance(market.token_b);
+    env.claim_b(&market, dec!(1000), dec!(1000) / market.strike_rate).expect_commit_success();
+    assert_eq!(env.balance(market.token_b), balance_b + dec!(1000));
+}
+
+#[test]
+fn test_rebalance_against_the_market_fails() {
+    let mut env = TestEnv::new();
+    let market = env.new_market(MarketParams { market_premium: dec!("0.97"), ..Default::default() });
+    env.lend_b(&market, dec!(10)).expect_commit_success();
+
+    // Token A trades below the strike rate, so rebalancing into token B is not justified
+    env.rebalance(&market, market.token_b, dec!(500)).expect_commit_failure();
+}
+
+#[test]
+fn test_rebalance_halts_whichever_price_bound_is_crossed() {
+    // The oracle reference is 10% below the spot price, past the 5% rebalancing bound, then 30% below it, past the
+    // 20% of the circuit breaker
+    for deviation in [dec!("1.1"), dec!("1.3")] {
+        let mut env = TestEnv::new();
+        let market = env.new_market(MarketParams { market_premium: dec!("1.03"), ..Default::default() });
+        env.lend_b(&market, dec!(10)).expect_commit_success();
+        let spot = market.strike_rate * dec!("1.03");
+        let (oracle, _) = env.new_oracle(market.token_a, market.token_b, spot / deviation);
+        env.call_as_admin(market.component, "set_price_source", market.admin_badge, args!(oracle))
+            .expect_commit_success();
+
+        // Both halt rebalancing and hand the collateral back
+        let balance_b = env.balance(market.token_b);
+        env.rebalance(&market, market.token_b, dec!(500)).expect_commit_success();
+        assert_eq!(env.balance(market.token_b), balance_b);
+        env.rebalance(&market, market.token_b, dec!(500)).expect_commit_failure();
+    }
+}
+
+#[test]
+fn test_liquidations_halt_with_rebalancing() {
+    let mut env = TestEnv::new();
+    let market = env.new_market(MarketParams { market_premium: dec!("1.03"), ..Default::default() });
+    env.lend_a(&market, dec!(10000)).expect_commit_success();
+    env.borrow_a(&market, dec!(100), dec!(1), dec!(100)).expect_commit_success();
+    let loan = env.non_fungible_ids(env.account, market.loan_badge)[0].clone();
+
+    // An oracle 30% below the spot price makes the loan unhealthy, but trips the circuit breaker instead of
+    // seizing collateral at that price
+    let spot = market.strike_rate * dec!("1.03");
+    let (oracle, _) = env.new_oracle(market.token_a, market.token_b, spot / dec!("1.3"));
+    env.call_as_admin(market.component, "set_price_source", market.admin_badge, args!(oracle))
+        .expect_commit_success();
+    let (balance_a, balance_b) = (env.balance(market.token_a), env.balance(market.token_b));
+    env.execute_success(manifests::liquidate(env.account, market.component, loan.clone(), market.token_b, dec!(50),
+        false));
+    assert_eq!(env.balance(market.token_a), balance_a);
+    assert_eq!(env.balance(market.token_b), balance_b);
+
+    // While the breaker is halted nothing can be liquidated
+    env.execute(manifests::liquidate(env.account, market.component, loan, market.token_b, dec!(50), false))
+        .expect_commit_failure();
+}
+
+#[test]
+fn test_circuit_breaker_halts_and_resumes_swaps() {
+    let mut env = TestEnv::new();
+    let pool = env.new_pool(dec!(1000), dec!(100000), dec!("0.003"));
+
+    // A swap moving the price by about a third trips the breaker and hands the input back
+    let balance_a = env.balance(pool.token_a);
+    let balance_b = env.balance(pool.token_b);
+    env.swap(&pool, pool.token_a, dec!(200)).expect_commit_success();
+    assert_eq!(env.balance(pool.token_a), balance_a);
+    assert_eq!(env.balance(pool.token_b), balance_b);
+    assert!(env.call::<bool>(pool.component, "is_halted", args!()));
+
+    // Swaps are halted but withdrawals stay open
+    env.swap(&pool, pool.token_a, dec!(1)).expect_commit_failure();
+    env.call_with_bucket(pool.component, "remove_liquidity", pool.pool_units, dec!(10))
+        .expect_commit_success();
+
+    // The admin badge guards the pool by default and can resume trading
+    env.execute_success(manifests::resume_trading(env.account, pool.component, pool.admin_badge));
+    assert!(!env.call::<bool>(pool.component, "is_halted", args!()));
+    env.swap(&pool, pool.token_a, dec!(1)).expect_commit_success();
+}
+
+#[test]
+fn test_circuit_breaker_cooldown_expires() {
+    let mut env = TestEnv::new();
+    let pool = env.new_pool(dec!(1000), dec!(100000), dec!("0.003"));
+
+    env.swap(&pool, pool.token_a, dec!(200)).expect_commit_success();
+    assert!(env.call::<bool>(pool.component, "is_halted", args!()));
+
+    // The default cooldown is an hour
+    env.advance_time(3600);
+    assert!(!env.call::<bool>(pool.component, "is_halted", args!()));
+    env.swap(&pool, pool.token_a, dec!(1)).expect_commit_success();
+}
+
+#[test]
+fn test_locking_liquidity_mints_bonds_at_the_required_interest() {
+    let mut env = TestEnv::new();
+    let params = MarketParams::default();
+    let (locked_a, required_interest) = (params.locked_a, params.required_interest);
+    let market = env.new_market(params);
+
+    // The token B locked is worth as much token A at the strike rate, the bonds carry the interest on both
+    assert_eq!(env.balance(market.cctoken_a), locked_a);
+    assert_eq!(env.balance(market.bond_token), required_interest * locked_a * 2);
+}
+
+#[test]
+fn test_strike_rate_changes_once_every_claim_is_redeemed() {
+    let mut env = TestEnv::new();
+    let market = env.new_market(MarketParams::default());
+
+    // The claims minted when the liquidity was locked hold the strike rate in place
+    env.call_as_admin(market.component, "set_strike_rate", market.admin_badge, args!(dec!(50)))
+        .expect_commit_failure();
+
+    env.execute_success(manifests::option_a_withdraw(env.account, market.component, market.token_a,
+        market.cctoken_a, dec!(1000), market.bond_token, dec!(100)));
+    env.execute_success(manifests::option_b_withdraw(env.account, market.component, market.token_b,
+        market.cctoken_b, dec!(100000), market.bond_token, dec!(100)));
+    env.call_as_admin(market.component, "set_strike_rate", market.admin_badge, args!(dec!(50)))
+        .expect_commit_success();
+}
+
+#[test]
+fn test_borrow_converts_the_claims_of_the_borrower() {
+    let mut env = TestEnv::new();
+    let market = env.new_market(MarketParams { market_premium: dec!("1.03"), ..Default::default() });
+    env.lend_a(&market, dec!(10000)).expect_commit_success();
+
+    // The claims the pool holds for its lenders are left alone, the borrower's are burned for token B
+    let pool_claims = env.test_runner.get_component_resources(market.component)[&market.cctoken_b];
+    let claims = env.balance(market.cctoken_b);
+    let balance_b = env.balance(market.token_b);
+    env.borrow_a(&market, dec!(100), dec!(1), dec!(100)).expect_commit_success();
+    assert_eq!(env.test_runner.get_component_resources(market.component)[&market.cctoken_b], pool_claims);
+    assert_eq!(env.balance(market.cctoken_b), claims - dec!(100));
+    assert_eq!(env.balance(market.token_b), balance_b + dec!(100));
+
+    // One token A never pays out more than the strike rate
+    env.borrow_a(&market, dec!(101), dec!(1), dec!(100)).expect_commit_failure();
+}
+
+#[test]
+fn test_repaying_a_loan_pays_its_interest() {
+    let mut env = TestEnv::new();
+    let market = env.new_market(MarketParams { market_premium: dec!("1.03"), ..Default::default() });
+    env.lend_a(&market, dec!(10000)).expect_commit_success();
+    // Liquidity gives the pool the bonds it quotes interest with
+    env.deposit_liquidity_a(&market, dec!(100)).expect_commit_success();
+
+    // Part of the 2 token A of collateral pays the interest, the rest is converted at the strike rate
+    let balance_b = env.balance(market.token_b);
+    env.borrow_a(&market, dec!(100), dec!(2), dec!(200)).expect_commit_success();
+    let borrowed = env.balance(market.token_b) - balance_b;
+    assert!(borrowed >= dec!(100) && borrowed < dec!(200));
+
+    // Repaying what was borrowed leaves the interest owed and part of the collateral locked
+    let balance_a = env.balance(market.token_a);
+    env.repay_a(&market, borrowed).expect_commit_success();
+    assert!(env.balance(market.token_a) < balance_a + dec!(2));
+
+    // Paying the interest too releases all of it, so the borrower paid the interest at the strike rate
+    env.repay_a(&market, dec!(200)).expect_commit_success();
+    assert_eq!(env.balance(market.token_a), balance_a + dec!(2));
+    assert_eq!(env.balance(market.token_b), balance_b + borrowed - dec!(200));
+}
+
+#[test]
+fn test_forfeited_loans_absorb_the_shortfall_of_the_sale() {
+    let mut env = TestEnv::new();
+    let market = env.new_market(MarketParams { market_premium: dec!("0.9"), ..Default::default() });
+    env.lend_a(&market, dec!(10000)).expect_commit_success();
+    env.borrow_a(&market, dec!(100), dec!(1), dec!(100)).expect_commit_success();
+    env.borrow_a(&market, dec!(100), dec!(1), dec!(100)).expect_commit_success();
+    let loans = env.non_fungible_ids(env.account, market.loan_badge);
+
+    // The collateral is worth 90 of the 100 owed, but nothing is absorbed before it is sold
+    env.set_time(market.maturity + 60);
+    let mut lots = Vec::new();
+    for loan in loans {
+        let receipt = env.execute_success(manifests::forfeit_loan(env.account, market.component, loan));
+        let lot: Option<NonFungibleLocalId> = receipt.expect_commit().output(1);
+        lots.push(lot.unwrap());
+    }
+    let redemption_rate: Decimal = env.call(market.component, "redemption_rate", args!());
+    assert_eq!(redemption_rate, dec!(1));
+
+    // Sold at the start price of 108 the first lot recovers its debt
+    env.execute_success(manifests::bid(env.account, market.component, lots[0].clone(), market.token_b, dec!(200)));
+    let redemption_rate: Decimal = env.call(market.component, "redemption_rate", args!());
+    assert_eq!(redemption_rate, dec!(1));
+
+    // Sold at the floor price of 45 the second lot leaves 55 unpaid, which the empty insurance fund can't cover
+    env.advance_time(3600);
+    env.execute_success(manifests::bid(env.account, market.component, lots[1].clone(), market.token_b, dec!(200)));
+    let redemption_rate: Decimal = env.call(market.component, "redemption_rate", args!());
+    assert!(redemption_rate < dec!(1));
+
+    // Converting token A for token B bears the haircut too, so it can't get cctoken B holders ahead of the others
+    let balance_b = env.balance(market.token_b);
+    env.execute_success(manifests::convert_option(env.account, market.component, market.token_a, dec!(1),
+        market.cctoken_b, dec!(100)));
+    assert_eq!(env.balance(market.token_b), balance_b + dec!(100) * redemption_rate);
 }
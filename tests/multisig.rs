@@ -0,0 +1,45 @@
+mod fixtures;
+
+use fixtures::*;
+use lending::manifests;
+use lending::manifests::MultisigAction;
+use scrypto::prelude::*;
+
+#[test]
+fn test_lowering_the_threshold_starts_the_delay_of_approved_transactions() {
+    let mut env = TestEnv::new();
+    let account = env.account;
+
+    // 3 signers who must all approve, and an hour of delay. Resources are created in order: the minter badge and
+    // the signer badge.
+    let receipt = env.execute_success(manifests::instantiate_multisig(env.package, account, 3, 3, 3600));
+    let commit = receipt.expect_commit();
+    let multisig = commit.entity_changes.new_component_addresses[0];
+    let signer_badge = commit.entity_changes.new_resource_addresses[1];
+    let signer = NonFungibleLocalId::integer;
+
+    let badge = env.new_token(dec!(1));
+    env.execute_success(manifests::deposit_badge(account, multisig, badge, dec!(1)));
+
+    // Handing the badge back is approved by 2 of the 3 signers, not enough yet
+    let withdraw = MultisigAction::Withdraw { resource: badge, amount: dec!(1), account };
+    env.execute_success(manifests::queue(account, multisig, signer_badge, signer(1), &withdraw));
+    env.execute_success(manifests::approve(account, multisig, signer_badge, signer(2), 0));
+    env.advance_time(3600);
+    env.execute(manifests::execute_transaction(account, multisig, 0)).expect_commit_failure();
+
+    // All 3 lower the threshold to 2
+    let set_threshold = MultisigAction::SetThreshold { threshold: 2 };
+    env.execute_success(manifests::queue(account, multisig, signer_badge, signer(1), &set_threshold));
+    env.execute_success(manifests::approve(account, multisig, signer_badge, signer(2), 1));
+    env.execute_success(manifests::approve(account, multisig, signer_badge, signer(3), 1));
+    env.advance_time(3600);
+    env.execute_success(manifests::execute_transaction(account, multisig, 1));
+
+    // The withdrawal is approved from then on, without another approval, and waits out its own delay
+    env.execute(manifests::execute_transaction(account, multisig, 0)).expect_commit_failure();
+    env.advance_time(3600);
+    let balance = env.balance(badge);
+    env.execute_success(manifests::execute_transaction(account, multisig, 0));
+    assert_eq!(env.balance(badge), balance + dec!(1));
+}
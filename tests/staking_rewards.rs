@@ -0,0 +1,33 @@
+mod fixtures;
+
+use fixtures::*;
+use lending::manifests;
+use scrypto::prelude::*;
+
+#[test]
+fn test_rewards_stream_from_now_and_carry_over_idle_time() {
+    let mut env = TestEnv::new();
+    let account = env.account;
+    let pool_units = env.new_token(dec!(100));
+    let reward = env.new_token(dec!(1200));
+
+    // Resources are created in order: the admin badge, the minter badge and the stake badge
+    let receipt = env.execute_success(manifests::instantiate_staking_rewards(env.package, account, pool_units));
+    let commit = receipt.expect_commit();
+    let staking = commit.entity_changes.new_component_addresses[0];
+    let (admin_badge, stake_badge) =
+        (commit.entity_changes.new_resource_addresses[0], commit.entity_changes.new_resource_addresses[2]);
+
+    // A start in the past is moved to now, so the 1200 tokens stream at 1 per second
+    let (start, end) = (env.now - 1200, env.now + 1200);
+    env.execute_success(manifests::add_reward(account, staking, admin_badge, reward, dec!(1200), start, end));
+
+    // Nothing is staked for the first half, its 600 tokens are streamed over the second half
+    env.advance_time(600);
+    env.execute_success(manifests::stake(account, staking, pool_units, dec!(100)));
+    let stake_id = env.non_fungible_ids(account, stake_badge)[0].clone();
+
+    env.advance_time(600);
+    let earned: HashMap<ResourceAddress, Decimal> = env.call(staking, "earned", args!(stake_id));
+    assert_eq!(earned[&reward], dec!(1200));
+}
@@ -0,0 +1,75 @@
+use std::path::PathBuf;
+use std::process::exit;
+use std::str::FromStr;
+
+use defi_tools::sim::{backtest, load_prices, SimConfig};
+
+const USAGE: &str = "\
+Replays a price path through models of a Radiswap pool and a ConstantSumAmm market using it as price source,
+with synthetic arbitrageur, keeper, lender and borrower agents, and reports how the LPs, lenders and
+borrowers fared at maturity.
+
+Usage: backtest [OPTIONS] <PRICES>
+
+PRICES is a CSV file of `timestamp,price` rows, the price of token A in token B at a time in seconds.
+
+Options:
+    --pool <A>               Token A in the Radiswap pool, 100 by default
+    --fee <FEE>              Fee of the Radiswap pool, 0.003 by default
+    --locked <A>             Token A locked in the market, 10 by default
+    --strike <RATE>          Strike rate of the market, the first price by default
+    --interest <RATE>        Required interest of the market, 0.1 by default
+    --maturity <TIMESTAMP>   Maturity of the market, the last timestamp by default
+    --min-rate <RATE>        Lower bound of the rate model, 0 by default
+    --max-rate <RATE>        Upper bound of the rate model, 1 by default
+    --lend <B>               Token B lent at a time, 1000 by default
+    --lend-every <ROWS>      Rows between lends, 24 by default, 0 for none
+    --borrow <A>             Token A posted as collateral at a time, 0.1 by default
+    --borrow-every <ROWS>    Rows between borrows, 24 by default, 0 for none
+    --rebalance <SHARE>      Share of the cctokens a rebalance uses, 0.1 by default
+";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    exit(1)
+}
+
+fn next_value<T: FromStr>(args: &mut impl Iterator<Item = String>, option: &str) -> T {
+    let value = args.next().unwrap_or_else(|| fail(&format!("{} needs a value\n\n{}", option, USAGE)));
+    value.parse().unwrap_or_else(|_| fail(&format!("{}: invalid value {:?}", option, value)))
+}
+
+fn main() {
+    let mut config = SimConfig::default();
+    let mut prices: Option<PathBuf> = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--pool" => config.pool_a = next_value(&mut args, "--pool"),
+            "--fee" => config.pool_fee = next_value(&mut args, "--fee"),
+            "--locked" => config.locked_a = next_value(&mut args, "--locked"),
+            "--strike" => config.strike_rate = Some(next_value(&mut args, "--strike")),
+            "--interest" => config.required_interest = next_value(&mut args, "--interest"),
+            "--maturity" => config.maturity = Some(next_value(&mut args, "--maturity")),
+            "--min-rate" => config.min_rate = next_value(&mut args, "--min-rate"),
+            "--max-rate" => config.max_rate = next_value(&mut args, "--max-rate"),
+            "--lend" => config.lend_amount = next_value(&mut args, "--lend"),
+            "--lend-every" => config.lend_every = next_value(&mut args, "--lend-every"),
+            "--borrow" => config.borrow_collateral = next_value(&mut args, "--borrow"),
+            "--borrow-every" => config.borrow_every = next_value(&mut args, "--borrow-every"),
+            "--rebalance" => config.rebalance_share = next_value(&mut args, "--rebalance"),
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                return;
+            }
+            _ if prices.is_none() && !arg.starts_with('-') => prices = Some(PathBuf::from(arg)),
+            _ => fail(&format!("unexpected argument {:?}\n\n{}", arg, USAGE)),
+        }
+    }
+
+    let prices = prices.unwrap_or_else(|| fail(USAGE));
+    let prices = load_prices(&prices).unwrap_or_else(|error| fail(&error));
+    let report = backtest(config, prices).unwrap_or_else(|error| fail(&error));
+    print!("{}", report);
+}
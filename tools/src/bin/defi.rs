@@ -0,0 +1,414 @@
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use defi_tools::client::{open_ledger, Client, MarketView, RadiswapView};
+use defi_tools::simulator::{Outcome, Session};
+use defi_tools::template::Vars;
+use lending::manifests;
+use lending::manifests::LockingLiquidity;
+use radix_engine::ledger::ReadableSubstateStore;
+use scrypto::prelude::*;
+use scrypto::radix_engine_interface::address::{Bech32Decoder, Bech32Encoder};
+use scrypto::radix_engine_interface::node::NetworkDefinition;
+use transaction::model::TransactionManifest;
+
+const USAGE: &str = "\
+Operates Radiswap pools and ConstantSumAmm markets on the ledger of the local simulator, as the account of
+the config, and prints what each transaction moved in and out of the account and the state it left.
+
+Usage: defi [OPTIONS] <COMMAND> [ARGS]
+
+Commands:
+    pool create <TOKEN_A> <AMOUNT_A> <TOKEN_B> <AMOUNT_B> [--fee <FEE>]
+    swap <POOL> <INPUT> <AMOUNT>
+    add-liquidity <POOL> <AMOUNT_A> <AMOUNT_B>
+    market create <POOL> <AMOUNT_A> --strike <RATE> --maturity <TIMESTAMP> [--amount-b <AMOUNT>]
+                  [--interest <RATE>]
+    lend <MARKET> <TOKEN> <AMOUNT>
+    borrow <MARKET> <AMOUNT> <COLLATERAL> [--cc <AMOUNT>]
+    rebalance <MARKET> <TOKEN> <AMOUNT> [--min-output <AMOUNT>]
+    status [COMPONENT]...
+
+Addresses and amounts are given as they are or as $NAME of the config, e.g. `defi swap '$RADISWAP' '$BTC' 1`.
+`lend` lends token B for ccB when TOKEN is token B of the market, token A for ccA otherwise. `borrow` posts
+COLLATERAL token A and converts as much ccB as it borrows unless --cc says otherwise. `market create` locks
+AMOUNT_A times the strike rate of token B unless --amount-b says otherwise, and uses the rate model and
+insurance fund of the config. `status` shows $RADISWAP and $MARKET without arguments.
+
+Options:
+    --config <FILE>     NAME=value lines, rtm/sim.env by default. ACCOUNT and PUBLIC_KEY, as printed by
+                        `resim new-account`, must be set there or in the environment.
+    --ledger <DIR>      Ledger of `resim`, ~/.scrypto by default
+    --now <TIMESTAMP>   Time at which prices and the circuit breaker are read, the system clock by default
+    --fee <FEE>         Fee of a new pool, 0.003 by default
+    --strike <RATE>     Strike rate of a new market
+    --maturity <TIMESTAMP>
+                        Maturity of a new market
+    --amount-b <AMOUNT> Token B locked in a new market
+    --interest <RATE>   Required interest of a new market, 0.1 by default
+    --cc <AMOUNT>       ccB converted by a borrow
+    --min-output <AMOUNT>
+                        Least a rebalance may return, 0 by default
+";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    exit(1)
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, option: &str) -> String {
+    args.next().unwrap_or_else(|| fail(&format!("{} needs a value\n\n{}", option, USAGE)))
+}
+
+// The config, to resolve `$NAME` arguments and to label addresses in output
+struct Names {
+    vars: Vars,
+    decoder: Bech32Decoder,
+    encoder: Bech32Encoder,
+}
+
+impl Names {
+    fn value(&self, arg: &str) -> String {
+        match arg.strip_prefix('$') {
+            Some(name) => match self.vars.get(name) {
+                Some(value) => value.to_owned(),
+                None => fail(&format!("${} is not set in the config", name)),
+            },
+            None => arg.to_owned(),
+        }
+    }
+
+    fn component(&self, arg: &str) -> ComponentAddress {
+        self.decoder
+            .validate_and_decode_component_address(&self.value(arg))
+            .unwrap_or_else(|error| fail(&format!("{}: not a component address: {:?}", arg, error)))
+    }
+
+    fn resource(&self, arg: &str) -> ResourceAddress {
+        self.decoder
+            .validate_and_decode_resource_address(&self.value(arg))
+            .unwrap_or_else(|error| fail(&format!("{}: not a resource address: {:?}", arg, error)))
+    }
+
+    fn package(&self) -> PackageAddress {
+        self.decoder
+            .validate_and_decode_package_address(&self.value("$PACKAGE"))
+            .unwrap_or_else(|error| fail(&format!("$PACKAGE: not a package address: {:?}", error)))
+    }
+
+    fn decimal(&self, arg: &str) -> Decimal {
+        Decimal::from_str(&self.value(arg)).unwrap_or_else(|_| fail(&format!("{}: not a decimal", arg)))
+    }
+
+    // Name of the address in the config, or the address itself
+    fn label(&self, address: String) -> String {
+        match self.vars.name_of(&address) {
+            Some(name) => name.to_owned(),
+            None => address,
+        }
+    }
+
+    fn resource_label(&self, resource: ResourceAddress) -> String {
+        self.label(self.encoder.encode_resource_address_to_string(&resource))
+    }
+
+    fn component_label(&self, component: ComponentAddress) -> String {
+        self.label(self.encoder.encode_component_address_to_string(&component))
+    }
+}
+
+// Options taking a value, wherever they appear after the command
+#[derive(Default)]
+struct Options {
+    fee: Option<Decimal>,
+    strike: Option<Decimal>,
+    maturity: Option<i64>,
+    amount_b: Option<Decimal>,
+    interest: Option<Decimal>,
+    cc: Option<Decimal>,
+    min_output: Option<Decimal>,
+}
+
+fn main() {
+    let mut config: Option<PathBuf> = None;
+    let mut ledger: Option<PathBuf> = None;
+    let mut now: Option<i64> = None;
+    let mut raw_options: Vec<(String, String)> = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => config = Some(PathBuf::from(next_value(&mut args, "--config"))),
+            "--ledger" => ledger = Some(PathBuf::from(next_value(&mut args, "--ledger"))),
+            "--now" => {
+                let value = next_value(&mut args, "--now");
+                now = Some(value.parse().unwrap_or_else(|_| fail(&format!("--now: invalid value {:?}", value))));
+            }
+            "--fee" | "--strike" | "--maturity" | "--amount-b" | "--interest" | "--cc" | "--min-output" => {
+                let value = next_value(&mut args, &arg);
+                raw_options.push((arg, value));
+            }
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                return;
+            }
+            _ => words.push(arg),
+        }
+    }
+    if words.is_empty() {
+        fail(USAGE);
+    }
+
+    let mut vars = match config {
+        Some(path) => Vars::load(&path),
+        None if Path::new("rtm/sim.env").exists() => Vars::load(Path::new("rtm/sim.env")),
+        None => Ok(Vars::new()),
+    }
+    .unwrap_or_else(|error| fail(&error));
+    vars.merge_env(&["ACCOUNT".to_owned(), "PUBLIC_KEY".to_owned()]);
+    let network = NetworkDefinition::simulator();
+    let names = Names { vars, decoder: Bech32Decoder::new(&network), encoder: Bech32Encoder::new(&network) };
+
+    let mut options = Options::default();
+    for (option, value) in &raw_options {
+        let decimal = || Some(names.decimal(value));
+        match option.as_str() {
+            "--fee" => options.fee = decimal(),
+            "--strike" => options.strike = decimal(),
+            "--amount-b" => options.amount_b = decimal(),
+            "--interest" => options.interest = decimal(),
+            "--cc" => options.cc = decimal(),
+            "--min-output" => options.min_output = decimal(),
+            "--maturity" => {
+                let maturity = names.value(value);
+                options.maturity =
+                    Some(maturity.parse().unwrap_or_else(|_| fail(&format!("--maturity: invalid value {:?}", value))));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    let now = now.unwrap_or_else(|| {
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs() as i64).unwrap_or(0)
+    });
+    let dir = ledger.unwrap_or_else(|| {
+        let home = std::env::var("HOME").unwrap_or_else(|_| fail("HOME is not set, give --ledger"));
+        PathBuf::from(home).join(".scrypto")
+    });
+    let mut store = open_ledger(&dir).unwrap_or_else(|error| fail(&error));
+
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+    if words[0] == "status" {
+        let components: Vec<ComponentAddress> = if words.len() > 1 {
+            words[1..].iter().map(|word| names.component(word)).collect()
+        } else {
+            ["RADISWAP", "MARKET"]
+                .iter()
+                .filter(|name| names.vars.get(name).is_some())
+                .map(|name| names.component(&format!("${}", name)))
+                .collect()
+        };
+        if components.is_empty() {
+            fail("No component to show, give one or set RADISWAP or MARKET in the config");
+        }
+        for component in components {
+            print_component(&names, &Client::new(&store), component, now);
+        }
+        return;
+    }
+
+    let account = names.component("$ACCOUNT");
+    let public_key = EcdsaSecp256k1PublicKey::from_str(&names.value("$PUBLIC_KEY"))
+        .unwrap_or_else(|error| fail(&format!("PUBLIC_KEY: {:?}", error)));
+    let mut session = Session::new(&mut store, public_key, account);
+
+    // The component whose state is shown after the transaction, None when it is the one the transaction creates
+    let (manifest, component) = match words.as_slice() {
+        ["pool", "create", token_a, amount_a, token_b, amount_b] => {
+            let manifest = manifests::instantiate_radiswap(names.package(), account, names.resource(token_a),
+                names.decimal(amount_a), names.resource(token_b), names.decimal(amount_b),
+                options.fee.unwrap_or(dec!("0.003")));
+            (manifest, None)
+        }
+        ["swap", pool, input, amount] => {
+            let pool = names.component(pool);
+            (manifests::swap(account, pool, names.resource(input), names.decimal(amount)), Some(pool))
+        }
+        ["add-liquidity", pool, amount_a, amount_b] => {
+            let pool = names.component(pool);
+            let view = radiswap(&session.client(), pool);
+            let manifest = manifests::add_liquidity(account, pool, view.token_a.resource, names.decimal(amount_a),
+                view.token_b.resource, names.decimal(amount_b));
+            (manifest, Some(pool))
+        }
+        ["market", "create", pool, amount_a] => {
+            let manifest = locking_liquidity(&names, &session.client(), account, names.component(pool),
+                names.decimal(amount_a), &options);
+            (manifest, None)
+        }
+        ["lend", market, token, amount] => {
+            let market = names.component(market);
+            let view = self::market(&session.client(), market);
+            let (token, amount) = (names.resource(token), names.decimal(amount));
+            let manifest = if token == view.token_b.resource {
+                manifests::lend_a(account, market, token, amount)
+            } else if token == view.token_a.resource {
+                manifests::lend_b(account, market, token, amount)
+            } else {
+                fail(&format!("{} is not a token of the market", names.resource_label(token)))
+            };
+            (manifest, Some(market))
+        }
+        ["borrow", market, amount, collateral] => {
+            let market = names.component(market);
+            let view = self::market(&session.client(), market);
+            let amount = names.decimal(amount);
+            let manifest = manifests::borrow_a(account, market, amount, view.token_a.resource,
+                names.decimal(collateral), view.cctoken_b.resource, options.cc.unwrap_or(amount));
+            (manifest, Some(market))
+        }
+        ["rebalance", market, token, amount] => {
+            let market = names.component(market);
+            let manifest = manifests::rebalance_transaction(account, market, names.resource(token),
+                names.decimal(amount), options.min_output.unwrap_or(Decimal::zero()));
+            (manifest, Some(market))
+        }
+        _ => fail(USAGE),
+    };
+
+    let outcome = session.execute(manifest).unwrap_or_else(|error| fail(&error));
+    print_outcome(&names, &outcome);
+    if let Some(component) = component.or_else(|| outcome.new_components.first().copied()) {
+        println!();
+        print_component(&names, &session.client(), component, now);
+    }
+}
+
+fn radiswap<S: ReadableSubstateStore>(client: &Client<S>, pool: ComponentAddress) -> RadiswapView {
+    client.radiswap(pool).unwrap_or_else(|error| fail(&format!("Not a Radiswap pool: {}", error)))
+}
+
+fn market<S: ReadableSubstateStore>(client: &Client<S>, market: ComponentAddress) -> MarketView {
+    client.market(market).unwrap_or_else(|error| fail(&format!("Not a ConstantSumAmm market: {}", error)))
+}
+
+// A market over the tokens of `pool`, which is also its price source. The claim tokens and the LP token are
+// named after the names of the tokens in the config.
+fn locking_liquidity<S: ReadableSubstateStore>(names: &Names, client: &Client<S>, account: ComponentAddress,
+pool: ComponentAddress, amount_a: Decimal, options: &Options) -> TransactionManifest {
+    let strike_rate = options.strike.unwrap_or_else(|| fail("market create needs --strike"));
+    let maturity = options.maturity.unwrap_or_else(|| fail("market create needs --maturity"));
+    let view = radiswap(client, pool);
+    let symbol = |resource: ResourceAddress, fallback: &str| match names.vars.name_of(
+        &names.encoder.encode_resource_address_to_string(&resource),
+    ) {
+        Some(name) => name.to_owned(),
+        None => fallback.to_owned(),
+    };
+    let (symbol_a, symbol_b) = (symbol(view.token_a.resource, "A"), symbol(view.token_b.resource, "B"));
+
+    manifests::locking_liquidity(names.package(), account, &LockingLiquidity {
+        token_a: view.token_a.resource,
+        amount_a,
+        token_a_name: format!("Collateral Claim {}", symbol_a),
+        token_a_symbol: format!("cc{}", symbol_a),
+        token_b: view.token_b.resource,
+        amount_b: options.amount_b.unwrap_or(amount_a * strike_rate),
+        token_b_name: format!("Collateral Claim {}", symbol_b),
+        token_b_symbol: format!("cc{}", symbol_b),
+        maturity,
+        required_interest: options.interest.unwrap_or(dec!("0.1")),
+        strike_rate,
+        lp_name: format!("{}/{} LP", symbol_a, symbol_b),
+        lp_symbol: format!("LP-{}-{}", symbol_a, symbol_b),
+        amm_address: pool,
+        price_source: pool,
+        rate_model: names.component("$RATE_MODEL"),
+        rate_model_badge: names.resource("$RATE_MODEL_BADGE"),
+        insurance_fund: names.component("$INSURANCE_FUND"),
+        insurance_badge: names.resource("$INSURANCE_BADGE"),
+    })
+}
+
+fn print_outcome(names: &Names, outcome: &Outcome) {
+    println!("Committed, {} cost units", outcome.cost_units);
+    for component in &outcome.new_components {
+        println!("New component  {}", names.component_label(*component));
+    }
+    for resource in &outcome.new_resources {
+        println!("New resource   {}", names.resource_label(*resource));
+    }
+    if !outcome.account_changes.is_empty() {
+        println!("Account");
+        for (resource, amount) in &outcome.account_changes {
+            let sign = if amount.is_negative() { "" } else { "+" };
+            println!("    {}{} {}", sign, amount, names.resource_label(*resource));
+        }
+    }
+}
+
+// Shows a component as a pool or a market, whichever its state decodes as
+fn print_component<S: ReadableSubstateStore>(names: &Names, client: &Client<S>, component: ComponentAddress,
+now: i64) {
+    if let Ok(view) = client.radiswap(component) {
+        print_pool(names, &view, now);
+    } else if let Ok(view) = client.market(component) {
+        let price = client.market_price(&view, now);
+        print_market(names, &view, price, now);
+    } else {
+        println!("{}: neither a Radiswap pool nor a ConstantSumAmm market", names.component_label(component));
+    }
+}
+
+fn print_pool(names: &Names, view: &RadiswapView, now: i64) {
+    let (a, b) = (names.resource_label(view.token_a.resource), names.resource_label(view.token_b.resource));
+    println!("Radiswap {}", names.component_label(view.component));
+    println!("    Reserves        {} {}, {} {}", view.token_a.amount, a, view.token_b.amount, b);
+    println!("    Pool units      {}", view.pool_units_supply);
+    println!("    Fee             {}%", view.fee() * dec!(100));
+    println!("    Spot price      {} {} per {}", view.spot_price(), b, a);
+    match view.twap(now) {
+        Ok(twap) => println!("    TWAP            {} {} per {}", twap, b, a),
+        Err(error) => println!("    TWAP            unavailable: {}", error),
+    }
+    println!("    TVL             {} {}", view.tvl(), b);
+    println!("    Trading         {}", if view.is_halted(now) { "halted" } else { "open" });
+}
+
+fn print_market(names: &Names, view: &MarketView, price: Result<Decimal, String>, now: i64) {
+    let (a, b) = (names.resource_label(view.token_a.resource), names.resource_label(view.token_b.resource));
+    let percent = |rate: Option<Decimal>| match rate {
+        Some(rate) => format!("{}%", rate * dec!(100)),
+        None => "unknown".to_owned(),
+    };
+    let (debt, collateral) = view.total_debt();
+
+    println!("ConstantSumAmm {}", names.component_label(view.component));
+    println!("    Strike rate     {} {} per {}", view.strike_rate(), b, a);
+    let left = view.maturity() - now;
+    if left > 0 {
+        println!("    Maturity        {}, in {}d {}h", view.maturity(), left / 86400, left % 86400 / 3600);
+    } else {
+        println!("    Maturity        {}, matured", view.maturity());
+    }
+    println!("    Vaults          {} {}, {} {}", view.token_a.amount, a, view.token_b.amount, b);
+    println!("    Claims          {} of {} ccA, {} of {} ccB held by the market", view.cctoken_a.amount,
+        view.cctoken_a_supply, view.cctoken_b.amount, view.cctoken_b_supply);
+    println!("    Bonds           {} of {} held by the market", view.bonds.amount, view.bond_supply);
+    println!("    LP tokens       {}", view.lp_supply);
+    match price {
+        Ok(price) => {
+            println!("    Market price    {} {} per {}", price, b, a);
+            println!("    TVL             {} {}", view.tvl(price), b);
+        }
+        Err(error) => println!("    Market price    unavailable: {}", error),
+    }
+    println!("    Utilization     {}%", view.utilization() * dec!(100));
+    println!("    Borrow APR      {}", percent(view.borrow_apr()));
+    println!("    Supply APR      {}", percent(view.supply_apr()));
+    println!("    Required APR    {}", percent(view.required_apr(now)));
+    println!("    Loans           {} open, {} {} owed against {} {}", view.state.loans.len(), debt, b, collateral, a);
+}
@@ -0,0 +1,119 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use defi_tools::template::{encode, placeholders, render_and_check, template_names, Vars};
+use scrypto::radix_engine_interface::node::NetworkDefinition;
+
+const USAGE: &str = "\
+Renders the manifest templates of rtm/ with the addresses and amounts of the current simulator ledger.
+
+Usage: rtm-render [OPTIONS] <TEMPLATE>... [NAME=value]...
+
+Placeholders such as $ACCOUNT take their value, in increasing priority, from the config file, the
+environment variable of the same name and NAME=value arguments. Without --out the rendered manifests
+are printed, ready for `resim run`.
+
+Options:
+    --config <FILE>     NAME=value lines, rtm/sim.env by default
+    --templates <DIR>   Directory of the templates, rtm by default
+    --out <DIR>         Writes <TEMPLATE>.rtm and the compiled <TEMPLATE>.bin to DIR
+    --list              Lists the templates and their placeholders
+";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    exit(1)
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, option: &str) -> String {
+    args.next().unwrap_or_else(|| fail(&format!("{} needs a value\n\n{}", option, USAGE)))
+}
+
+fn main() {
+    let mut config: Option<PathBuf> = None;
+    let mut templates = PathBuf::from("rtm");
+    let mut out: Option<PathBuf> = None;
+    let mut list = false;
+    let mut names: Vec<String> = Vec::new();
+    let mut overrides: Vec<(String, String)> = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => config = Some(PathBuf::from(next_value(&mut args, "--config"))),
+            "--templates" => templates = PathBuf::from(next_value(&mut args, "--templates")),
+            "--out" => out = Some(PathBuf::from(next_value(&mut args, "--out"))),
+            "--list" => list = true,
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                return;
+            }
+            _ => match arg.split_once('=') {
+                Some((name, value)) => overrides.push((name.trim_start_matches('$').to_owned(), value.to_owned())),
+                None => names.push(arg.trim_end_matches(".rtm").to_owned()),
+            },
+        }
+    }
+
+    if list {
+        for name in template_names(&templates).unwrap_or_else(|error| fail(&error)) {
+            let template = read_template(&templates, &name);
+            println!("{}: ${}", name, placeholders(&template).join(" $"));
+        }
+        return;
+    }
+    if names.is_empty() {
+        fail(USAGE);
+    }
+
+    let vars = match config {
+        Some(path) => Vars::load(&path),
+        None if Path::new("rtm/sim.env").exists() => Vars::load(Path::new("rtm/sim.env")),
+        None => Ok(Vars::new()),
+    }
+    .unwrap_or_else(|error| fail(&error));
+
+    let network = NetworkDefinition::simulator();
+    let mut failed = false;
+    for name in &names {
+        let template = read_template(&templates, name);
+        let mut vars = vars.clone();
+        vars.merge_env(&placeholders(&template));
+        for (placeholder, value) in &overrides {
+            vars.set(placeholder, value);
+        }
+
+        let rendered = match render_and_check(&template, &vars, &network) {
+            Ok(rendered) => rendered,
+            Err(errors) => {
+                eprintln!("{}:\n    {}", name, errors.join("\n    "));
+                failed = true;
+                continue;
+            }
+        };
+
+        match &out {
+            Some(dir) => {
+                fs::create_dir_all(dir).unwrap_or_else(|error| fail(&format!("{}: {}", dir.display(), error)));
+                let text_path = dir.join(format!("{}.rtm", name));
+                let binary_path = dir.join(format!("{}.bin", name));
+                fs::write(&text_path, &rendered.text)
+                    .unwrap_or_else(|error| fail(&format!("{}: {}", text_path.display(), error)));
+                fs::write(&binary_path, encode(&rendered.manifest))
+                    .unwrap_or_else(|error| fail(&format!("{}: {}", binary_path.display(), error)));
+                println!("{} -> {}, {}", name, text_path.display(), binary_path.display());
+            }
+            None => print!("{}", rendered.text),
+        }
+    }
+
+    if failed {
+        exit(1);
+    }
+}
+
+fn read_template(dir: &Path, name: &str) -> String {
+    let path = dir.join(format!("{}.rtm", name));
+    fs::read_to_string(&path).unwrap_or_else(|error| fail(&format!("{}: {}", path.display(), error)))
+}
@@ -0,0 +1,543 @@
+use std::path::Path;
+
+use radix_engine::ledger::ReadableSubstateStore;
+use radix_engine::model::PersistedSubstate;
+use radix_engine::types::{
+    ComponentId, ComponentOffset, GlobalAddress, GlobalOffset, RENodeId, ResourceManagerOffset, SubstateId,
+    SubstateOffset, VaultOffset,
+};
+use radix_engine_stores::rocks_db::RadixEngineDB;
+use scrypto::prelude::*;
+
+use crate::model::{BreakerModel, LoanModel, MarketModel, Observation, RadiswapModel, Side, SECONDS_PER_YEAR};
+
+// Reads the state of Radiswap and ConstantSumAmm components straight from a substate store, the one of a
+// TestRunner (`test_runner.substate_store()`) or the ledger of a local simulator (`open_ledger`). Component
+// state is decoded with the SBOR layout of the blueprints, so the structs below must list the fields of the
+// blueprints in the same order with the same types.
+
+// `Observation` of `radiswap.rs`
+#[derive(ScryptoCategorize, ScryptoDecode, Clone, Debug)]
+pub struct ObservationState {
+    pub timestamp: i64,
+    pub price_a_cumulative: Decimal,
+    pub price_b_cumulative: Decimal,
+}
+
+// `Halt` of `circuit_breaker.rs`
+#[derive(ScryptoCategorize, ScryptoDecode, Clone, Debug)]
+pub struct HaltState {
+    pub timestamp: i64,
+    pub price: Decimal,
+    pub window_start_price: Decimal,
+    pub reference_price: Decimal,
+    pub resumed_at: Option<i64>,
+}
+
+// `CircuitBreaker` of `circuit_breaker.rs`
+#[derive(ScryptoCategorize, ScryptoDecode, Clone, Debug)]
+pub struct CircuitBreakerState {
+    pub max_move: Decimal,
+    pub window: i64,
+    pub max_reference_deviation: Decimal,
+    pub cooldown: i64,
+    pub guardian_badge: ResourceAddress,
+    pub window_start: i64,
+    pub window_start_price: Decimal,
+    pub halted_until: i64,
+    pub halts: Vec<HaltState>,
+}
+
+// `Radiswap` of `radiswap.rs`
+#[derive(ScryptoCategorize, ScryptoDecode, Clone, Debug)]
+pub struct RadiswapState {
+    pub vault_a: Vault,
+    pub vault_b: Vault,
+    pub pool_units_resource_address: ResourceAddress,
+    pub pool_units_minter_badge: Vault,
+    pub fee: Decimal,
+    pub observations: Vec<ObservationState>,
+    pub insurance_fund: Option<ComponentAddress>,
+    pub protocol_fee_share: Decimal,
+    pub circuit_breaker: CircuitBreakerState,
+}
+
+// `Loan` of `constantsum.rs`
+#[derive(ScryptoCategorize, ScryptoDecode, Clone, Debug)]
+pub struct LoanState {
+    pub debt: Decimal,
+    pub collateral: Decimal,
+}
+
+// `AuctionLot` of `constantsum.rs`
+#[derive(ScryptoCategorize, ScryptoDecode, Clone, Debug)]
+pub struct AuctionLotState {
+    pub remaining: Decimal,
+    pub start_price: Decimal,
+    pub floor_price: Decimal,
+    pub start_time: i64,
+    pub duration: i64,
+    pub debt: Decimal,
+    pub proceeds: Decimal,
+}
+
+// `Haircut` of `constantsum.rs`
+#[derive(ScryptoCategorize, ScryptoDecode, Clone, Debug)]
+pub struct HaircutState {
+    pub timestamp: i64,
+    pub shortfall: Decimal,
+    pub covered: Decimal,
+    pub socialized: Decimal,
+    pub redemption_rate: Decimal,
+}
+
+// `ConstantSumAmm` of `constantsum.rs`
+#[derive(ScryptoCategorize, ScryptoDecode, Clone, Debug)]
+pub struct ConstantSumAmmState {
+    pub token_a_vault: Vault,
+    pub token_b_vault: Vault,
+    pub cct_a: Vault,
+    pub cct_b: Vault,
+    pub bt_per_second_vault: Vault,
+    pub duration: i64,
+    pub lp_admin_badge_vault: Vault,
+    pub lp_resource_address: ResourceAddress,
+    pub strike_rate: Decimal,
+    pub constant_product: Decimal,
+    pub interest: Decimal,
+    pub amm_address: ComponentAddress,
+    pub price_source: ComponentAddress,
+    pub max_price_deviation: Decimal,
+    pub keeper_reward: Decimal,
+    pub rate_model: ComponentAddress,
+    pub rate_model_badge: Vault,
+    pub loan_badge_address: ResourceAddress,
+    pub loans: HashMap<NonFungibleLocalId, LoanState>,
+    pub loan_claims: Vault,
+    pub liquidation_threshold: Decimal,
+    pub close_factor: Decimal,
+    pub liquidation_bonus: Decimal,
+    pub auction_mode: bool,
+    pub auction_lot_address: ResourceAddress,
+    pub auction_lot_vault: Vault,
+    pub auction_lots: HashMap<NonFungibleLocalId, AuctionLotState>,
+    pub auction_start_multiplier: Decimal,
+    pub auction_floor_multiplier: Decimal,
+    pub auction_duration: i64,
+    pub insurance_fund: ComponentAddress,
+    pub insurance_badge: Vault,
+    pub protocol_fee_share: Decimal,
+    pub redemption_rate: Decimal,
+    pub haircuts: Vec<HaircutState>,
+    pub flash_loan_receipt_address: ResourceAddress,
+    pub flash_loan_fee: Decimal,
+    pub circuit_breaker: CircuitBreakerState,
+}
+
+// `LinearRateModel` of `linear_rate_model.rs`
+#[derive(ScryptoCategorize, ScryptoDecode, Clone, Debug)]
+pub struct LinearRateModelState {
+    pub base_rate: Decimal,
+    pub slope: Decimal,
+    pub min_rate: Decimal,
+    pub max_rate: Decimal,
+}
+
+// `JumpRateModel` of `jump_rate_model.rs`
+#[derive(ScryptoCategorize, ScryptoDecode, Clone, Debug)]
+pub struct JumpRateModelState {
+    pub base_rate: Decimal,
+    pub slope: Decimal,
+    pub jump_slope: Decimal,
+    pub kink: Decimal,
+    pub min_rate: Decimal,
+    pub max_rate: Decimal,
+}
+
+// `PidRateModel` of `pid_rate_model.rs`
+#[derive(ScryptoCategorize, ScryptoDecode, Clone, Debug)]
+pub struct PidRateModelState {
+    pub target_utilization: Decimal,
+    pub base_rate: Decimal,
+    pub kp: Decimal,
+    pub ki: Decimal,
+    pub integral: Decimal,
+    pub last_utilization: Decimal,
+    pub last_update: i64,
+    pub min_rate: Decimal,
+    pub max_rate: Decimal,
+}
+
+// The rate model of a market, told apart by the number of fields of its state
+#[derive(Clone, Debug)]
+pub enum RateModelState {
+    Linear(LinearRateModelState),
+    Jump(JumpRateModelState),
+    Pid(PidRateModelState),
+}
+
+impl RateModelState {
+    // `borrow_rate` of the blueprints, the PID model without the error accumulated since its last update
+    pub fn borrow_rate(&self, utilization: Decimal) -> Decimal {
+        let (rate, min_rate, max_rate) = match self {
+            RateModelState::Linear(model) => {
+                (model.base_rate + model.slope * utilization, model.min_rate, model.max_rate)
+            }
+            RateModelState::Jump(model) => {
+                let rate = if utilization <= model.kink {
+                    model.base_rate + model.slope * utilization
+                } else {
+                    model.base_rate + model.slope * model.kink + model.jump_slope * (utilization - model.kink)
+                };
+                (rate, model.min_rate, model.max_rate)
+            }
+            RateModelState::Pid(model) => {
+                let error = utilization - model.target_utilization;
+                (model.base_rate + model.kp * error + model.ki * model.integral, model.min_rate, model.max_rate)
+            }
+        };
+        std::cmp::max(min_rate, std::cmp::min(rate, max_rate))
+    }
+
+    pub fn rate_bounds(&self) -> (Decimal, Decimal) {
+        match self {
+            RateModelState::Linear(model) => (model.min_rate, model.max_rate),
+            RateModelState::Jump(model) => (model.min_rate, model.max_rate),
+            RateModelState::Pid(model) => (model.min_rate, model.max_rate),
+        }
+    }
+}
+
+// Amount and resource of a vault
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VaultBalance {
+    pub resource: ResourceAddress,
+    pub amount: Decimal,
+}
+
+// A Radiswap pool with the balances of its vaults
+#[derive(Clone, Debug)]
+pub struct RadiswapView {
+    pub component: ComponentAddress,
+    pub state: RadiswapState,
+    pub token_a: VaultBalance,
+    pub token_b: VaultBalance,
+    pub pool_units_supply: Decimal,
+}
+
+impl RadiswapView {
+    pub fn fee(&self) -> Decimal {
+        self.state.fee
+    }
+
+    // Price of token A in token B
+    pub fn spot_price(&self) -> Decimal {
+        self.token_b.amount / self.token_a.amount
+    }
+
+    // `Radiswap::price`, the TWAP a market using the pool as price source reads at `now`
+    pub fn twap(&self, now: i64) -> Result<Decimal, String> {
+        self.to_model().price(now, Side::A)
+    }
+
+    // Value of the reserves in token B at the spot price
+    pub fn tvl(&self) -> Decimal {
+        self.token_a.amount * self.spot_price() + self.token_b.amount
+    }
+
+    // Token A and token B one pool unit redeems for
+    pub fn pool_unit_value(&self) -> (Decimal, Decimal) {
+        (self.token_a.amount / self.pool_units_supply, self.token_b.amount / self.pool_units_supply)
+    }
+
+    pub fn is_halted(&self, now: i64) -> bool {
+        now < self.state.circuit_breaker.halted_until
+    }
+
+    // The pool as a reference model, to quote swaps against the current state
+    pub fn to_model(&self) -> RadiswapModel {
+        RadiswapModel {
+            reserve_a: self.token_a.amount,
+            reserve_b: self.token_b.amount,
+            pool_units: self.pool_units_supply,
+            fee: self.state.fee,
+            protocol_fee_share: self.state.insurance_fund.map(|_| self.state.protocol_fee_share),
+            protocol_fees_a: Decimal::zero(),
+            protocol_fees_b: Decimal::zero(),
+            observations: self
+                .state
+                .observations
+                .iter()
+                .map(|observation| Observation {
+                    timestamp: observation.timestamp,
+                    price_a_cumulative: observation.price_a_cumulative,
+                    price_b_cumulative: observation.price_b_cumulative,
+                })
+                .collect(),
+            breaker: breaker_model(&self.state.circuit_breaker),
+        }
+    }
+}
+
+// A ConstantSumAmm market with the balances of its vaults and the supplies of its tokens
+#[derive(Clone, Debug)]
+pub struct MarketView {
+    pub component: ComponentAddress,
+    pub state: ConstantSumAmmState,
+    pub token_a: VaultBalance,
+    pub token_b: VaultBalance,
+    pub cctoken_a: VaultBalance,
+    pub cctoken_b: VaultBalance,
+    pub bonds: VaultBalance,
+    // cctoken A minted for the collateral of the open loans
+    pub loan_claims: VaultBalance,
+    pub cctoken_a_supply: Decimal,
+    pub cctoken_b_supply: Decimal,
+    pub bond_supply: Decimal,
+    pub lp_supply: Decimal,
+    // None when the rate model is not one of the blueprints of the package
+    pub rate_model: Option<RateModelState>,
+}
+
+impl MarketView {
+    pub fn strike_rate(&self) -> Decimal {
+        self.state.strike_rate
+    }
+
+    pub fn constant_product(&self) -> Decimal {
+        self.state.constant_product
+    }
+
+    // `ConstantSumAmm::interest`, the bond tokens per token A of claims held by the pool
+    pub fn interest(&self) -> Decimal {
+        let claims = self.cctoken_a.amount + self.cctoken_b.amount / self.state.strike_rate;
+        if claims.is_zero() || self.bonds.amount.is_zero() {
+            return self.state.interest;
+        }
+        self.bonds.amount / claims
+    }
+
+    // Maturity in seconds since the unix epoch
+    pub fn maturity(&self) -> i64 {
+        self.state.duration
+    }
+
+    // Value of the token vaults in token B at `price`, the price of token A in token B
+    pub fn tvl(&self, price: Decimal) -> Decimal {
+        self.token_a.amount * price + self.token_b.amount
+    }
+
+    // `ConstantSumAmm::utilization`
+    pub fn utilization(&self) -> Decimal {
+        if self.bond_supply.is_zero() {
+            return Decimal::zero();
+        }
+        dec!(1) - self.bonds.amount / self.bond_supply
+    }
+
+    // Yearly rate paid by borrowers according to the rate model
+    pub fn borrow_apr(&self) -> Option<Decimal> {
+        Some(self.rate_model.as_ref()?.borrow_rate(self.utilization()))
+    }
+
+    // Yearly rate earned by lenders, the borrow rate scaled by utilization
+    pub fn supply_apr(&self) -> Option<Decimal> {
+        Some(self.borrow_apr()? * self.utilization())
+    }
+
+    // Yearly rate of the interest required by the LPs, over the time left at `now`
+    pub fn required_apr(&self, now: i64) -> Option<Decimal> {
+        let left = self.state.duration - now;
+        if left <= 0 {
+            return None;
+        }
+        Some(self.interest() * Decimal::from(SECONDS_PER_YEAR) / Decimal::from(left))
+    }
+
+    // Debt and collateral of the open loans
+    pub fn total_debt(&self) -> (Decimal, Decimal) {
+        self.state.loans.values().fold((Decimal::zero(), Decimal::zero()), |(debt, collateral), loan| {
+            (debt + loan.debt, collateral + loan.collateral)
+        })
+    }
+
+    // The market as a reference model, to quote lends and borrows against the current state, with the loans
+    // numbered in no particular order. Fails when the rate bounds are unknown.
+    pub fn to_model(&self) -> Result<MarketModel, String> {
+        let (min_rate, max_rate) = self
+            .rate_model
+            .as_ref()
+            .map(RateModelState::rate_bounds)
+            .ok_or("Unknown rate model")?;
+        let mut loans = BTreeMap::new();
+        for (id, loan) in self.state.loans.values().enumerate() {
+            loans.insert(id as u64, LoanModel { debt: loan.debt, collateral: loan.collateral });
+        }
+
+        Ok(MarketModel {
+            token_a: self.token_a.amount,
+            token_b: self.token_b.amount,
+            cct_a: self.cctoken_a.amount,
+            cct_b: self.cctoken_b.amount,
+            bonds: self.bonds.amount,
+            loan_claims: self.loan_claims.amount,
+            cct_a_supply: self.cctoken_a_supply,
+            cct_b_supply: self.cctoken_b_supply,
+            bond_supply: self.bond_supply,
+            lp_supply: self.lp_supply,
+            duration: self.state.duration,
+            strike_rate: self.state.strike_rate,
+            constant_product: self.state.constant_product,
+            interest: self.state.interest,
+            max_price_deviation: self.state.max_price_deviation,
+            keeper_reward: self.state.keeper_reward,
+            min_rate,
+            max_rate,
+            next_loan_id: loans.len() as u64,
+            loans,
+            liquidation_threshold: self.state.liquidation_threshold,
+            close_factor: self.state.close_factor,
+            liquidation_bonus: self.state.liquidation_bonus,
+            protocol_fee_share: self.state.protocol_fee_share,
+            redemption_rate: self.state.redemption_rate,
+            haircuts: Vec::new(),
+            // The balances of the insurance fund are not part of the market
+            insurance_a: Decimal::zero(),
+            insurance_b: Decimal::zero(),
+            breaker: breaker_model(&self.state.circuit_breaker),
+        })
+    }
+}
+
+fn breaker_model(state: &CircuitBreakerState) -> BreakerModel {
+    BreakerModel {
+        max_move: state.max_move,
+        window: state.window,
+        max_reference_deviation: state.max_reference_deviation,
+        cooldown: state.cooldown,
+        window_start: state.window_start,
+        window_start_price: state.window_start_price,
+        halted_until: state.halted_until,
+        halts: state.halts.len(),
+    }
+}
+
+// Opens the ledger of `resim`, found in `~/.scrypto` unless `resim` was given another data directory. `resim`
+// must not be running a transaction at the same time.
+pub fn open_ledger(dir: &Path) -> Result<RadixEngineDB, String> {
+    if !dir.is_dir() {
+        return Err(format!("{}: no simulator ledger", dir.display()));
+    }
+    Ok(RadixEngineDB::new(dir.to_path_buf()))
+}
+
+pub struct Client<'s, S: ReadableSubstateStore> {
+    store: &'s S,
+}
+
+impl<'s, S: ReadableSubstateStore> Client<'s, S> {
+    pub fn new(store: &'s S) -> Self {
+        Self { store }
+    }
+
+    fn substate(&self, node: RENodeId, offset: SubstateOffset) -> Result<PersistedSubstate, String> {
+        let id = SubstateId(node, offset);
+        self.store
+            .get_substate(&id)
+            .map(|output| output.substate)
+            .ok_or_else(|| format!("no substate {:?}", id))
+    }
+
+    // Node a global address points to
+    fn deref(&self, address: GlobalAddress) -> Result<RENodeId, String> {
+        match self.substate(RENodeId::Global(address), SubstateOffset::Global(GlobalOffset::Global))? {
+            PersistedSubstate::Global(global) => Ok(global.node_deref()),
+            _ => Err(format!("{:?} is not a global address", address)),
+        }
+    }
+
+    // Id of the node behind a component address, which receipts use to tell whose vaults changed
+    pub fn component_id(&self, component: ComponentAddress) -> Result<ComponentId, String> {
+        match self.deref(GlobalAddress::Component(component))? {
+            RENodeId::Component(id) => Ok(id),
+            node => Err(format!("{:?} points to {:?}, not a component", component, node)),
+        }
+    }
+
+    // State of a component, decoded as `T`
+    pub fn component_state<T: ScryptoDecode>(&self, component: ComponentAddress) -> Result<T, String> {
+        let node = self.deref(GlobalAddress::Component(component))?;
+        match self.substate(node, SubstateOffset::Component(ComponentOffset::State))? {
+            PersistedSubstate::ComponentState(state) => scrypto_decode(&state.raw).map_err(|error| {
+                format!("{:?} does not decode as {}: {:?}", component, std::any::type_name::<T>(), error)
+            }),
+            _ => Err(format!("{:?} has no state", component)),
+        }
+    }
+
+    pub fn vault(&self, vault: &Vault) -> Result<VaultBalance, String> {
+        match self.substate(RENodeId::Vault(vault.0), SubstateOffset::Vault(VaultOffset::Vault))? {
+            PersistedSubstate::Vault(vault) => {
+                Ok(VaultBalance { resource: vault.0.resource_address(), amount: vault.0.amount() })
+            }
+            _ => Err(format!("{:?} is not a vault", vault.0)),
+        }
+    }
+
+    pub fn total_supply(&self, resource: ResourceAddress) -> Result<Decimal, String> {
+        let node = self.deref(GlobalAddress::Resource(resource))?;
+        match self.substate(node, SubstateOffset::ResourceManager(ResourceManagerOffset::ResourceManager))? {
+            PersistedSubstate::ResourceManager(manager) => Ok(manager.total_supply),
+            _ => Err(format!("{:?} is not a resource", resource)),
+        }
+    }
+
+    pub fn radiswap(&self, component: ComponentAddress) -> Result<RadiswapView, String> {
+        let state: RadiswapState = self.component_state(component)?;
+        Ok(RadiswapView {
+            component,
+            token_a: self.vault(&state.vault_a)?,
+            token_b: self.vault(&state.vault_b)?,
+            pool_units_supply: self.total_supply(state.pool_units_resource_address)?,
+            state,
+        })
+    }
+
+    pub fn rate_model(&self, component: ComponentAddress) -> Option<RateModelState> {
+        if let Ok(state) = self.component_state(component) {
+            return Some(RateModelState::Linear(state));
+        }
+        if let Ok(state) = self.component_state(component) {
+            return Some(RateModelState::Jump(state));
+        }
+        self.component_state(component).ok().map(RateModelState::Pid)
+    }
+
+    pub fn market(&self, component: ComponentAddress) -> Result<MarketView, String> {
+        let state: ConstantSumAmmState = self.component_state(component)?;
+        let cctoken_a = self.vault(&state.cct_a)?;
+        let cctoken_b = self.vault(&state.cct_b)?;
+        let bonds = self.vault(&state.bt_per_second_vault)?;
+        let loan_claims = self.vault(&state.loan_claims)?;
+        Ok(MarketView {
+            component,
+            token_a: self.vault(&state.token_a_vault)?,
+            token_b: self.vault(&state.token_b_vault)?,
+            cctoken_a_supply: self.total_supply(cctoken_a.resource)?,
+            cctoken_b_supply: self.total_supply(cctoken_b.resource)?,
+            bond_supply: self.total_supply(bonds.resource)?,
+            lp_supply: self.total_supply(state.lp_resource_address)?,
+            cctoken_a,
+            cctoken_b,
+            bonds,
+            loan_claims,
+            rate_model: self.rate_model(state.rate_model),
+            state,
+        })
+    }
+
+    // Price of token A in token B the market reads from its price source at `now`, when that is a Radiswap pool
+    pub fn market_price(&self, market: &MarketView, now: i64) -> Result<Decimal, String> {
+        self.radiswap(market.state.price_source)?.twap(now)
+    }
+}
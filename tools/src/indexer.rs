@@ -0,0 +1,310 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use radix_engine::ledger::ReadableSubstateStore;
+use radix_engine::transaction::{TransactionOutcome, TransactionReceipt, TransactionResult};
+use scrypto::prelude::*;
+use scrypto::radix_engine_interface::address::Bech32Encoder;
+use scrypto::radix_engine_interface::node::NetworkDefinition;
+use serde::{Deserialize, Serialize};
+use transaction::model::{BasicInstruction, TransactionManifest};
+
+use crate::client::Client;
+
+// Turns the receipts of transactions calling Radiswap and ConstantSumAmm components into normalized records,
+// kept one JSON object per line so a history can be appended to and read back by any tool. Addresses are
+// bech32 encoded and amounts are decimal strings, signed from the point of view of their holder: a swap of
+// 10 A into a pool shows -10 A for the account and +10 A for the pool.
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Blueprint {
+    Radiswap,
+    ConstantSumAmm,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    Swap,
+    AddLiquidity,
+    RemoveLiquidity,
+    Lend,
+    Borrow,
+    Repay,
+    Claim,
+    Rebalance,
+    Liquidate,
+}
+
+// Kind of a call to `method` of a `blueprint` component, None for calls which move no funds of their caller
+pub fn classify(blueprint: Blueprint, method: &str) -> Option<Kind> {
+    match (blueprint, method) {
+        (Blueprint::Radiswap, "swap") => Some(Kind::Swap),
+        (Blueprint::Radiswap, "add_liquidity") => Some(Kind::AddLiquidity),
+        (Blueprint::Radiswap, "remove_liquidity") => Some(Kind::RemoveLiquidity),
+        (Blueprint::ConstantSumAmm, "deposit_liquidity_a" | "deposit_liquidity_b") => Some(Kind::AddLiquidity),
+        (Blueprint::ConstantSumAmm, "withdraw_liquidity") => Some(Kind::RemoveLiquidity),
+        (Blueprint::ConstantSumAmm, "lend_a" | "lend_b") => Some(Kind::Lend),
+        (Blueprint::ConstantSumAmm, "borrow_a") => Some(Kind::Borrow),
+        (Blueprint::ConstantSumAmm, "repay_a") => Some(Kind::Repay),
+        (Blueprint::ConstantSumAmm, "option_a_withdraw" | "option_b_withdraw") => Some(Kind::Claim),
+        (Blueprint::ConstantSumAmm, "rebalance_transaction") => Some(Kind::Rebalance),
+        (Blueprint::ConstantSumAmm, "liquidate" | "bid") => Some(Kind::Liquidate),
+        _ => None,
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Record {
+    // Position of the transaction in the indexed history, records of the same transaction share it
+    pub transaction: u64,
+    // Seconds since the unix epoch
+    pub timestamp: i64,
+    pub account: String,
+    pub component: String,
+    pub blueprint: Blueprint,
+    pub method: String,
+    pub kind: Kind,
+    // Net change of the balances of the account per resource. A transaction calling several components can't
+    // be split between them, so the changes go to its first record and are left empty on the others.
+    #[serde(with = "decimal_map")]
+    pub account_changes: BTreeMap<String, Decimal>,
+    // Net change of the vaults of the component per resource over the whole transaction
+    #[serde(with = "decimal_map")]
+    pub component_changes: BTreeMap<String, Decimal>,
+}
+
+mod decimal_map {
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+
+    use scrypto::prelude::Decimal;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(map: &BTreeMap<String, Decimal>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(map.iter().map(|(resource, amount)| (resource, amount.to_string())))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<String, Decimal>, D::Error> {
+        BTreeMap::<String, String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(resource, amount)| match Decimal::from_str(&amount) {
+                Ok(amount) => Ok((resource, amount)),
+                Err(_) => Err(D::Error::custom(format!("{} is not a decimal", amount))),
+            })
+            .collect()
+    }
+}
+
+pub struct Indexer {
+    encoder: Bech32Encoder,
+    components: HashMap<ComponentAddress, Blueprint>,
+    next_transaction: u64,
+}
+
+impl Indexer {
+    pub fn new(network: &NetworkDefinition) -> Self {
+        Self { encoder: Bech32Encoder::new(network), components: HashMap::new(), next_transaction: 0 }
+    }
+
+    pub fn component_address(&self, component: ComponentAddress) -> String {
+        self.encoder.encode_component_address_to_string(&component)
+    }
+
+    pub fn resource_address(&self, resource: ResourceAddress) -> String {
+        self.encoder.encode_resource_address_to_string(&resource)
+    }
+
+    // Components instantiated by an indexed transaction are registered on their own. Others, e.g. created
+    // before indexing started or by the market factory, must be registered to have their calls indexed.
+    pub fn register(&mut self, component: ComponentAddress, blueprint: Blueprint) {
+        self.components.insert(component, blueprint);
+    }
+
+    // Continues the numbering of a history read back from a store
+    pub fn resume(&mut self, records: &[Record]) {
+        if let Some(last) = records.iter().map(|record| record.transaction).max() {
+            self.next_transaction = self.next_transaction.max(last + 1);
+        }
+    }
+
+    // Records of a transaction `account` signed at `timestamp`. `store` is the ledger the receipt was committed
+    // to and is only read to find the vaults of the components. Failed and rejected transactions move no funds
+    // and give no records, nor do transactions which call no registered component.
+    pub fn index<S: ReadableSubstateStore>(&mut self, store: &S, timestamp: i64, account: ComponentAddress,
+    manifest: &TransactionManifest, receipt: &TransactionReceipt) -> Vec<Record> {
+        let commit = match &receipt.result {
+            TransactionResult::Commit(commit) if matches!(commit.outcome, TransactionOutcome::Success(_)) => commit,
+            _ => return Vec::new(),
+        };
+
+        // Calls in manifest order. New components are taken in the order they were instantiated, which is the
+        // order of the calls as each instantiating function creates a single one.
+        let mut new_components = commit.entity_changes.new_component_addresses.iter();
+        let mut calls: Vec<(ComponentAddress, Blueprint, String, Kind)> = Vec::new();
+        for instruction in &manifest.instructions {
+            match instruction {
+                BasicInstruction::CallFunction { blueprint_name, function_name, .. } => {
+                    let blueprint = match (blueprint_name.as_str(), function_name.as_str()) {
+                        ("Radiswap", "instantiate_radiswap" | "instantiate_radiswap_with_insurance") => {
+                            Blueprint::Radiswap
+                        }
+                        ("ConstantSumAmm", "locking_liquidity") => Blueprint::ConstantSumAmm,
+                        _ => continue,
+                    };
+                    let component = match new_components.next() {
+                        Some(component) => *component,
+                        None => continue,
+                    };
+                    self.register(component, blueprint);
+                    calls.push((component, blueprint, function_name.clone(), Kind::AddLiquidity));
+                }
+                BasicInstruction::CallMethod { component_address, method_name, .. } => {
+                    let blueprint = match self.components.get(component_address) {
+                        Some(blueprint) => *blueprint,
+                        None => continue,
+                    };
+                    if let Some(kind) = classify(blueprint, method_name) {
+                        calls.push((*component_address, blueprint, method_name.clone(), kind));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if calls.is_empty() {
+            return Vec::new();
+        }
+
+        let transaction = self.next_transaction;
+        self.next_transaction += 1;
+        let client = Client::new(store);
+        let changes = |component: ComponentAddress| -> BTreeMap<String, Decimal> {
+            let id = match client.component_id(component) {
+                Ok(id) => id,
+                Err(_) => return BTreeMap::new(),
+            };
+            let mut changes: BTreeMap<String, Decimal> = BTreeMap::new();
+            for change in commit.resource_changes.iter().filter(|change| change.component_id == id) {
+                let resource = self.resource_address(change.resource_address);
+                *changes.entry(resource).or_insert(Decimal::zero()) += change.amount;
+            }
+            changes.retain(|_, amount| !amount.is_zero());
+            changes
+        };
+
+        let mut account_changes = Some(changes(account));
+        calls
+            .into_iter()
+            .map(|(component, blueprint, method, kind)| Record {
+                transaction,
+                timestamp,
+                account: self.component_address(account),
+                component: self.component_address(component),
+                blueprint,
+                method,
+                kind,
+                account_changes: account_changes.take().unwrap_or_default(),
+                component_changes: changes(component),
+            })
+            .collect()
+    }
+}
+
+// History kept as JSON lines in a file, appended to as transactions get indexed
+pub struct JsonlStore {
+    path: PathBuf,
+}
+
+impl JsonlStore {
+    pub fn new(path: &Path) -> Self {
+        Self { path: path.to_path_buf() }
+    }
+
+    pub fn append(&self, records: &[Record]) -> Result<(), String> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|error| format!("{}: {}", self.path.display(), error))?;
+        for record in records {
+            let line = serde_json::to_string(record).map_err(|error| error.to_string())?;
+            writeln!(file, "{}", line).map_err(|error| format!("{}: {}", self.path.display(), error))?;
+        }
+        Ok(())
+    }
+
+    // Every record of the store, an empty history when the file doesn't exist yet
+    pub fn load(&self) -> Result<Vec<Record>, String> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let file = File::open(&self.path).map_err(|error| format!("{}: {}", self.path.display(), error))?;
+        let mut records = Vec::new();
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|error| format!("{}: {}", self.path.display(), error))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = serde_json::from_str(&line)
+                .map_err(|error| format!("{}:{}: {}", self.path.display(), index + 1, error))?;
+            records.push(record);
+        }
+        Ok(records)
+    }
+}
+
+// Records of `account`, oldest first
+pub fn history<'r>(records: &'r [Record], account: &str) -> Vec<&'r Record> {
+    records.iter().filter(|record| record.account == account).collect()
+}
+
+// Net flows of `account` into each component per resource: positive amounts are funds the account put in and
+// has not taken out yet, negative ones are what it took out beyond that, e.g. borrowed tokens or swap outputs
+pub fn positions(records: &[Record], account: &str) -> BTreeMap<String, BTreeMap<String, Decimal>> {
+    let mut positions: BTreeMap<String, BTreeMap<String, Decimal>> = BTreeMap::new();
+    for record in history(records, account) {
+        let position = positions.entry(record.component.clone()).or_default();
+        for (resource, amount) in &record.account_changes {
+            *position.entry(resource.clone()).or_insert(Decimal::zero()) -= *amount;
+        }
+    }
+    positions
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PoolStats {
+    pub counts: BTreeMap<Kind, usize>,
+    // Tokens paid into the pool by swaps, per resource
+    pub volume: BTreeMap<String, Decimal>,
+    // Net change of the vaults of the pool per resource, since the first indexed transaction
+    pub net_flows: BTreeMap<String, Decimal>,
+    pub accounts: BTreeSet<String>,
+    pub first: Option<i64>,
+    pub last: Option<i64>,
+}
+
+// Activity of `component`. Vault changes are counted once per transaction, even if it called the component
+// several times.
+pub fn pool_stats(records: &[Record], component: &str) -> PoolStats {
+    let mut stats = PoolStats::default();
+    let mut counted = BTreeSet::new();
+    for record in records.iter().filter(|record| record.component == component) {
+        *stats.counts.entry(record.kind).or_insert(Decimal::zero()) += 1;
+        stats.accounts.insert(record.account.clone());
+        stats.first = Some(stats.first.map_or(record.timestamp, |first| first.min(record.timestamp)));
+        stats.last = Some(stats.last.map_or(record.timestamp, |last| last.max(record.timestamp)));
+        if !counted.insert(record.transaction) {
+            continue;
+        }
+        for (resource, amount) in &record.component_changes {
+            *stats.net_flows.entry(resource.clone()).or_insert(Decimal::zero()) += *amount;
+            if record.kind == Kind::Swap && amount.is_positive() {
+                *stats.volume.entry(resource.clone()).or_insert(Decimal::zero()) += *amount;
+            }
+        }
+    }
+    stats
+}
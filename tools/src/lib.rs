@@ -0,0 +1,9 @@
+// Native tools for operating the blueprints of the lending package on a simulator ledger
+
+pub mod client;
+pub mod indexer;
+pub mod model;
+pub mod signatures;
+pub mod sim;
+pub mod simulator;
+pub mod template;
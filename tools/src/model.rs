@@ -0,0 +1,831 @@
+use std::collections::BTreeMap;
+
+use scrypto::prelude::*;
+
+// Reference models of what the Radiswap and ConstantSumAmm pools are meant to do, written from their economics
+// rather than from the code of the blueprints:
+//
+// - Radiswap is a constant product market maker. A swap pays out what keeps the product of the reserves
+//   constant for the input left after the fee, and the fee stays in the pool. Pool units are a pro rata claim
+//   on the reserves. Markets see the time weighted average of the spot price.
+// - A ConstantSumAmm market locks token A and token B, which convert into each other at the strike rate, and
+//   issues collateral claims (cctokens) on them. Bonds carry the interest. The market prices them on a constant
+//   product curve between the claims it holds on the token lent, counted in token A, and its bonds per second
+//   until maturity: lending adds claims and pays out bonds off the curve, borrowing converts collateral and
+//   takes claims out.
+// - Debt the collateral of a loan doesn't cover is paid by the insurance fund first, and what it can't pay is
+//   socialized by lowering the redemption rate of every cctoken B.
+// - Both pools stop trading for a while when the price moves too fast or strays from its reference.
+//
+// A call which breaks a rule returns why and leaves the model untouched, as a failed transaction would. Amounts
+// are `Decimal`s like on ledger, and where the rounding depends on the order of the operations they are done in
+// the order the blueprints use, so `tests/differential.rs` can compare the two to the last digit. Only amounts
+// are tracked, not resources.
+
+// The number of seconds in a (non leap) year, as in `rate_model.rs`
+pub const SECONDS_PER_YEAR: i64 = 31_536_000;
+
+// Window of the TWAP a Radiswap pool reports as its price, as in `radiswap.rs`
+pub const PRICE_TWAP_WINDOW: i64 = 600;
+
+// Shortest history in seconds a Radiswap pool computes a TWAP over, as in `radiswap.rs`
+pub const MIN_TWAP_HISTORY: i64 = 60;
+
+// Number of observations a Radiswap pool keeps, as in `radiswap.rs`
+pub const MAX_OBSERVATIONS: usize = 64;
+
+// One of the two tokens of a pool or market
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    A,
+    B,
+}
+
+impl Side {
+    pub fn other(self) -> Side {
+        match self {
+            Side::A => Side::B,
+            Side::B => Side::A,
+        }
+    }
+}
+
+fn check(condition: bool, message: &str) -> Result<(), String> {
+    if condition { Ok(()) } else { Err(message.to_owned()) }
+}
+
+fn div(numerator: Decimal, denominator: Decimal) -> Result<Decimal, String> {
+    check(!denominator.is_zero(), "Division by zero")?;
+    Ok(numerator / denominator)
+}
+
+// Takes `amount` out of a vault or bucket
+fn take(balance: &mut Decimal, amount: Decimal) -> Result<Decimal, String> {
+    check(amount >= Decimal::zero() && amount <= *balance, "Insufficient balance")?;
+    *balance -= amount;
+    Ok(amount)
+}
+
+// Applies `f` to a copy of `state`, keeping the copy only when it succeeds
+fn transact<S: Clone, T>(state: &mut S, f: impl FnOnce(&mut S) -> Result<T, String>) -> Result<T, String> {
+    let mut next = state.clone();
+    let result = f(&mut next)?;
+    *state = next;
+    Ok(result)
+}
+
+// Relative distance of `to` from `from`, none from a zero price
+fn relative_change(from: Decimal, to: Decimal) -> Decimal {
+    if from.is_zero() {
+        return Decimal::zero();
+    }
+    if to > from { (to - from) / from } else { (from - to) / from }
+}
+
+// Output of a constant product curve with reserves `reserve_in` and `reserve_out` for `amount_in`, of which the
+// curve sees the part left after `fee`
+fn constant_product_output(reserve_in: Decimal, reserve_out: Decimal, amount_in: Decimal,
+fee: Decimal) -> Result<Decimal, String> {
+    let kept = dec!("1") - fee;
+    div(reserve_out * kept * amount_in, reserve_in + amount_in * kept)
+}
+
+// A circuit breaker: trading halts for `cooldown` seconds once the price moves more than `max_move` within
+// `window` seconds, or strays more than `max_reference_deviation` from the reference price. Only the guardian
+// resumes it early, the badge isn't modeled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BreakerModel {
+    pub max_move: Decimal,
+    pub window: i64,
+    pub max_reference_deviation: Decimal,
+    pub cooldown: i64,
+    // Start of the current window and the price it opened at, zero until the first trade
+    pub window_start: i64,
+    pub window_start_price: Decimal,
+    pub halted_until: i64,
+    pub halts: usize,
+}
+
+impl BreakerModel {
+    // The limits a pool starts with
+    pub fn new() -> Self {
+        Self {
+            max_move: dec!("0.1"),
+            window: 300,
+            max_reference_deviation: dec!("0.2"),
+            cooldown: 3600,
+            window_start: 0,
+            window_start_price: Decimal::zero(),
+            halted_until: 0,
+            halts: 0,
+        }
+    }
+
+    pub fn configure(&mut self, max_move: Decimal, window: i64, max_reference_deviation: Decimal,
+    cooldown: i64) -> Result<(), String> {
+        check(max_move > Decimal::zero() && max_reference_deviation > Decimal::zero(), "Invalid price limits")?;
+        check(window > 0 && cooldown >= 0, "Invalid durations")?;
+        *self = Self { max_move, window, max_reference_deviation, cooldown, ..self.clone() };
+        Ok(())
+    }
+
+    pub fn is_halted(&self, now: i64) -> bool {
+        now < self.halted_until
+    }
+
+    pub fn assert_open(&self, now: i64) -> Result<(), String> {
+        check(!self.is_halted(now), "Trading is halted by the circuit breaker")
+    }
+
+    // Whether a trade from `price_before` to `price_after` halts trading. The first trade of a window opens it
+    // at the price before the trade.
+    pub fn trip(&mut self, now: i64, price_before: Decimal, price_after: Decimal, reference_price: Decimal) -> bool {
+        let window_over = now >= self.window_start + self.window;
+        if self.window_start_price.is_zero() || window_over {
+            self.window_start = now;
+            self.window_start_price = price_before;
+        }
+
+        let too_fast = relative_change(self.window_start_price, price_after) > self.max_move;
+        let too_far = relative_change(reference_price, price_after) > self.max_reference_deviation;
+        if too_fast || too_far {
+            self.halt(now);
+        }
+        too_fast || too_far
+    }
+
+    // Halts trading for the cooldown, when a pool's own limits are crossed
+    pub fn halt(&mut self, now: i64) {
+        self.halted_until = now + self.cooldown;
+        self.halts += 1;
+    }
+
+    // Ends a halt, the next trade opens a new window
+    pub fn resume(&mut self, now: i64) -> Result<(), String> {
+        check(self.is_halted(now), "Trading is not halted")?;
+        self.halted_until = now;
+        self.window_start_price = Decimal::zero();
+        Ok(())
+    }
+}
+
+impl Default for BreakerModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The prices of token A and of token B summed over time, up to `timestamp`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Observation {
+    pub timestamp: i64,
+    pub price_a_cumulative: Decimal,
+    pub price_b_cumulative: Decimal,
+}
+
+// A Radiswap pool: its reserves, the pool units claiming them, its fees and its price history
+#[derive(Debug, Clone, PartialEq)]
+pub struct RadiswapModel {
+    pub reserve_a: Decimal,
+    pub reserve_b: Decimal,
+    // Total supply of pool units
+    pub pool_units: Decimal,
+    pub fee: Decimal,
+    // Share of the fees sent to the insurance fund, only when the pool has one
+    pub protocol_fee_share: Option<Decimal>,
+    // Fees sent to the insurance fund so far
+    pub protocol_fees_a: Decimal,
+    pub protocol_fees_b: Decimal,
+    pub observations: Vec<Observation>,
+    pub breaker: BreakerModel,
+}
+
+impl RadiswapModel {
+    // A pool of `amount_a` and `amount_b`, whose creator holds 100 pool units
+    pub fn instantiate(now: i64, amount_a: Decimal, amount_b: Decimal, fee: Decimal) -> Result<Self, String> {
+        Self::instantiate_with_insurance(now, amount_a, amount_b, fee, None)
+    }
+
+    pub fn instantiate_with_insurance(now: i64, amount_a: Decimal, amount_b: Decimal, fee: Decimal,
+    protocol_fee_share: Option<Decimal>) -> Result<Self, String> {
+        let is_share = |value: Decimal| value >= Decimal::zero() && value <= Decimal::one();
+        check(!amount_a.is_zero() && !amount_b.is_zero(), "You must pass in an initial supply of each token")?;
+        check(is_share(fee), "Invalid fee in thousandths")?;
+        check(is_share(protocol_fee_share.unwrap_or_default()), "Invalid protocol fee share")?;
+
+        let origin =
+            Observation { timestamp: now, price_a_cumulative: Decimal::zero(), price_b_cumulative: Decimal::zero() };
+        Ok(Self {
+            reserve_a: amount_a,
+            reserve_b: amount_b,
+            pool_units: dec!(100),
+            fee,
+            protocol_fee_share,
+            protocol_fees_a: Decimal::zero(),
+            protocol_fees_b: Decimal::zero(),
+            observations: vec![origin],
+            breaker: BreakerModel::new(),
+        })
+    }
+
+    fn reserve_mut(&mut self, side: Side) -> &mut Decimal {
+        match side {
+            Side::A => &mut self.reserve_a,
+            Side::B => &mut self.reserve_b,
+        }
+    }
+
+    // Swaps `amount` of the `input` token. Returns the side and amount handed back, which is the input itself
+    // when the swap trips the circuit breaker.
+    pub fn swap(&mut self, now: i64, input: Side, amount: Decimal) -> Result<(Side, Decimal), String> {
+        transact(self, |pool| pool.swap_inner(now, input, amount))
+    }
+
+    fn swap_inner(&mut self, now: i64, input: Side, amount: Decimal) -> Result<(Side, Decimal), String> {
+        self.breaker.assert_open(now)?;
+        self.record_observation(now);
+
+        // The insurance fund's cut of the fee never enters the pool
+        let protocol_fee = self.protocol_fee_share.map_or(Decimal::zero(), |share| amount * self.fee * share);
+        let traded = amount - protocol_fee;
+
+        let (reserve_in, reserve_out) = match input {
+            Side::A => (self.reserve_a, self.reserve_b),
+            Side::B => (self.reserve_b, self.reserve_a),
+        };
+        let output = constant_product_output(reserve_in, reserve_out, traded, self.fee)?;
+
+        // A trade which would move the price too far is refused and the input handed back
+        let (after_a, after_b) = match input {
+            Side::A => (reserve_in + traded, reserve_out - output),
+            Side::B => (reserve_out - output, reserve_in + traded),
+        };
+        let price_before = self.spot_price(Side::A)?;
+        let price_after = div(after_b, after_a)?;
+        let reference = self.twap(now, Side::A, PRICE_TWAP_WINDOW).unwrap_or(price_before);
+        if self.breaker.trip(now, price_before, price_after, reference) {
+            return Ok((input, amount));
+        }
+
+        match input {
+            Side::A => self.protocol_fees_a += protocol_fee,
+            Side::B => self.protocol_fees_b += protocol_fee,
+        }
+        *self.reserve_mut(input) += traded;
+        let output = take(self.reserve_mut(input.other()), output)?;
+        Ok((input.other(), output))
+    }
+
+    // Adds as much of `dm` token A and `dn` token B as fits the ratio of the reserves. Returns the token A and
+    // token B handed back and the pool units minted, a share of the supply equal to the share of token A added.
+    pub fn add_liquidity(&mut self, now: i64, dm: Decimal, dn: Decimal) -> Result<(Decimal, Decimal, Decimal), String> {
+        transact(self, |pool| pool.add_liquidity_inner(now, dm, dn))
+    }
+
+    fn add_liquidity_inner(&mut self, now: i64, dm: Decimal,
+    dn: Decimal) -> Result<(Decimal, Decimal, Decimal), String> {
+        self.record_observation(now);
+
+        let (m, n) = (self.reserve_a, self.reserve_b);
+        let (added_a, added_b) = if m.is_zero() || n.is_zero() {
+            (dm, dn)
+        } else {
+            let (pool_ratio, deposit_ratio) = (div(m, n)?, div(dm, dn)?);
+            if pool_ratio == deposit_ratio {
+                (dm, dn)
+            } else if pool_ratio < deposit_ratio {
+                // Too much token A, all of token B goes in
+                (div(dn * m, n)?, dn)
+            } else {
+                (dm, div(dm * n, m)?)
+            }
+        };
+        let (mut change_a, mut change_b) = (dm, dn);
+        self.reserve_a += take(&mut change_a, added_a)?;
+        self.reserve_b += take(&mut change_b, added_b)?;
+
+        let minted = if self.pool_units.is_zero() { dec!(100) } else { div(added_a * self.pool_units, m)? };
+        self.pool_units += minted;
+        Ok((change_a, change_b, minted))
+    }
+
+    // Burns `units` pool units for their share of both reserves
+    pub fn remove_liquidity(&mut self, now: i64, units: Decimal) -> Result<(Decimal, Decimal), String> {
+        transact(self, |pool| {
+            pool.record_observation(now);
+            let share = div(units, pool.pool_units)?;
+            take(&mut pool.pool_units, units)?;
+            let (owed_a, owed_b) = (pool.reserve_a * share, pool.reserve_b * share);
+            Ok((take(&mut pool.reserve_a, owed_a)?, take(&mut pool.reserve_b, owed_b)?))
+        })
+    }
+
+    // Price of the `base` token in the other one, the ratio of the reserves
+    pub fn spot_price(&self, base: Side) -> Result<Decimal, String> {
+        match base {
+            Side::A => div(self.reserve_b, self.reserve_a),
+            Side::B => div(self.reserve_a, self.reserve_b),
+        }
+    }
+
+    // Average spot price of `base` over the last `window` seconds, or over the whole history when it is shorter.
+    // A pool younger than `MIN_TWAP_HISTORY` has no average yet.
+    pub fn twap(&self, now: i64, base: Side, window: i64) -> Result<Decimal, String> {
+        let oldest = self.observations[0];
+        let start = self.observations.iter().rev().find(|observation| observation.timestamp <= now - window);
+        let start = *start.unwrap_or(&oldest);
+
+        let elapsed = now - start.timestamp;
+        if now - oldest.timestamp < MIN_TWAP_HISTORY || elapsed <= 0 {
+            return Err("Not enough price history for a time weighted average price".to_owned());
+        }
+
+        let latest = self.accumulate(now);
+        let summed = match base {
+            Side::A => latest.price_a_cumulative - start.price_a_cumulative,
+            Side::B => latest.price_b_cumulative - start.price_b_cumulative,
+        };
+        Ok(summed / Decimal::from(elapsed))
+    }
+
+    // The price a market using the pool as price source sees
+    pub fn price(&self, now: i64, base: Side) -> Result<Decimal, String> {
+        self.twap(now, base, PRICE_TWAP_WINDOW)
+    }
+
+    pub fn is_halted(&self, now: i64) -> bool {
+        self.breaker.is_halted(now)
+    }
+
+    // Closes the price history at `now` before the reserves change, dropping the oldest observation once the
+    // history is full
+    fn record_observation(&mut self, now: i64) {
+        let last = self.observations[self.observations.len() - 1];
+        if now <= last.timestamp {
+            return;
+        }
+
+        self.observations.push(self.accumulate(now));
+        if self.observations.len() > MAX_OBSERVATIONS {
+            self.observations.remove(0);
+        }
+    }
+
+    // The last observation carried forward to `now` at the current spot prices
+    fn accumulate(&self, now: i64) -> Observation {
+        let last = self.observations[self.observations.len() - 1];
+        let elapsed = Decimal::from(now - last.timestamp);
+        if elapsed <= Decimal::zero() || self.reserve_a.is_zero() || self.reserve_b.is_zero() {
+            return last;
+        }
+
+        Observation {
+            timestamp: now,
+            price_a_cumulative: last.price_a_cumulative + self.reserve_b / self.reserve_a * elapsed,
+            price_b_cumulative: last.price_b_cumulative + self.reserve_a / self.reserve_b * elapsed,
+        }
+    }
+}
+
+// An open loan: token B owed and token A held as collateral
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoanModel {
+    pub debt: Decimal,
+    pub collateral: Decimal,
+}
+
+// A loss the insurance fund could not fully cover
+#[derive(Debug, Clone, PartialEq)]
+pub struct HaircutModel {
+    pub timestamp: i64,
+    pub shortfall: Decimal,
+    pub covered: Decimal,
+    pub socialized: Decimal,
+}
+
+// What the liquidity provider of a new market receives
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locked {
+    pub cctoken_a: Decimal,
+    pub cctoken_b: Decimal,
+    pub bonds: Decimal,
+}
+
+// What a borrower receives
+#[derive(Debug, Clone, PartialEq)]
+pub struct Borrowed {
+    // Token B borrowed, the debt of the loan
+    pub token_b: Decimal,
+    pub loan_id: u64,
+    // Bonds quoted, paid in token A collateral
+    pub bonds: Decimal,
+    // cctoken B handed back, beyond what the conversion burned
+    pub cctoken_b: Decimal,
+}
+
+// A ConstantSumAmm market, with the LinearRateModel bounds and the balances of its insurance fund
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketModel {
+    // Tokens locked in the market
+    pub token_a: Decimal,
+    pub token_b: Decimal,
+    // Claims the market holds for the liquidity providers, and the bonds priced against them
+    pub cct_a: Decimal,
+    pub cct_b: Decimal,
+    pub bonds: Decimal,
+    // cctoken A minted for the collateral of the open loans
+    pub loan_claims: Decimal,
+    pub cct_a_supply: Decimal,
+    pub cct_b_supply: Decimal,
+    pub bond_supply: Decimal,
+    pub lp_supply: Decimal,
+    // Maturity in seconds since the unix epoch, which the bond curve also counts its rate per second over
+    pub duration: i64,
+    pub strike_rate: Decimal,
+    // Claims times bonds per second on the bond curve, zero until liquidity is deposited
+    pub constant_product: Decimal,
+    pub interest: Decimal,
+    pub max_price_deviation: Decimal,
+    pub keeper_reward: Decimal,
+    // Yearly rates the interest quoted on a principal is kept between
+    pub min_rate: Decimal,
+    pub max_rate: Decimal,
+    pub loans: BTreeMap<u64, LoanModel>,
+    pub next_loan_id: u64,
+    pub liquidation_threshold: Decimal,
+    pub close_factor: Decimal,
+    pub liquidation_bonus: Decimal,
+    pub protocol_fee_share: Decimal,
+    // Token B paid out per cctoken B
+    pub redemption_rate: Decimal,
+    pub haircuts: Vec<HaircutModel>,
+    pub insurance_a: Decimal,
+    pub insurance_b: Decimal,
+    pub breaker: BreakerModel,
+}
+
+impl MarketModel {
+    // A market locking `amount_a` token A and `amount_b` token B, which must be worth the same at the strike rate.
+    // The liquidity provider gets a claim on each and `required_interest` bonds per token A of claims, token B
+    // counting at the strike rate. The interest quoted is kept between `min_rate` and `max_rate` a year.
+    pub fn locking_liquidity(now: i64, amount_a: Decimal, amount_b: Decimal, duration: i64, required_interest: Decimal,
+    strike_rate: Decimal, min_rate: Decimal, max_rate: Decimal) -> Result<(Self, Locked), String> {
+        check(div(amount_a, amount_b)? == dec!(1) / strike_rate, "Tokens provided in the wrong ratio")?;
+        check(now <= duration, "Maturity of the pool is over")?;
+        let bonds = required_interest * (amount_a + (amount_b / strike_rate));
+
+        let market = Self {
+            token_a: amount_a,
+            token_b: amount_b,
+            cct_a: Decimal::zero(),
+            cct_b: Decimal::zero(),
+            bonds: Decimal::zero(),
+            loan_claims: Decimal::zero(),
+            cct_a_supply: amount_a,
+            cct_b_supply: amount_b,
+            bond_supply: bonds,
+            lp_supply: Decimal::zero(),
+            duration,
+            strike_rate,
+            constant_product: Decimal::zero(),
+            interest: required_interest,
+            max_price_deviation: dec!("0.05"),
+            keeper_reward: dec!("0.1"),
+            min_rate,
+            max_rate,
+            loans: BTreeMap::new(),
+            next_loan_id: 0,
+            liquidation_threshold: dec!("0.9"),
+            close_factor: dec!("0.5"),
+            liquidation_bonus: dec!("0.05"),
+            protocol_fee_share: dec!("0.1"),
+            redemption_rate: dec!(1),
+            haircuts: Vec::new(),
+            insurance_a: Decimal::zero(),
+            insurance_b: Decimal::zero(),
+            breaker: BreakerModel::new(),
+        };
+        Ok((market, Locked { cctoken_a: amount_a, cctoken_b: amount_b, bonds }))
+    }
+
+    // Share of the bonds which are out of the market
+    pub fn utilization(&self) -> Decimal {
+        if self.bond_supply.is_zero() {
+            return Decimal::zero();
+        }
+        dec!(1) - self.bonds / self.bond_supply
+    }
+
+    // Bonds per second of the bond curve
+    fn bond_rate(&self) -> Decimal {
+        self.bonds / Decimal::from(self.duration)
+    }
+
+    // Bonds the curve pays out, or asks for when negative, to move from the current bond rate to the one which
+    // keeps the constant product at `claims`
+    fn curve_quote(&self, claims: Decimal) -> Result<Decimal, String> {
+        Ok((self.bond_rate() - div(self.constant_product, claims)?) * Decimal::from(self.duration))
+    }
+
+    // Keeps the bonds quoted for `principal`, in token A, between the yearly rate bounds over the time left
+    fn clamp_quote(&self, now: i64, bonds: Decimal, principal: Decimal) -> Decimal {
+        let years_left = Decimal::from(std::cmp::max(self.duration - now, 0)) / Decimal::from(SECONDS_PER_YEAR);
+        let floor = principal * self.min_rate * years_left;
+        let ceiling = principal * self.max_rate * years_left;
+        std::cmp::max(floor, std::cmp::min(bonds, ceiling))
+    }
+
+    // Locks `amount` token A or token B, minting a claim on it and a bond per token A of its value
+    fn lock(&mut self, side: Side, amount: Decimal) -> (Decimal, Decimal) {
+        match side {
+            Side::A => {
+                self.token_a += amount;
+                self.cct_a_supply += amount;
+                self.bond_supply += amount;
+                (amount, amount)
+            }
+            Side::B => {
+                self.token_b += amount;
+                self.cct_b_supply += amount;
+                let bonds = amount / self.strike_rate;
+                self.bond_supply += bonds;
+                (amount, bonds)
+            }
+        }
+    }
+
+    // Swaps `amount` of `side` into the market for the other token at the strike rate, burning the matching
+    // claims on the other token. Token B is released at the redemption rate. Returns what was released.
+    fn convert(&mut self, side: Side, amount: Decimal) -> Result<Decimal, String> {
+        match side {
+            Side::A => {
+                let released = amount * self.strike_rate;
+                take(&mut self.cct_b_supply, released)?;
+                self.cct_a_supply += amount;
+                self.token_a += amount;
+                take(&mut self.token_b, released * self.redemption_rate)
+            }
+            Side::B => {
+                let released = amount / self.strike_rate;
+                take(&mut self.cct_a_supply, released)?;
+                self.cct_b_supply += amount;
+                self.token_b += amount;
+                take(&mut self.token_a, released)
+            }
+        }
+    }
+
+    // Lends `amount` token B. The lender's claim joins the market's and the lender gets the bonds of the deposit
+    // and the bonds the curve pays for it. Returns both.
+    pub fn lend_a(&mut self, now: i64, amount: Decimal) -> Result<(Decimal, Decimal), String> {
+        transact(self, |market| {
+            let (claim, bonds) = market.lock(Side::B, amount);
+            let quote = market.curve_quote((market.cct_b / market.strike_rate) + bonds)?;
+            let quote = market.clamp_quote(now, quote, bonds);
+            market.cct_b += claim;
+            Ok((bonds, take(&mut market.bonds, quote)?))
+        })
+    }
+
+    // Lends `amount` token A. The lender's claim joins the market's and the lender gets the bonds of the deposit
+    // and the bonds the curve pays for it. Returns both.
+    pub fn lend_b(&mut self, now: i64, amount: Decimal) -> Result<(Decimal, Decimal), String> {
+        transact(self, |market| {
+            let (claim, bonds) = market.lock(Side::A, amount);
+            let quote = market.curve_quote(market.cct_a + bonds)?;
+            let quote = market.clamp_quote(now, quote, bonds);
+            market.cct_a += claim;
+            Ok((bonds, take(&mut market.bonds, quote)?))
+        })
+    }
+
+    // Borrows token B against `collateral` token A, taking `borrow_amount` worth of claims out of the curve. The
+    // interest is paid upfront in collateral, which goes back to the market as bonds, and owed at the strike rate
+    // on top of what is borrowed until the loan is repaid. The rest of the collateral is converted at the strike
+    // rate, burning the cctoken B it releases out of `cc_amount`.
+    pub fn borrow_a(&mut self, now: i64, borrow_amount: Decimal, collateral: Decimal,
+    cc_amount: Decimal) -> Result<Borrowed, String> {
+        transact(self, |market| {
+            let claims_left = market.cct_b / market.strike_rate - collateral / market.strike_rate;
+            let quote = -market.curve_quote(claims_left)?;
+            let interest = market.clamp_quote(now, quote, borrow_amount / market.strike_rate);
+
+            let mut collateral_left = collateral;
+            let interest = take(&mut collateral_left, interest)?;
+            let (claim, bonds) = market.lock(Side::A, interest);
+            market.bonds += bonds;
+
+            let mut cc_left = cc_amount;
+            take(&mut cc_left, collateral_left * market.strike_rate)?;
+            let borrowed = market.convert(Side::A, collateral_left)?;
+            check(borrowed >= borrow_amount, "Collateral does not cover the borrow amount")?;
+
+            let loan_id = market.next_loan_id;
+            market.next_loan_id += 1;
+            market.loans.insert(loan_id, LoanModel { debt: borrowed + interest * market.strike_rate, collateral });
+            market.loan_claims += claim + collateral_left;
+
+            Ok(Borrowed { token_b: borrowed, loan_id, bonds: interest, cctoken_b: cc_left })
+        })
+    }
+
+    // Repays `amount` token B of a loan, releasing the same share of its collateral. Returns the collateral
+    // released and the change.
+    pub fn repay_a(&mut self, loan_id: u64, amount: Decimal) -> Result<(Decimal, Decimal), String> {
+        transact(self, |market| {
+            let loan = market.loans.get_mut(&loan_id).ok_or("Loan is closed")?;
+            let repaid = std::cmp::min(loan.debt, amount);
+            let released =
+                if repaid == loan.debt { loan.collateral } else { div(loan.collateral * repaid, loan.debt)? };
+            loan.debt -= repaid;
+            loan.collateral -= released;
+            if loan.debt.is_zero() {
+                market.loans.remove(&loan_id);
+            }
+
+            market.token_b += repaid;
+            market.release_collateral(released)?;
+            Ok((take(&mut market.token_a, released)?, amount - repaid))
+        })
+    }
+
+    // Collateral value over debt at `price`, weighted by the liquidation threshold. Below 1 the loan can be
+    // liquidated.
+    pub fn health_factor(&self, loan_id: u64, price: Decimal) -> Result<Decimal, String> {
+        let loan = self.loans.get(&loan_id).ok_or("Loan is closed")?;
+        div(loan.collateral * price * self.liquidation_threshold, loan.debt)
+    }
+
+    // Repays up to the close factor of an unhealthy loan with `amount` token B, for collateral worth the repayment
+    // and the liquidation bonus at the price of `pool`. The price is checked as for rebalancing, one which trips
+    // the circuit breaker seizes nothing. Debt left without collateral is a shortfall. Returns the collateral
+    // seized and the change.
+    pub fn liquidate(&mut self, pool: &RadiswapModel, now: i64, loan_id: u64,
+    amount: Decimal) -> Result<(Decimal, Decimal), String> {
+        transact(self, |market| {
+            if market.checked_market_price(pool, now)?.is_none() {
+                return Ok((Decimal::zero(), amount));
+            }
+            let price = pool.price(now, Side::A)?;
+            check(market.health_factor(loan_id, price)? < dec!(1), "Loan is healthy")?;
+
+            let close_factor = market.close_factor;
+            let bonus = market.liquidation_bonus;
+            let loan = market.loans.get_mut(&loan_id).unwrap();
+            let repaid = std::cmp::min(loan.debt * close_factor, amount);
+            let seized = std::cmp::min(div(repaid, price)? * (dec!(1) + bonus), loan.collateral);
+            loan.debt -= repaid;
+            loan.collateral -= seized;
+            let unbacked = if loan.collateral.is_zero() { loan.debt } else { Decimal::zero() };
+            if loan.debt.is_zero() || loan.collateral.is_zero() {
+                market.loans.remove(&loan_id);
+            }
+
+            market.token_b += repaid;
+            market.release_collateral(seized)?;
+            let seized = take(&mut market.token_a, seized)?;
+            market.absorb_shortfall(now, unbacked);
+            Ok((seized, amount - repaid))
+        })
+    }
+
+    // Closes a loan left unpaid after maturity. Its collateral is sold at `price` and stays in the market until
+    // it is, the debt it doesn't cover is a shortfall.
+    pub fn forfeit_loan(&mut self, now: i64, loan_id: u64, price: Decimal) -> Result<(), String> {
+        transact(self, |market| {
+            check(now > market.duration, "Loans can only be forfeited after maturity")?;
+            let loan = market.loans.remove(&loan_id).ok_or("Loan is closed")?;
+            market.release_collateral(loan.collateral)?;
+
+            let recovered = loan.collateral * price;
+            market.absorb_shortfall(now, std::cmp::max(loan.debt - recovered, Decimal::zero()));
+            Ok(())
+        })
+    }
+
+    // Collateral leaving the loans takes its claim out of the supply
+    fn release_collateral(&mut self, amount: Decimal) -> Result<(), String> {
+        take(&mut self.loan_claims, amount)?;
+        take(&mut self.cct_a_supply, amount)?;
+        Ok(())
+    }
+
+    // The insurance fund pays what it can of `shortfall`, every cctoken B holder bears the rest pro rata
+    fn absorb_shortfall(&mut self, now: i64, shortfall: Decimal) {
+        if shortfall.is_zero() {
+            return;
+        }
+
+        let covered = std::cmp::min(shortfall, self.insurance_b);
+        self.insurance_b -= covered;
+        self.token_b += covered;
+
+        let socialized = shortfall - covered;
+        if socialized.is_zero() {
+            return;
+        }
+        if self.cct_b_supply > Decimal::zero() {
+            self.redemption_rate -= std::cmp::min(socialized / self.cct_b_supply, self.redemption_rate);
+        }
+        self.haircuts.push(HaircutModel { timestamp: now, shortfall, covered, socialized });
+    }
+
+    // A keeper brings `amount` of `collateral` while the market price of token A is on the other side of the
+    // strike rate. The market converts it at the strike rate and sells the other token through `pool`, which is
+    // also the price source. The keeper gets the collateral back and its reward share of the surplus, the
+    // insurance fund its fee and the market the rest. Returns the side and amount handed back to the keeper,
+    // which is the collateral itself when the price trips a circuit breaker.
+    pub fn rebalance(&mut self, pool: &mut RadiswapModel, now: i64, collateral: Side, amount: Decimal,
+    min_output: Decimal) -> Result<(Side, Decimal), String> {
+        let mut state = (self.clone(), pool.clone());
+        let result = transact(&mut state, |(market, pool)| {
+            market.rebalance_inner(pool, now, collateral, amount, min_output)
+        })?;
+        *self = state.0;
+        *pool = state.1;
+        Ok(result)
+    }
+
+    fn rebalance_inner(&mut self, pool: &mut RadiswapModel, now: i64, collateral: Side, amount: Decimal,
+    min_output: Decimal) -> Result<(Side, Decimal), String> {
+        check(!amount.is_zero(), "No tokens provided")?;
+        let market_price = match self.checked_market_price(pool, now)? {
+            Some(price) => price,
+            None => return Ok((collateral, amount)),
+        };
+
+        // The market gives up its own claims on the token it sells, the keeper's collateral takes their place
+        let (output_side, output) = match collateral {
+            Side::B => {
+                check(market_price > self.strike_rate, "Market price does not justify rebalancing into token b")?;
+                take(&mut self.cct_a, amount / self.strike_rate)?;
+                let sold = self.convert(Side::B, amount)?;
+                let output = pool.swap_inner(now, Side::A, sold)?;
+                self.cct_b += amount;
+                output
+            }
+            Side::A => {
+                check(market_price < self.strike_rate, "Market price does not justify rebalancing into token a")?;
+                take(&mut self.cct_b, amount * self.strike_rate)?;
+                let sold = self.convert(Side::A, amount)?;
+                let output = pool.swap_inner(now, Side::B, sold)?;
+                self.cct_a += amount;
+                output
+            }
+        };
+        check(output_side == collateral, "Radiswap halted trading during the rebalance")?;
+        check(output >= min_output, "Rebalance output is below the minimum")?;
+        check(output > amount, "Rebalance is not profitable at the market price")?;
+
+        let surplus = output - amount;
+        let mut keeper = output;
+        let mut kept = take(&mut keeper, surplus * (dec!(1) - self.keeper_reward))?;
+        let fee = take(&mut kept, kept * self.protocol_fee_share)?;
+        match collateral {
+            Side::A => {
+                self.insurance_a += fee;
+                self.token_a += kept;
+            }
+            Side::B => {
+                self.insurance_b += fee;
+                self.token_b += kept;
+            }
+        }
+        Ok((collateral, keeper))
+    }
+
+    // The spot price of token A in `pool`, refused when either pool is halted. None, halting the market, when it
+    // trips the market's circuit breaker or strays more than the allowed deviation from the reference price.
+    fn checked_market_price(&mut self, pool: &RadiswapModel, now: i64) -> Result<Option<Decimal>, String> {
+        self.breaker.assert_open(now)?;
+        check(!pool.is_halted(now), "Radiswap trading is halted")?;
+        let spot = pool.spot_price(Side::A)?;
+        let reference = pool.price(now, Side::A)?;
+        if self.breaker.trip(now, spot, spot, reference) {
+            return Ok(None);
+        }
+        if relative_change(reference, spot) > self.max_price_deviation {
+            self.breaker.halt(now);
+            return Ok(None);
+        }
+        Ok(Some(spot))
+    }
+
+    // Redeems `cc_amount` cctoken A for as much token A, burning `bond_amount` bonds with them
+    pub fn option_a_withdraw(&mut self, cc_amount: Decimal, bond_amount: Decimal) -> Result<Decimal, String> {
+        transact(self, |market| {
+            take(&mut market.cct_a_supply, cc_amount)?;
+            take(&mut market.bond_supply, bond_amount)?;
+            take(&mut market.token_a, cc_amount)
+        })
+    }
+
+    // Redeems `cc_amount` cctoken B for token B at the redemption rate, burning `bond_amount` bonds with them
+    pub fn option_b_withdraw(&mut self, cc_amount: Decimal, bond_amount: Decimal) -> Result<Decimal, String> {
+        transact(self, |market| {
+            take(&mut market.cct_b_supply, cc_amount)?;
+            take(&mut market.bond_supply, bond_amount)?;
+            take(&mut market.token_b, cc_amount * market.redemption_rate)
+        })
+    }
+}
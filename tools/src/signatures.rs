@@ -0,0 +1,392 @@
+use scrypto::abi::{BlueprintAbi, Fields, Type};
+use scrypto::prelude::*;
+use transaction::model::{BasicInstruction, TransactionManifest};
+
+// Kinds of the arguments taken by the blueprint entry points, as they appear in a compiled manifest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ArgType {
+    Bool,
+    // A `u64` or a `usize`, whichever width the encoding gives the latter
+    U64,
+    I64,
+    String,
+    Decimal,
+    PackageAddress,
+    ComponentAddress,
+    ResourceAddress,
+    NonFungibleLocalId,
+    Bucket,
+    Proof,
+    // An `Option` or another enum, e.g. a `Multisig` action
+    Enum,
+    // A `Vec<u8>`, e.g. SBOR encoded arguments
+    Bytes,
+    Map,
+}
+
+// Signature of a function or method of a blueprint of the package
+#[derive(Debug, Clone, Copy)]
+pub struct Signature {
+    pub blueprint: &'static str,
+    pub name: &'static str,
+    // Functions are called on the package, methods on a component
+    pub is_function: bool,
+    pub args: &'static [ArgType],
+}
+
+const fn function(blueprint: &'static str, name: &'static str, args: &'static [ArgType]) -> Signature {
+    Signature { blueprint, name, is_function: true, args }
+}
+
+const fn method(blueprint: &'static str, name: &'static str, args: &'static [ArgType]) -> Signature {
+    Signature { blueprint, name, is_function: false, args }
+}
+
+use ArgType as A;
+
+// Blueprints of the package, in the order of `SIGNATURES`
+pub const BLUEPRINTS: &[&str] = &[
+    "Radiswap",
+    "ConstantSumAmm",
+    "MarketFactory",
+    "LendingMarket",
+    "PriceOracle",
+    "InsuranceFund",
+    "LinearRateModel",
+    "JumpRateModel",
+    "PidRateModel",
+    "StakingRewards",
+    "EmissionController",
+    "VoteEscrow",
+    "Multisig",
+];
+
+// Entry points of every blueprint of the package, compared with the ABI the package exports by
+// `tools/tests/signatures.rs`
+pub const SIGNATURES: &[Signature] = &[
+    function("Radiswap", "instantiate_radiswap", &[A::Bucket, A::Bucket, A::Decimal]),
+    function("Radiswap", "instantiate_radiswap_with_insurance",
+        &[A::Bucket, A::Bucket, A::Decimal, A::Enum, A::Decimal]),
+    method("Radiswap", "swap", &[A::Bucket]),
+    method("Radiswap", "add_liquidity", &[A::Bucket, A::Bucket]),
+    method("Radiswap", "remove_liquidity", &[A::Bucket]),
+    method("Radiswap", "spot_price", &[A::ResourceAddress]),
+    method("Radiswap", "twap", &[A::ResourceAddress, A::I64]),
+    method("Radiswap", "set_fee", &[A::Decimal]),
+    method("Radiswap", "set_insurance_fund", &[A::Enum, A::Decimal]),
+    method("Radiswap", "set_circuit_breaker", &[A::Decimal, A::I64, A::Decimal, A::I64, A::ResourceAddress]),
+    method("Radiswap", "resume_trading", &[A::Proof]),
+    method("Radiswap", "is_halted", &[]),
+    method("Radiswap", "circuit_breaker", &[]),
+    method("Radiswap", "price", &[A::ResourceAddress]),
+    method("Radiswap", "token_addresses", &[]),
+    function("ConstantSumAmm", "locking_liquidity", &[
+        A::Bucket, A::String, A::String, A::Bucket, A::String, A::String,
+        A::I64, A::Decimal, A::Decimal, A::String, A::String,
+        A::ComponentAddress, A::ComponentAddress, A::ComponentAddress, A::Bucket, A::ComponentAddress, A::Bucket,
+    ]),
+    method("ConstantSumAmm", "deposit_liquidity_a", &[A::Bucket, A::Bucket, A::Decimal, A::I64]),
+    method("ConstantSumAmm", "deposit_liquidity_b", &[A::Bucket, A::Bucket, A::Decimal, A::I64]),
+    method("ConstantSumAmm", "withdraw_liquidity", &[A::Bucket, A::Decimal, A::I64]),
+    method("ConstantSumAmm", "rebalance_transaction", &[A::Bucket, A::Decimal]),
+    method("ConstantSumAmm", "convert_option", &[A::Bucket, A::Bucket]),
+    method("ConstantSumAmm", "option_a_deposit", &[A::Bucket]),
+    method("ConstantSumAmm", "option_a_withdraw", &[A::ResourceAddress, A::Bucket, A::Bucket]),
+    method("ConstantSumAmm", "option_b_deposit", &[A::Bucket]),
+    method("ConstantSumAmm", "option_b_withdraw", &[A::ResourceAddress, A::Bucket, A::Bucket]),
+    method("ConstantSumAmm", "lend_a", &[A::Bucket]),
+    method("ConstantSumAmm", "lend_b", &[A::Bucket]),
+    method("ConstantSumAmm", "borrow_a", &[A::Decimal, A::Bucket, A::Bucket]),
+    method("ConstantSumAmm", "repay_a", &[A::Proof, A::Bucket]),
+    method("ConstantSumAmm", "health_factor", &[A::NonFungibleLocalId]),
+    method("ConstantSumAmm", "loan", &[A::NonFungibleLocalId]),
+    method("ConstantSumAmm", "liquidate", &[A::NonFungibleLocalId, A::Bucket, A::Bool]),
+    method("ConstantSumAmm", "utilization", &[]),
+    method("ConstantSumAmm", "borrow_rate", &[]),
+    method("ConstantSumAmm", "supply_rate", &[]),
+    method("ConstantSumAmm", "forfeit_loan", &[A::NonFungibleLocalId]),
+    method("ConstantSumAmm", "auction_price", &[A::NonFungibleLocalId]),
+    method("ConstantSumAmm", "bid", &[A::NonFungibleLocalId, A::Bucket]),
+    method("ConstantSumAmm", "auction_lot", &[A::NonFungibleLocalId]),
+    method("ConstantSumAmm", "auction_lots", &[]),
+    method("ConstantSumAmm", "flash_loan", &[A::ResourceAddress, A::Decimal]),
+    method("ConstantSumAmm", "repay_flash_loan", &[A::Bucket, A::Bucket]),
+    method("ConstantSumAmm", "set_strike_rate", &[A::Decimal]),
+    method("ConstantSumAmm", "set_rebalance_params", &[A::Decimal, A::Decimal]),
+    method("ConstantSumAmm", "set_liquidation_params", &[A::Decimal, A::Decimal, A::Decimal]),
+    method("ConstantSumAmm", "set_auction_params", &[A::Bool, A::Decimal, A::Decimal, A::I64]),
+    method("ConstantSumAmm", "set_fee_params", &[A::Decimal, A::Decimal]),
+    method("ConstantSumAmm", "set_price_source", &[A::ComponentAddress]),
+    method("ConstantSumAmm", "set_circuit_breaker", &[A::Decimal, A::I64, A::Decimal, A::I64, A::ResourceAddress]),
+    method("ConstantSumAmm", "resume_trading", &[A::Proof]),
+    method("ConstantSumAmm", "circuit_breaker", &[]),
+    method("ConstantSumAmm", "haircuts", &[]),
+    method("ConstantSumAmm", "redemption_rate", &[]),
+    method("ConstantSumAmm", "lp_resource_address", &[]),
+    method("ConstantSumAmm", "resource_addresses", &[]),
+    method("ConstantSumAmm", "interest", &[]),
+    function("MarketFactory", "instantiate_market_factory", &[]),
+    method("MarketFactory", "create_market", &[
+        A::Bucket, A::Bucket, A::I64, A::Decimal, A::Decimal,
+        A::ComponentAddress, A::ComponentAddress, A::ComponentAddress, A::Bucket, A::ComponentAddress, A::Bucket,
+    ]),
+    method("MarketFactory", "get_market", &[A::ResourceAddress, A::ResourceAddress, A::Decimal, A::I64]),
+    method("MarketFactory", "markets_for_pair", &[A::ResourceAddress, A::ResourceAddress]),
+    method("MarketFactory", "active_markets", &[]),
+    method("MarketFactory", "expired_markets", &[]),
+    method("MarketFactory", "rollover_lend",
+        &[A::ComponentAddress, A::ComponentAddress, A::Bucket, A::Bucket, A::Decimal, A::Decimal]),
+    method("MarketFactory", "rollover_liquidity",
+        &[A::ComponentAddress, A::ComponentAddress, A::Bucket, A::Decimal, A::Decimal]),
+    function("LendingMarket", "instantiate_lending_market", &[A::ResourceAddress]),
+    method("LendingMarket", "list_asset",
+        &[A::ResourceAddress, A::ComponentAddress, A::Decimal, A::Decimal, A::Decimal, A::Decimal]),
+    method("LendingMarket", "deposit", &[A::Bucket]),
+    method("LendingMarket", "redeem", &[A::Bucket]),
+    method("LendingMarket", "open_position", &[]),
+    method("LendingMarket", "deposit_collateral", &[A::Proof, A::Bucket]),
+    method("LendingMarket", "withdraw_collateral", &[A::Proof, A::ResourceAddress, A::Decimal]),
+    method("LendingMarket", "borrow", &[A::Proof, A::ResourceAddress, A::Decimal]),
+    method("LendingMarket", "repay", &[A::Proof, A::Bucket]),
+    method("LendingMarket", "liquidate", &[A::NonFungibleLocalId, A::Bucket, A::ResourceAddress]),
+    method("LendingMarket", "collect_reserves", &[A::ResourceAddress]),
+    method("LendingMarket", "flash_loan", &[A::ResourceAddress, A::Decimal]),
+    method("LendingMarket", "repay_flash_loan", &[A::Bucket, A::Bucket]),
+    method("LendingMarket", "set_insurance_fund", &[A::ComponentAddress]),
+    method("LendingMarket", "set_liquidation_params", &[A::Decimal, A::Decimal]),
+    method("LendingMarket", "sweep_reserves_to_insurance", &[A::ResourceAddress]),
+    method("LendingMarket", "exchange_rate", &[A::ResourceAddress]),
+    method("LendingMarket", "utilization", &[A::ResourceAddress]),
+    method("LendingMarket", "borrow_rate", &[A::ResourceAddress]),
+    method("LendingMarket", "supply_rate", &[A::ResourceAddress]),
+    method("LendingMarket", "asset_state", &[A::ResourceAddress]),
+    method("LendingMarket", "position", &[A::NonFungibleLocalId]),
+    method("LendingMarket", "position_values", &[A::NonFungibleLocalId]),
+    function("PriceOracle", "instantiate_price_oracle", &[A::ResourceAddress, A::ResourceAddress, A::I64, A::U64]),
+    method("PriceOracle", "add_feeder", &[A::String]),
+    method("PriceOracle", "remove_feeder", &[A::NonFungibleLocalId]),
+    method("PriceOracle", "set_limits", &[A::I64, A::U64]),
+    method("PriceOracle", "push_price", &[A::Proof, A::Decimal]),
+    method("PriceOracle", "price", &[A::ResourceAddress]),
+    method("PriceOracle", "feeder_prices", &[]),
+    function("InsuranceFund", "instantiate_insurance_fund", &[]),
+    method("InsuranceFund", "issue_claimant_badge", &[]),
+    method("InsuranceFund", "deposit_fees", &[A::Bucket]),
+    method("InsuranceFund", "cover_shortfall", &[A::ResourceAddress, A::Decimal]),
+    method("InsuranceFund", "balance", &[A::ResourceAddress]),
+    method("InsuranceFund", "payouts", &[]),
+    function("LinearRateModel", "instantiate_linear_rate_model", &[A::Decimal, A::Decimal, A::Decimal, A::Decimal]),
+    method("LinearRateModel", "borrow_rate", &[A::Decimal]),
+    method("LinearRateModel", "rate_bounds", &[]),
+    method("LinearRateModel", "update", &[A::Decimal]),
+    function("JumpRateModel", "instantiate_jump_rate_model",
+        &[A::Decimal, A::Decimal, A::Decimal, A::Decimal, A::Decimal, A::Decimal]),
+    method("JumpRateModel", "borrow_rate", &[A::Decimal]),
+    method("JumpRateModel", "rate_bounds", &[]),
+    method("JumpRateModel", "update", &[A::Decimal]),
+    function("PidRateModel", "instantiate_pid_rate_model",
+        &[A::Decimal, A::Decimal, A::Decimal, A::Decimal, A::Decimal, A::Decimal]),
+    method("PidRateModel", "borrow_rate", &[A::Decimal]),
+    method("PidRateModel", "rate_bounds", &[]),
+    method("PidRateModel", "update", &[A::Decimal]),
+    method("PidRateModel", "integral", &[]),
+    function("StakingRewards", "instantiate_staking_rewards", &[A::ResourceAddress]),
+    method("StakingRewards", "add_reward", &[A::Bucket, A::I64, A::I64]),
+    method("StakingRewards", "top_up", &[A::Bucket, A::I64]),
+    method("StakingRewards", "stake", &[A::Bucket]),
+    method("StakingRewards", "add_stake", &[A::Proof, A::Bucket]),
+    method("StakingRewards", "unstake", &[A::Proof, A::Decimal]),
+    method("StakingRewards", "claim", &[A::Proof]),
+    method("StakingRewards", "earned", &[A::NonFungibleLocalId]),
+    method("StakingRewards", "streams", &[]),
+    method("StakingRewards", "total_staked", &[]),
+    function("EmissionController", "instantiate_emission_controller",
+        &[A::ResourceAddress, A::Decimal, A::I64, A::ComponentAddress]),
+    method("EmissionController", "register_pool", &[A::ComponentAddress, A::ResourceAddress]),
+    method("EmissionController", "set_emission_per_epoch", &[A::Decimal]),
+    method("EmissionController", "fund", &[A::Bucket]),
+    method("EmissionController", "vote", &[A::Proof, A::Map]),
+    method("EmissionController", "distribute", &[]),
+    method("EmissionController", "current_epoch", &[]),
+    method("EmissionController", "gauges", &[]),
+    method("EmissionController", "weights", &[A::U64]),
+    method("EmissionController", "has_voted", &[A::U64, A::NonFungibleLocalId]),
+    method("EmissionController", "payouts", &[A::U64]),
+    method("EmissionController", "remaining_rewards", &[]),
+    function("VoteEscrow", "instantiate_vote_escrow", &[A::ResourceAddress, A::I64, A::Decimal, A::Decimal, A::I64]),
+    method("VoteEscrow", "lock", &[A::Bucket, A::I64]),
+    method("VoteEscrow", "extend_lock", &[A::Proof, A::I64]),
+    method("VoteEscrow", "withdraw", &[A::Proof]),
+    method("VoteEscrow", "voting_power", &[A::NonFungibleLocalId, A::I64]),
+    method("VoteEscrow", "deposit_admin_badge", &[A::Bucket]),
+    method("VoteEscrow", "propose",
+        &[A::Proof, A::String, A::ComponentAddress, A::String, A::Bytes, A::ResourceAddress]),
+    method("VoteEscrow", "vote", &[A::Proof, A::U64, A::Bool]),
+    method("VoteEscrow", "execute", &[A::U64]),
+    method("VoteEscrow", "proposal", &[A::U64]),
+    method("VoteEscrow", "lock_badge_address", &[]),
+    method("VoteEscrow", "lock_info", &[A::NonFungibleLocalId]),
+    function("Multisig", "instantiate_multisig", &[A::U64, A::U64, A::I64]),
+    method("Multisig", "deposit_badge", &[A::Bucket]),
+    method("Multisig", "queue", &[A::Proof, A::Enum]),
+    method("Multisig", "approve", &[A::Proof, A::U64]),
+    method("Multisig", "cancel", &[A::Proof, A::U64]),
+    method("Multisig", "execute", &[A::U64]),
+    method("Multisig", "transaction", &[A::U64]),
+    method("Multisig", "config", &[]),
+    method("Multisig", "badges", &[]),
+];
+
+// Methods of the accounts the manifests withdraw from, deposit to and lock fees with. Their arguments are not
+// checked.
+pub const ACCOUNT_METHODS: &[&str] = &[
+    "lock_fee",
+    "lock_contingent_fee",
+    "withdraw",
+    "withdraw_by_amount",
+    "withdraw_by_ids",
+    "deposit",
+    "deposit_batch",
+    "create_proof",
+    "create_proof_by_amount",
+    "create_proof_by_ids",
+    "balance",
+];
+
+pub fn arg_type(value: &ScryptoValue) -> Option<ArgType> {
+    match value {
+        ScryptoValue::Bool { .. } => Some(A::Bool),
+        ScryptoValue::U32 { .. } | ScryptoValue::U64 { .. } => Some(A::U64),
+        ScryptoValue::I64 { .. } => Some(A::I64),
+        ScryptoValue::String { .. } => Some(A::String),
+        ScryptoValue::Enum { .. } => Some(A::Enum),
+        ScryptoValue::Array { element_value_kind: ScryptoValueKind::U8, .. } => Some(A::Bytes),
+        ScryptoValue::Map { .. } => Some(A::Map),
+        ScryptoValue::Custom { value } => match value {
+            ScryptoCustomValue::Decimal(_) => Some(A::Decimal),
+            ScryptoCustomValue::PackageAddress(_) => Some(A::PackageAddress),
+            ScryptoCustomValue::ComponentAddress(_) => Some(A::ComponentAddress),
+            ScryptoCustomValue::ResourceAddress(_) => Some(A::ResourceAddress),
+            ScryptoCustomValue::NonFungibleLocalId(_) => Some(A::NonFungibleLocalId),
+            ScryptoCustomValue::Bucket(_) => Some(A::Bucket),
+            ScryptoCustomValue::Proof(_) => Some(A::Proof),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// The kind of an argument as described by the ABI of the package
+pub fn abi_arg_type(kind: &Type) -> Option<ArgType> {
+    match kind {
+        Type::Bool => Some(A::Bool),
+        Type::U32 | Type::U64 => Some(A::U64),
+        Type::I64 => Some(A::I64),
+        Type::String => Some(A::String),
+        Type::Option { .. } | Type::Enum { .. } => Some(A::Enum),
+        Type::Vec { element_type } if matches!(**element_type, Type::U8) => Some(A::Bytes),
+        Type::HashMap { .. } | Type::TreeMap { .. } => Some(A::Map),
+        Type::Decimal => Some(A::Decimal),
+        Type::PackageAddress => Some(A::PackageAddress),
+        Type::ComponentAddress => Some(A::ComponentAddress),
+        Type::ResourceAddress => Some(A::ResourceAddress),
+        Type::NonFungibleLocalId => Some(A::NonFungibleLocalId),
+        Type::Bucket => Some(A::Bucket),
+        Type::Proof => Some(A::Proof),
+        _ => None,
+    }
+}
+
+// The functions and methods of a blueprint as its exported ABI describes them, as (name, is function,
+// arguments). Fails on arguments of a kind `ArgType` doesn't cover.
+pub fn abi_signatures(blueprint: &str, abi: &BlueprintAbi) -> Result<Vec<(String, bool, Vec<ArgType>)>, String> {
+    abi.fns
+        .iter()
+        .map(|function| {
+            let fields: Vec<&Type> = match &function.input {
+                Type::Struct { fields: Fields::Named { named }, .. } => named.iter().map(|(_, kind)| kind).collect(),
+                Type::Struct { fields: Fields::Unnamed { unnamed }, .. } => unnamed.iter().collect(),
+                Type::Struct { fields: Fields::Unit, .. } => Vec::new(),
+                other => return Err(format!("{}::{}: input is not a struct: {:?}", blueprint, function.ident, other)),
+            };
+            let args = fields
+                .into_iter()
+                .map(|kind| {
+                    abi_arg_type(kind)
+                        .ok_or_else(|| format!("{}::{}: unsupported argument {:?}", blueprint, function.ident, kind))
+                })
+                .collect::<Result<Vec<ArgType>, String>>()?;
+            Ok((function.ident.clone(), function.mutability.is_none(), args))
+        })
+        .collect()
+}
+
+fn arg_types(args: &[u8]) -> Result<Vec<Option<ArgType>>, String> {
+    match scrypto_decode::<ScryptoValue>(args) {
+        Ok(ScryptoValue::Tuple { fields }) => Ok(fields.iter().map(arg_type).collect()),
+        Ok(_) => Err("arguments are not a tuple".to_owned()),
+        Err(error) => Err(format!("arguments can't be decoded: {:?}", error)),
+    }
+}
+
+fn matches(signature: &Signature, args: &[Option<ArgType>]) -> bool {
+    signature.args.len() == args.len()
+        && signature.args.iter().zip(args).all(|(expected, found)| Some(*expected) == *found)
+}
+
+fn describe(args: &[Option<ArgType>]) -> String {
+    let kinds: Vec<String> = args
+        .iter()
+        .map(|arg| arg.map(|kind| format!("{:?}", kind)).unwrap_or_else(|| "?".to_owned()))
+        .collect();
+    format!("({})", kinds.join(", "))
+}
+
+// Checks the arguments of every call to a function or method against its signature. Methods are matched by
+// name only, as a manifest doesn't say which blueprint a component belongs to, so a call passes when any
+// blueprint has a method of that name taking those arguments, or when it is a method of an account. Calls to
+// functions and methods which are neither fail.
+pub fn check_manifest(manifest: &TransactionManifest) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    for (index, instruction) in manifest.instructions.iter().enumerate() {
+        let (candidates, name, args): (Vec<&Signature>, &str, &Vec<u8>) = match instruction {
+            BasicInstruction::CallFunction { blueprint_name, function_name, args, .. } => (
+                SIGNATURES
+                    .iter()
+                    .filter(|s| s.is_function && s.blueprint == blueprint_name && s.name == function_name)
+                    .collect(),
+                function_name,
+                args,
+            ),
+            BasicInstruction::CallMethod { method_name, args, .. } => (
+                SIGNATURES.iter().filter(|s| !s.is_function && s.name == method_name).collect(),
+                method_name,
+                args,
+            ),
+            _ => continue,
+        };
+        let is_account_method = matches!(instruction, BasicInstruction::CallMethod { .. })
+            && ACCOUNT_METHODS.contains(&name);
+        if candidates.is_empty() {
+            if !is_account_method {
+                errors.push(format!("instruction {}: {} is not a function or method of the package", index, name));
+            }
+            continue;
+        }
+
+        match arg_types(args) {
+            Ok(found) if candidates.iter().any(|signature| matches(signature, &found)) => {}
+            Ok(_) if is_account_method => {}
+            Ok(found) => errors.push(format!(
+                "instruction {}: {} takes {}, got {}",
+                index,
+                name,
+                describe(&candidates[0].args.iter().map(|arg| Some(*arg)).collect::<Vec<_>>()),
+                describe(&found)
+            )),
+            Err(error) => errors.push(format!("instruction {}: {}", index, error)),
+        }
+    }
+
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
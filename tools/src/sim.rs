@@ -0,0 +1,597 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use scrypto::prelude::*;
+
+use crate::model::{MarketModel, RadiswapModel, Side, SECONDS_PER_YEAR};
+
+// Replays a price path of token A in token B through a Radiswap pool and a ConstantSumAmm market using it as price
+// source, with synthetic agents acting on them:
+// - an arbitrageur moving the pool to the price of the path, and rebalancing the market when that pays
+// - a keeper liquidating the unhealthy loans
+// - a lender alternating lend_a and lend_b
+// - borrowers of token B against token A, repaying before maturity when their collateral is worth more than
+//   their debt and walking away otherwise
+
+// A row of the price path
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PricePoint {
+    // Seconds since the unix epoch
+    pub timestamp: i64,
+    // Price of token A in token B
+    pub price: f64,
+}
+
+// Parses `timestamp,price` lines, skipping blank lines, `#` comments and a header
+pub fn parse_prices(text: &str) -> Result<Vec<PricePoint>, String> {
+    let mut points: Vec<PricePoint> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("timestamp") {
+            continue;
+        }
+        let (timestamp, price) = line
+            .split_once(',')
+            .ok_or_else(|| format!("line {}: expected timestamp,price", number + 1))?;
+        let timestamp: i64 = timestamp
+            .trim()
+            .parse()
+            .map_err(|_| format!("line {}: invalid timestamp {:?}", number + 1, timestamp))?;
+        let price: f64 = price
+            .trim()
+            .parse()
+            .map_err(|_| format!("line {}: invalid price {:?}", number + 1, price))?;
+        if !(price.is_finite() && price > 0.0) {
+            return Err(format!("line {}: prices must be positive", number + 1));
+        }
+        if matches!(points.last(), Some(last) if last.timestamp >= timestamp) {
+            return Err(format!("line {}: timestamps must increase", number + 1));
+        }
+        points.push(PricePoint { timestamp, price });
+    }
+
+    if points.len() < 2 {
+        return Err("the price path needs at least two rows".to_owned());
+    }
+    Ok(points)
+}
+
+pub fn load_prices(path: &Path) -> Result<Vec<PricePoint>, String> {
+    let text = fs::read_to_string(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    parse_prices(&text).map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+pub fn to_decimal(value: f64) -> Decimal {
+    Decimal::from_str(&format!("{:.18}", value)).expect("Not a decimal")
+}
+
+pub fn to_f64(value: Decimal) -> f64 {
+    value.to_string().parse().expect("Not a number")
+}
+
+// `Clock::current_time_rounded_to_minutes`, the time the blueprints see
+fn round_to_minutes(timestamp: i64) -> i64 {
+    timestamp.div_euclid(60) * 60
+}
+
+#[derive(Debug, Clone)]
+pub struct SimConfig {
+    // Token A of the Radiswap pool, its token B follows from the first price
+    pub pool_a: Decimal,
+    pub pool_fee: Decimal,
+    // Token A locked in the market, its token B follows from the strike rate
+    pub locked_a: Decimal,
+    // The first price of the path when not set
+    pub strike_rate: Option<Decimal>,
+    pub required_interest: Decimal,
+    // The last timestamp of the path when not set
+    pub maturity: Option<i64>,
+    // Bounds of the rate model, as given to LinearRateModel
+    pub min_rate: Decimal,
+    pub max_rate: Decimal,
+    // Token B lent every `lend_every` rows, half of the time as token A at the strike rate
+    pub lend_amount: Decimal,
+    pub lend_every: usize,
+    // Token A posted every `borrow_every` rows to borrow its value in token B at the strike rate
+    pub borrow_collateral: Decimal,
+    pub borrow_every: usize,
+    // Share of the cctokens of the market a rebalance uses
+    pub rebalance_share: Decimal,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            pool_a: dec!(100),
+            pool_fee: dec!("0.003"),
+            locked_a: dec!(10),
+            strike_rate: None,
+            required_interest: dec!("0.1"),
+            maturity: None,
+            min_rate: dec!(0),
+            max_rate: dec!(1),
+            lend_amount: dec!(1000),
+            lend_every: 24,
+            borrow_collateral: dec!("0.1"),
+            borrow_every: 24,
+            rebalance_share: dec!("0.1"),
+        }
+    }
+}
+
+// What an LP put in and takes out, valued in token B
+#[derive(Debug, Clone, Default)]
+pub struct LpReport {
+    pub deposited_a: Decimal,
+    pub deposited_b: Decimal,
+    pub withdrawn_a: Decimal,
+    pub withdrawn_b: Decimal,
+    // Deposit at the first price
+    pub initial_value: f64,
+    // Withdrawal at the last price
+    pub final_value: f64,
+    // Deposit at the last price
+    pub hodl_value: f64,
+}
+
+impl LpReport {
+    pub fn pnl(&self) -> f64 {
+        self.final_value - self.initial_value
+    }
+
+    // Relative loss against holding the deposit, net of the fees earned
+    pub fn impermanent_loss(&self) -> f64 {
+        self.final_value / self.hodl_value - 1.0
+    }
+}
+
+// Interest quoted to lenders or borrowers, as annual rates of the bonds over the principal in token A
+#[derive(Debug, Clone, Default)]
+pub struct RateReport {
+    pub count: usize,
+    pub principal: Decimal,
+    pub bonds: Decimal,
+    // Principal weighted sum of the annual rates
+    weighted_rates: f64,
+    pub min_rate: Option<f64>,
+    pub max_rate: Option<f64>,
+}
+
+impl RateReport {
+    fn record(&mut self, principal: Decimal, bonds: Decimal, period: f64) {
+        self.count += 1;
+        self.principal += principal;
+        self.bonds += bonds;
+        if principal.is_zero() || period <= 0.0 {
+            return;
+        }
+        let rate = to_f64(bonds) / to_f64(principal) / period;
+        self.weighted_rates += rate * to_f64(principal);
+        self.min_rate = Some(self.min_rate.map_or(rate, |min| min.min(rate)));
+        self.max_rate = Some(self.max_rate.map_or(rate, |max| max.max(rate)));
+    }
+
+    pub fn average_rate(&self) -> Option<f64> {
+        if self.principal.is_zero() {
+            return None;
+        }
+        Some(self.weighted_rates / to_f64(self.principal))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub start: i64,
+    pub maturity: i64,
+    pub first_price: f64,
+    pub last_price: f64,
+    pub strike_rate: Decimal,
+    pub radiswap_lp: LpReport,
+    pub market_lp: LpReport,
+    // Token A and token B left in the market once its LP redeemed, such as the deposits of the lenders
+    pub stranded_a: Decimal,
+    pub stranded_b: Decimal,
+    pub lending: RateReport,
+    pub borrowing: RateReport,
+    pub repaid_loans: usize,
+    pub liquidations: usize,
+    pub forfeited_loans: usize,
+    // Debt the collateral of the forfeited and liquidated loans did not cover
+    pub bad_debt: Decimal,
+    pub covered_by_insurance: Decimal,
+    pub socialized: Decimal,
+    pub redemption_rate: Decimal,
+    pub arbitrage_swaps: usize,
+    pub rebalances: usize,
+    pub keeper_rewards_a: Decimal,
+    pub keeper_rewards_b: Decimal,
+    pub insurance_fees_a: Decimal,
+    pub insurance_fees_b: Decimal,
+    pub radiswap_halts: usize,
+    pub market_halts: usize,
+    // Calls the blueprints rejected, by panic message
+    pub failures: BTreeMap<String, usize>,
+}
+
+fn percent(value: f64) -> String {
+    format!("{:+.2}%", value * 100.0)
+}
+
+fn rate(value: Option<f64>) -> String {
+    value.map_or("-".to_owned(), |value| format!("{:.2}%", value * 100.0))
+}
+
+fn write_lp(f: &mut fmt::Formatter, name: &str, lp: &LpReport) -> fmt::Result {
+    writeln!(f, "{}", name)?;
+    writeln!(f, "  deposited            {} A + {} B, worth {:.4} B", lp.deposited_a, lp.deposited_b, lp.initial_value)?;
+    writeln!(f, "  withdrawn            {} A + {} B, worth {:.4} B", lp.withdrawn_a, lp.withdrawn_b, lp.final_value)?;
+    writeln!(f, "  pnl                  {:+.4} B ({})", lp.pnl(), percent(lp.pnl() / lp.initial_value))?;
+    writeln!(
+        f,
+        "  impermanent loss     {} against holding, worth {:.4} B",
+        percent(lp.impermanent_loss()),
+        lp.hodl_value
+    )
+}
+
+fn write_rates(f: &mut fmt::Formatter, name: &str, rates: &RateReport) -> fmt::Result {
+    writeln!(f, "{}", name)?;
+    writeln!(f, "  positions            {}, {} A of principal, {} bonds", rates.count, rates.principal, rates.bonds)?;
+    writeln!(
+        f,
+        "  annual rate          {} average, {} to {}",
+        rate(rates.average_rate()),
+        rate(rates.min_rate),
+        rate(rates.max_rate)
+    )
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} to maturity {}, price {} -> {}, strike {}",
+            self.start, self.maturity, self.first_price, self.last_price, self.strike_rate
+        )?;
+        write_lp(f, "Radiswap LP", &self.radiswap_lp)?;
+        write_lp(f, "Market LP", &self.market_lp)?;
+        writeln!(f, "  stranded in market   {} A + {} B", self.stranded_a, self.stranded_b)?;
+        write_rates(f, "Lenders", &self.lending)?;
+        write_rates(f, "Borrowers", &self.borrowing)?;
+        writeln!(
+            f,
+            "  loans                {} repaid, {} liquidations, {} forfeited at maturity",
+            self.repaid_loans, self.liquidations, self.forfeited_loans
+        )?;
+        writeln!(f, "Bad debt")?;
+        writeln!(f, "  shortfall            {} B", self.bad_debt)?;
+        writeln!(f, "  insurance            {} B", self.covered_by_insurance)?;
+        writeln!(f, "  socialized           {} B, redemption rate {}", self.socialized, self.redemption_rate)?;
+        writeln!(f, "Activity")?;
+        writeln!(f, "  arbitrage swaps      {}", self.arbitrage_swaps)?;
+        writeln!(
+            f,
+            "  rebalances           {}, keeper rewards {} A + {} B, insurance fees {} A + {} B",
+            self.rebalances, self.keeper_rewards_a, self.keeper_rewards_b, self.insurance_fees_a, self.insurance_fees_b
+        )?;
+        writeln!(f, "  breaker halts        {} Radiswap, {} market", self.radiswap_halts, self.market_halts)?;
+        for (message, count) in &self.failures {
+            writeln!(f, "  rejected {:>4}x       {}", count, message)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Simulation {
+    pub config: SimConfig,
+    pub pool: RadiswapModel,
+    pub market: MarketModel,
+    prices: Vec<PricePoint>,
+    // Loans of the borrowers which are still open
+    loans: Vec<u64>,
+    lp_cctoken_a: Decimal,
+    lp_cctoken_b: Decimal,
+    lp_bonds: Decimal,
+    report: Report,
+}
+
+impl Simulation {
+    pub fn new(config: SimConfig, prices: Vec<PricePoint>) -> Result<Self, String> {
+        if prices.len() < 2 {
+            return Err("the price path needs at least two rows".to_owned());
+        }
+        let start = round_to_minutes(prices[0].timestamp);
+        let maturity = config.maturity.unwrap_or(prices[prices.len() - 1].timestamp);
+        let first_price = to_decimal(prices[0].price);
+        let strike_rate = config.strike_rate.unwrap_or(first_price);
+
+        let pool = RadiswapModel::instantiate(start, config.pool_a, config.pool_a * first_price, config.pool_fee)
+            .map_err(|error| format!("instantiate_radiswap: {}", error))?;
+        let locked_b = config.locked_a * strike_rate;
+        let (market, locked) = MarketModel::locking_liquidity(
+            start,
+            config.locked_a,
+            locked_b,
+            maturity,
+            config.required_interest,
+            strike_rate,
+            config.min_rate,
+            config.max_rate,
+        )
+        .map_err(|error| format!("locking_liquidity: {}", error))?;
+
+        let report = Report {
+            start,
+            maturity,
+            first_price: prices[0].price,
+            last_price: prices[prices.len() - 1].price,
+            strike_rate,
+            radiswap_lp: LpReport {
+                deposited_a: pool.reserve_a,
+                deposited_b: pool.reserve_b,
+                ..Default::default()
+            },
+            market_lp: LpReport { deposited_a: config.locked_a, deposited_b: locked_b, ..Default::default() },
+            ..Default::default()
+        };
+
+        Ok(Self {
+            config,
+            pool,
+            market,
+            prices,
+            loans: Vec::new(),
+            lp_cctoken_a: locked.cctoken_a,
+            lp_cctoken_b: locked.cctoken_b,
+            lp_bonds: locked.bonds,
+            report,
+        })
+    }
+
+    fn fail(&mut self, call: &str, error: String) {
+        *self.report.failures.entry(format!("{}: {}", call, error)).or_default() += 1;
+    }
+
+    // Years left until maturity, as the rate model counts them
+    fn period(&self, now: i64) -> f64 {
+        std::cmp::max(self.market.duration - now, 0) as f64 / SECONDS_PER_YEAR as f64
+    }
+
+    pub fn run(mut self) -> Report {
+        let prices = std::mem::take(&mut self.prices);
+        let maturity = self.market.duration;
+        let mut settled_loans = false;
+
+        for (step, point) in prices.iter().enumerate() {
+            let now = round_to_minutes(point.timestamp);
+            self.arbitrage(now, point.price);
+
+            if now > maturity {
+                break;
+            }
+            self.liquidate(now);
+            self.rebalance(now);
+            if self.config.lend_every > 0 && step % self.config.lend_every == 0 {
+                self.lend(now, step / self.config.lend_every);
+            }
+            if self.config.borrow_every > 0 && step % self.config.borrow_every == 0 {
+                self.borrow(now);
+            }
+
+            // Borrowers decide at the last row before maturity
+            let next_is_past = prices.get(step + 1).map_or(true, |next| round_to_minutes(next.timestamp) > maturity);
+            if next_is_past && !settled_loans {
+                self.repay(now);
+                settled_loans = true;
+            }
+        }
+
+        let last = &prices[prices.len() - 1];
+        let settlement = std::cmp::max(round_to_minutes(last.timestamp), maturity) + 60;
+        self.settle(settlement, last.price);
+        self.report
+    }
+
+    // Trades the pool to `target`, halving the trade until it stays under the circuit breaker
+    fn arbitrage(&mut self, now: i64, target: f64) {
+        if self.pool.is_halted(now) {
+            return;
+        }
+        let (reserve_a, reserve_b) = (to_f64(self.pool.reserve_a), to_f64(self.pool.reserve_b));
+        let fee = to_f64(self.pool.fee);
+        let spot = reserve_b / reserve_a;
+        if (spot / target - 1.0).abs() <= fee {
+            return;
+        }
+
+        let k = reserve_a * reserve_b;
+        let (input, mut amount) = if spot < target {
+            (Side::B, ((k * target).sqrt() - reserve_b) / (1.0 - fee))
+        } else {
+            (Side::A, ((k / target).sqrt() - reserve_a) / (1.0 - fee))
+        };
+
+        for _ in 0..8 {
+            let mut probe = self.pool.clone();
+            match probe.swap(now, input, to_decimal(amount)) {
+                Ok((side, _)) if side != input => {
+                    self.pool = probe;
+                    self.report.arbitrage_swaps += 1;
+                    return;
+                }
+                Ok(_) => amount /= 2.0,
+                Err(error) => return self.fail("swap", error),
+            }
+        }
+    }
+
+    fn liquidate(&mut self, now: i64) {
+        let price = match self.pool.price(now, Side::A) {
+            Ok(price) => price,
+            Err(error) => return self.fail("price", error),
+        };
+        for id in self.loans.clone() {
+            if !matches!(self.market.health_factor(id, price), Ok(health) if health < dec!(1)) {
+                continue;
+            }
+            let debt = self.market.loans[&id].debt;
+            match self.market.liquidate(&self.pool, now, id, debt * self.market.close_factor) {
+                Ok((seized, _)) if seized.is_zero() => {}
+                Ok(_) => self.report.liquidations += 1,
+                Err(error) => self.fail("liquidate", error),
+            }
+            if !self.market.loans.contains_key(&id) {
+                self.loans.retain(|loan| *loan != id);
+            }
+        }
+    }
+
+    // Rebalances a share of the cctokens of the market when the keeper would be paid for it
+    fn rebalance(&mut self, now: i64) {
+        let Ok(spot) = self.pool.spot_price(Side::A) else { return };
+        let share = self.config.rebalance_share;
+        let (collateral, amount) = if spot > self.market.strike_rate {
+            (Side::B, self.market.cct_a * share * self.market.strike_rate)
+        } else if spot < self.market.strike_rate {
+            (Side::A, self.market.cct_b * share / self.market.strike_rate)
+        } else {
+            return;
+        };
+        if amount.is_zero() {
+            return;
+        }
+
+        // Keepers simulate first, a rejected rebalance would only cost them fees
+        let (mut market, mut pool) = (self.market.clone(), self.pool.clone());
+        let halts = (self.market.breaker.halts, self.pool.breaker.halts);
+        match market.rebalance(&mut pool, now, collateral, amount, Decimal::zero()) {
+            Ok((side, output)) if side == collateral && (market.breaker.halts, pool.breaker.halts) == halts => {
+                self.report.insurance_fees_a += market.insurance_a - self.market.insurance_a;
+                self.report.insurance_fees_b += market.insurance_b - self.market.insurance_b;
+                self.report.rebalances += 1;
+                self.market = market;
+                self.pool = pool;
+                match side {
+                    Side::A => self.report.keeper_rewards_a += output - amount,
+                    Side::B => self.report.keeper_rewards_b += output - amount,
+                }
+            }
+            Ok(_) => {}
+            Err(error) if error.starts_with("Rebalance is not profitable") => {}
+            Err(error) => self.fail("rebalance_transaction", error),
+        }
+    }
+
+    fn lend(&mut self, now: i64, count: usize) {
+        let period = self.period(now);
+        let amount = self.config.lend_amount;
+        let result = if count % 2 == 0 {
+            self.market.lend_a(now, amount).map_err(|error| ("lend_a", error))
+        } else {
+            self.market.lend_b(now, amount / self.market.strike_rate).map_err(|error| ("lend_b", error))
+        };
+        match result {
+            Ok((principal, bonds)) => self.report.lending.record(principal, bonds, period),
+            Err((call, error)) => self.fail(call, error),
+        }
+    }
+
+    fn borrow(&mut self, now: i64) {
+        let period = self.period(now);
+        let collateral = self.config.borrow_collateral;
+        let borrow_amount = collateral * self.market.strike_rate;
+        match self.market.borrow_a(now, borrow_amount, collateral, borrow_amount) {
+            Ok(borrowed) => {
+                self.report.borrowing.record(borrow_amount / self.market.strike_rate, borrowed.bonds, period);
+                self.loans.push(borrowed.loan_id);
+            }
+            Err(error) => self.fail("borrow_a", error),
+        }
+    }
+
+    // Borrowers whose collateral is worth more than their debt repay it, the others walk away
+    fn repay(&mut self, now: i64) {
+        let Ok(price) = self.pool.price(now, Side::A) else { return };
+        for loan in std::mem::take(&mut self.loans) {
+            let Some(state) = self.market.loans.get(&loan).cloned() else { continue };
+            if state.collateral * price <= state.debt {
+                self.loans.push(loan);
+                continue;
+            }
+            match self.market.repay_a(loan, state.debt) {
+                Ok(_) => self.report.repaid_loans += 1,
+                Err(error) => {
+                    self.fail("repay_a", error);
+                    self.loans.push(loan);
+                }
+            }
+        }
+    }
+
+    // Forfeits the unpaid loans, then the LPs of the pool and of the market withdraw
+    fn settle(&mut self, now: i64, last_price: f64) {
+        let price = self.pool.price(now, Side::A).unwrap_or_else(|_| to_decimal(last_price));
+        for loan in std::mem::take(&mut self.loans) {
+            match self.market.forfeit_loan(now, loan, price) {
+                Ok(()) => self.report.forfeited_loans += 1,
+                Err(error) => self.fail("forfeit_loan", error),
+            }
+        }
+        for haircut in &self.market.haircuts {
+            self.report.bad_debt += haircut.shortfall;
+            self.report.covered_by_insurance += haircut.covered;
+            self.report.socialized += haircut.socialized;
+        }
+        // Shortfalls the insurance fund covered in full leave no haircut
+        let covered = self.report.insurance_fees_b - self.market.insurance_b - self.report.covered_by_insurance;
+        if covered > Decimal::zero() {
+            self.report.bad_debt += covered;
+            self.report.covered_by_insurance += covered;
+        }
+        self.report.redemption_rate = self.market.redemption_rate;
+
+        let (first, last) = (self.report.first_price, last_price);
+        match self.pool.remove_liquidity(now, dec!(100)) {
+            Ok((a, b)) => {
+                let lp = &mut self.report.radiswap_lp;
+                lp.withdrawn_a = a;
+                lp.withdrawn_b = b;
+            }
+            Err(error) => self.fail("remove_liquidity", error),
+        }
+
+        // The LP redeems its claims as far as the vaults go
+        let cctoken_a = std::cmp::min(self.lp_cctoken_a, self.market.token_a);
+        match self.market.option_a_withdraw(cctoken_a, Decimal::zero()) {
+            Ok(a) => self.report.market_lp.withdrawn_a = a,
+            Err(error) => self.fail("option_a_withdraw", error),
+        }
+        let cctoken_b = if self.market.redemption_rate.is_zero() {
+            Decimal::zero()
+        } else {
+            std::cmp::min(self.lp_cctoken_b, self.market.token_b / self.market.redemption_rate)
+        };
+        match self.market.option_b_withdraw(cctoken_b, self.lp_bonds) {
+            Ok(b) => self.report.market_lp.withdrawn_b = b,
+            Err(error) => self.fail("option_b_withdraw", error),
+        }
+        self.report.stranded_a = self.market.token_a;
+        self.report.stranded_b = self.market.token_b;
+
+        for lp in [&mut self.report.radiswap_lp, &mut self.report.market_lp] {
+            lp.initial_value = to_f64(lp.deposited_a) * first + to_f64(lp.deposited_b);
+            lp.final_value = to_f64(lp.withdrawn_a) * last + to_f64(lp.withdrawn_b);
+            lp.hodl_value = to_f64(lp.deposited_a) * last + to_f64(lp.deposited_b);
+        }
+        self.report.radiswap_halts = self.pool.breaker.halts;
+        self.report.market_halts = self.market.breaker.halts;
+    }
+}
+
+// Runs a backtest of `config` over `prices`
+pub fn backtest(config: SimConfig, prices: Vec<PricePoint>) -> Result<Report, String> {
+    Ok(Simulation::new(config, prices)?.run())
+}
@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use radix_engine::kernel::ScryptoInterpreter;
+use radix_engine::ledger::{ReadableSubstateStore, WriteableSubstateStore};
+use radix_engine::transaction::{
+    execute_and_commit_transaction, ExecutionConfig, FeeReserveConfig, TransactionOutcome, TransactionReceipt,
+    TransactionResult,
+};
+use radix_engine::wasm::DefaultWasmEngine;
+use scrypto::prelude::*;
+use scrypto::radix_engine_interface::constants::DEFAULT_COST_UNIT_LIMIT;
+use scrypto::radix_engine_interface::model::FromPublicKey;
+use transaction::model::{TestTransaction, TransactionManifest};
+
+use crate::client::Client;
+
+// Runs manifests on a substate store the way `resim run` does, with the proof of a public key in the auth zone
+// of every transaction, and reports what they changed rather than the raw receipt. On the ledger of `resim`
+// (`client::open_ledger`) the transactions are those of its default account when given its public key.
+
+// What a committed transaction did
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub new_components: Vec<ComponentAddress>,
+    pub new_resources: Vec<ResourceAddress>,
+    // Net change of the balances of the account per resource: what the calls returned, less what was withdrawn
+    // to pay for them
+    pub account_changes: BTreeMap<ResourceAddress, Decimal>,
+    pub cost_units: u32,
+}
+
+pub struct Session<'s, S: ReadableSubstateStore + WriteableSubstateStore> {
+    store: &'s mut S,
+    interpreter: ScryptoInterpreter<DefaultWasmEngine>,
+    public_key: EcdsaSecp256k1PublicKey,
+    pub account: ComponentAddress,
+    nonce: u64,
+}
+
+impl<'s, S: ReadableSubstateStore + WriteableSubstateStore> Session<'s, S> {
+    pub fn new(store: &'s mut S, public_key: EcdsaSecp256k1PublicKey, account: ComponentAddress) -> Self {
+        // Transactions are told apart by their nonce, which must not repeat across runs of the tools
+        let nonce = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_nanos() as u64).unwrap_or(0);
+        Self { store, interpreter: ScryptoInterpreter::default(), public_key, account, nonce }
+    }
+
+    pub fn client(&self) -> Client<'_, S> {
+        Client::new(&*self.store)
+    }
+
+    // Executes and commits a manifest. Rejected and failed transactions change nothing but the fee payment,
+    // and give the error of the engine.
+    pub fn execute(&mut self, manifest: TransactionManifest) -> Result<Outcome, String> {
+        self.nonce += 1;
+        let transaction = TestTransaction::new(manifest, self.nonce, DEFAULT_COST_UNIT_LIMIT);
+        let executable = transaction.get_executable(vec![NonFungibleGlobalId::from_public_key(&self.public_key)]);
+        let receipt: TransactionReceipt = execute_and_commit_transaction(
+            &mut *self.store,
+            &self.interpreter,
+            &FeeReserveConfig::default(),
+            &ExecutionConfig::default(),
+            &executable,
+        );
+
+        let commit = match &receipt.result {
+            TransactionResult::Commit(commit) => commit,
+            TransactionResult::Reject(reject) => return Err(format!("Transaction rejected: {:?}", reject)),
+        };
+        if let TransactionOutcome::Failure(error) = &commit.outcome {
+            return Err(format!("Transaction failed: {:?}", error));
+        }
+
+        let account = self.client().component_id(self.account)?;
+        let mut account_changes: BTreeMap<ResourceAddress, Decimal> = BTreeMap::new();
+        for change in commit.resource_changes.iter().filter(|change| change.component_id == account) {
+            *account_changes.entry(change.resource_address).or_insert(Decimal::zero()) += change.amount;
+        }
+        account_changes.retain(|_, amount| !amount.is_zero());
+
+        Ok(Outcome {
+            new_components: commit.entity_changes.new_component_addresses.clone(),
+            new_resources: commit.entity_changes.new_resource_addresses.clone(),
+            account_changes,
+            cost_units: receipt.execution.fee_summary.cost_unit_consumed,
+        })
+    }
+}
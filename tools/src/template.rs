@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use scrypto::prelude::*;
+use scrypto::radix_engine_interface::node::NetworkDefinition;
+use transaction::manifest::compile;
+use transaction::model::TransactionManifest;
+
+use crate::signatures::check_manifest;
+
+// Manifest templates are `.rtm` files with named placeholders such as `$ACCOUNT` or `$BTC`, filled from a
+// config file of `NAME=value` lines, the environment and the command line, in increasing priority
+
+// Values of the placeholders, keyed by name without the `$`
+#[derive(Debug, Clone, Default)]
+pub struct Vars(BTreeMap<String, String>);
+
+impl Vars {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Parses `NAME=value` lines, skipping blank lines and `#` comments
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut vars = Self::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected NAME=value", number + 1))?;
+            let name = name.trim().trim_start_matches('$');
+            if !is_name(name) {
+                return Err(format!("line {}: invalid name {:?}", number + 1, name));
+            }
+            vars.set(name, value.trim());
+        }
+        Ok(vars)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        Self::parse(&text).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.0.insert(name.to_owned(), value.to_owned());
+    }
+
+    // Value of a placeholder. A value of the form `$OTHER` refers to the value of `OTHER`, so that e.g.
+    // `INPUT=$BTC` picks a token by name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        let mut value = self.0.get(name)?.as_str();
+        for _ in 0..self.0.len() {
+            match value.strip_prefix('$') {
+                Some(other) if is_name(other) => value = self.0.get(other)?.as_str(),
+                _ => return Some(value),
+            }
+        }
+        // The references loop
+        None
+    }
+
+    // Name of a placeholder holding `value` itself, rather than a reference to another one, e.g. `BTC` for the
+    // address of the token. Used to label addresses in output.
+    pub fn name_of(&self, value: &str) -> Option<&str> {
+        self.0.iter().find(|(_, other)| other.as_str() == value).map(|(name, _)| name.as_str())
+    }
+
+    // Overrides the values of `names` with the environment variables of the same names
+    pub fn merge_env(&mut self, names: &[String]) {
+        for name in names {
+            if let Ok(value) = std::env::var(name) {
+                self.set(name, &value);
+            }
+        }
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_uppercase() || c == '_')
+        && chars.all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+// Splits a template into literal text and placeholder names
+fn tokens(template: &str) -> Vec<(bool, &str)> {
+    let mut tokens = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('$') {
+        let after = &rest[start + 1..];
+        let length = after
+            .find(|c: char| !(c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_'))
+            .unwrap_or(after.len());
+        if length == 0 || !is_name(&after[..length]) {
+            // A lone `$` is kept as it is
+            tokens.push((false, &rest[..start + 1]));
+            rest = after;
+            continue;
+        }
+        tokens.push((false, &rest[..start]));
+        tokens.push((true, &after[..length]));
+        rest = &after[length..];
+    }
+    tokens.push((false, rest));
+    tokens
+}
+
+// Names of the placeholders of a template, in order of first use
+pub fn placeholders(template: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (is_placeholder, token) in tokens(template) {
+        if is_placeholder && !names.iter().any(|name| name == token) {
+            names.push(token.to_owned());
+        }
+    }
+    names
+}
+
+// Fills every placeholder, failing with the names of those missing a value
+pub fn render(template: &str, vars: &Vars) -> Result<String, Vec<String>> {
+    let mut text = String::with_capacity(template.len());
+    let mut missing: Vec<String> = Vec::new();
+    for (is_placeholder, token) in tokens(template) {
+        if !is_placeholder {
+            text.push_str(token);
+        } else if let Some(value) = vars.get(token) {
+            text.push_str(value);
+        } else if !missing.iter().any(|name| name == token) {
+            missing.push(token.to_owned());
+        }
+    }
+
+    if missing.is_empty() {
+        Ok(text)
+    } else {
+        Err(missing.into_iter().map(|name| format!("no value for ${}", name)).collect())
+    }
+}
+
+// A rendered template, as text for `resim run` and compiled
+pub struct Rendered {
+    pub text: String,
+    pub manifest: TransactionManifest,
+}
+
+// Renders a template, compiles it, which checks the values against the types of the manifest syntax, and
+// checks the arguments of the calls to the blueprints against their signatures. Calls to anything but the
+// blueprints and the accounts are rejected.
+pub fn render_and_check(template: &str, vars: &Vars, network: &NetworkDefinition) -> Result<Rendered, Vec<String>> {
+    let text = render(template, vars)?;
+    let manifest = compile(&text, network, Vec::new()).map_err(|error| vec![format!("{:?}", error)])?;
+    check_manifest(&manifest)?;
+    Ok(Rendered { text, manifest })
+}
+
+// Binary form of a compiled manifest
+pub fn encode(manifest: &TransactionManifest) -> Vec<u8> {
+    scrypto_encode(manifest).expect("Failed to encode the manifest")
+}
+
+// Names of the templates in a directory, without the `.rtm` extension
+pub fn template_names(dir: &Path) -> Result<Vec<String>, String> {
+    let entries = fs::read_dir(dir).map_err(|error| format!("{}: {}", dir.display(), error))?;
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension()? != "rtm" {
+                return None;
+            }
+            Some(path.file_stem()?.to_string_lossy().into_owned())
+        })
+        .collect();
+    names.sort();
+    Ok(names)
+}
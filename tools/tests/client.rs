@@ -0,0 +1,53 @@
+mod fixtures;
+
+use defi_tools::client::{Client, RateModelState};
+use defi_tools::model::Side;
+use fixtures::*;
+use lending::manifests;
+use scrypto::prelude::*;
+
+#[test]
+fn test_radiswap_state_and_derived_views() {
+    let mut ledger = Ledger::new();
+    let pool = ledger.new_pool(dec!(100), dec!(9000));
+
+    let view = Client::new(ledger.test_runner.substate_store()).radiswap(pool.component).unwrap();
+    assert_eq!((view.token_a.resource, view.token_a.amount), (pool.token_a, dec!(100)));
+    assert_eq!((view.token_b.resource, view.token_b.amount), (pool.token_b, dec!(9000)));
+    assert_eq!(view.fee(), dec!("0.003"));
+    assert_eq!(view.pool_units_supply, dec!(100));
+    assert_eq!(view.spot_price(), dec!(90));
+    assert_eq!(view.twap(NOW + 600).unwrap(), dec!(90));
+    assert_eq!(view.tvl(), dec!(18000));
+    assert_eq!(view.pool_unit_value(), (dec!(1), dec!(90)));
+
+    // The model quotes what the pool will pay, for a trade small enough not to trip the circuit breaker
+    let (_, quoted) = view.to_model().swap(NOW, Side::A, dec!(1)).unwrap();
+    ledger.execute_success(manifests::swap(ledger.account, pool.component, pool.token_a, dec!(1)));
+    let view = Client::new(ledger.test_runner.substate_store()).radiswap(pool.component).unwrap();
+    assert_eq!(view.token_b.amount, dec!(9000) - quoted);
+}
+
+#[test]
+fn test_market_state_and_derived_views() {
+    let mut ledger = Ledger::new();
+    let market = ledger.new_market();
+    ledger.execute_success(manifests::lend_a(ledger.account, market.component, market.pool.token_b, dec!(500)));
+
+    let client = Client::new(ledger.test_runner.substate_store());
+    let view = client.market(market.component).unwrap();
+    assert_eq!((view.strike_rate(), view.maturity()), (dec!(100), market.maturity));
+    // Without bond tokens in the pool it quotes the interest required at locking
+    assert_eq!((view.constant_product(), view.interest()), (dec!(0), dec!("0.1")));
+    assert_eq!((view.token_a.amount, view.token_b.amount), (dec!(10), dec!(1500)));
+    assert_eq!((view.cctoken_b.amount, view.cctoken_b_supply), (dec!(500), dec!(1500)));
+    assert_eq!(view.tvl(dec!(100)), dec!(2500));
+    assert_eq!(client.market_price(&view, NOW).unwrap(), dec!(100));
+
+    // Every bond minted by the lend left the pool
+    assert!(matches!(view.rate_model, Some(RateModelState::Linear(_))));
+    assert_eq!(view.utilization(), dec!(1));
+    assert_eq!(view.borrow_apr(), Some(dec!("0.22")));
+    assert_eq!(view.supply_apr(), Some(dec!("0.22")));
+    assert_eq!(view.to_model().unwrap().bond_supply, view.bond_supply);
+}
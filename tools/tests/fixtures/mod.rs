@@ -0,0 +1,122 @@
+// Shared by the tests reading a TestRunner ledger, not all of them use every helper
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use lending::manifests;
+use lending::manifests::LockingLiquidity;
+use radix_engine::transaction::TransactionReceipt;
+use scrypto::prelude::*;
+use scrypto::radix_engine_interface::model::FromPublicKey;
+use scrypto_unit::*;
+use transaction::model::TransactionManifest;
+
+// Seconds since the unix epoch at which every test starts
+pub const NOW: i64 = 1_680_000_000;
+
+// A ledger with the package published and a funded account
+pub struct Ledger {
+    pub test_runner: TestRunner,
+    pub public_key: EcdsaSecp256k1PublicKey,
+    pub account: ComponentAddress,
+    pub package: PackageAddress,
+}
+
+// A Radiswap pool of two fresh tokens
+pub struct Pool {
+    pub component: ComponentAddress,
+    pub token_a: ResourceAddress,
+    pub token_b: ResourceAddress,
+    pub pool_units: ResourceAddress,
+}
+
+// A ConstantSumAmm market over a Radiswap pool, which is also its price source
+pub struct Market {
+    pub component: ComponentAddress,
+    pub pool: Pool,
+    pub maturity: i64,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        let mut test_runner = TestRunner::builder().build();
+        let (public_key, _private_key, account) = test_runner.new_allocated_account();
+        let package = test_runner.compile_and_publish(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(".."));
+        test_runner.set_current_time(NOW * 1000);
+        Self { test_runner, public_key, account, package }
+    }
+
+    // Executes a manifest signed by the test account, without checking the outcome
+    pub fn execute(&mut self, manifest: TransactionManifest) -> TransactionReceipt {
+        self.test_runner.execute_manifest_ignoring_fee(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&self.public_key)],
+        )
+    }
+
+    // Executes a manifest which must succeed. Returns the new components and resources.
+    pub fn execute_success(&mut self, manifest: TransactionManifest) -> (Vec<ComponentAddress>, Vec<ResourceAddress>) {
+        let receipt = self.execute(manifest);
+        let commit = receipt.expect_commit_success();
+        (commit.entity_changes.new_component_addresses.clone(), commit.entity_changes.new_resource_addresses.clone())
+    }
+
+    pub fn new_token(&mut self, amount: Decimal) -> ResourceAddress {
+        self.test_runner.create_fungible_resource(amount, DIVISIBILITY_MAXIMUM, self.account)
+    }
+
+    // Creates two fresh tokens and a pool of `amount_a` token A and `amount_b` token B with a 0.3% fee
+    pub fn new_pool(&mut self, amount_a: Decimal, amount_b: Decimal) -> Pool {
+        let (token_a, token_b) = (self.new_token(amount_a * 10), self.new_token(amount_b * 10));
+        let manifest = manifests::instantiate_radiswap(self.package, self.account, token_a, amount_a, token_b,
+            amount_b, dec!("0.003"));
+
+        // The pool is created a TWAP window in the past, so it has the price history `price` needs at NOW
+        self.test_runner.set_current_time((NOW - 600) * 1000);
+        let (components, resources) = self.execute_success(manifest);
+        self.test_runner.set_current_time(NOW * 1000);
+
+        // Resources are created in order: the pool unit minter badge, the admin badge and the pool units
+        Pool { component: components[0], token_a, token_b, pool_units: resources[2] }
+    }
+
+    // Creates a market locking 10 token A and 1000 token B at a strike rate of 100, maturing in 30 days, over
+    // a pool of 100 token A and 10000 token B. Its rate model is linear, from 2% to 22% without bounds.
+    pub fn new_market(&mut self) -> Market {
+        let (account, package) = (self.account, self.package);
+        let pool = self.new_pool(dec!(100), dec!(10000));
+
+        let (components, resources) = self.execute_success(manifests::instantiate_linear_rate_model(package, account,
+            dec!("0.02"), dec!("0.2"), dec!(0), dec!(1)));
+        let (rate_model, rate_model_badge) = (components[0], resources[0]);
+        let (components, resources) = self.execute_success(manifests::instantiate_insurance_fund(package, account));
+        // Resources are created in order: the admin badge, the claimant minter badge and the claimant badge
+        let (insurance_fund, admin_badge, claimant_badge) = (components[0], resources[0], resources[2]);
+        self.execute_success(manifests::issue_claimant_badge(account, insurance_fund, admin_badge));
+
+        let maturity = NOW + 30 * 24 * 60 * 60;
+        let (components, _) = self.execute_success(manifests::locking_liquidity(package, account, &LockingLiquidity {
+            token_a: pool.token_a,
+            amount_a: dec!(10),
+            token_a_name: "Collateral Claim A".to_owned(),
+            token_a_symbol: "ccA".to_owned(),
+            token_b: pool.token_b,
+            amount_b: dec!(1000),
+            token_b_name: "Collateral Claim B".to_owned(),
+            token_b_symbol: "ccB".to_owned(),
+            maturity,
+            required_interest: dec!("0.1"),
+            strike_rate: dec!(100),
+            lp_name: "A/B LP".to_owned(),
+            lp_symbol: "LP-AB".to_owned(),
+            amm_address: pool.component,
+            price_source: pool.component,
+            rate_model,
+            rate_model_badge,
+            insurance_fund,
+            insurance_badge: claimant_badge,
+        }));
+
+        Market { component: components[0], pool, maturity }
+    }
+}
@@ -0,0 +1,117 @@
+mod fixtures;
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use defi_tools::indexer::*;
+use fixtures::*;
+use lending::manifests;
+use scrypto::prelude::*;
+use scrypto::radix_engine_interface::node::NetworkDefinition;
+use transaction::model::TransactionManifest;
+
+// Executes a manifest and indexes its receipt, whatever its outcome
+fn execute(ledger: &mut Ledger, indexer: &mut Indexer, timestamp: i64, manifest: TransactionManifest) -> Vec<Record> {
+    let receipt = ledger.execute(manifest.clone());
+    indexer.index(ledger.test_runner.substate_store(), timestamp, ledger.account, &manifest, &receipt)
+}
+
+#[test]
+fn test_index_radiswap_trades() {
+    let mut ledger = Ledger::new();
+    let mut indexer = Indexer::new(&NetworkDefinition::simulator());
+    let (account, package) = (ledger.account, ledger.package);
+    let (token_a, token_b) = (ledger.new_token(dec!(1000)), ledger.new_token(dec!(90000)));
+    let (a, b) = (indexer.resource_address(token_a), indexer.resource_address(token_b));
+    let mut history_records = Vec::new();
+
+    // The pool is registered by the transaction instantiating it
+    let manifest = manifests::instantiate_radiswap(package, account, token_a, dec!(100), token_b, dec!(9000),
+        dec!("0.003"));
+    let receipt = ledger.execute(manifest.clone());
+    let radiswap = receipt.expect_commit().entity_changes.new_component_addresses[0];
+    let records = indexer.index(ledger.test_runner.substate_store(), NOW, account, &manifest, &receipt);
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].component, indexer.component_address(radiswap));
+    assert_eq!((records[0].blueprint, records[0].kind), (Blueprint::Radiswap, Kind::AddLiquidity));
+    assert_eq!((records[0].component_changes[&a], records[0].component_changes[&b]), (dec!(100), dec!(9000)));
+    assert_eq!((records[0].account_changes[&a], records[0].account_changes[&b]), (dec!(-100), dec!(-9000)));
+    history_records.extend(records);
+
+    // The swap is small enough not to trip the circuit breaker
+    let records = execute(&mut ledger, &mut indexer, NOW + 60, manifests::swap(account, radiswap, token_a, dec!(1)));
+    assert_eq!((records.len(), records[0].kind, records[0].transaction), (1, Kind::Swap, 1));
+    let output = -records[0].component_changes[&b];
+    assert!(output.is_positive());
+    assert_eq!(records[0].component_changes[&a], dec!(1));
+    assert_eq!((records[0].account_changes[&a], records[0].account_changes[&b]), (dec!(-1), output));
+    history_records.extend(records);
+
+    // Failed transactions and calls to other components are not recorded
+    let manifest = manifests::swap(account, radiswap, token_a, dec!(1000000));
+    assert!(execute(&mut ledger, &mut indexer, NOW + 120, manifest).is_empty());
+    let manifest = manifests::instantiate_insurance_fund(package, account);
+    assert!(execute(&mut ledger, &mut indexer, NOW + 120, manifest).is_empty());
+
+    let manifest = manifests::add_liquidity(account, radiswap, token_a, dec!(10), token_b, dec!(1000));
+    let records = execute(&mut ledger, &mut indexer, NOW + 180, manifest);
+    assert_eq!((records.len(), records[0].kind, records[0].transaction), (1, Kind::AddLiquidity, 2));
+    assert_eq!(records[0].component_changes[&a], dec!(10));
+    history_records.extend(records);
+
+    // The history survives a round trip through the store
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("indexer-radiswap.jsonl");
+    let _ = std::fs::remove_file(&path);
+    let store = JsonlStore::new(&path);
+    assert!(store.load().unwrap().is_empty());
+    store.append(&history_records[..1]).unwrap();
+    store.append(&history_records[1..]).unwrap();
+    let loaded = store.load().unwrap();
+    assert_eq!(loaded, history_records);
+
+    let owner = indexer.component_address(account);
+    let pool = indexer.component_address(radiswap);
+    assert_eq!(history(&loaded, &owner).len(), 3);
+    assert!(history(&loaded, &pool).is_empty());
+
+    // The account put in 111 A and took out the swap output
+    let positions = positions(&loaded, &owner);
+    assert_eq!(positions[&pool][&a], dec!(111));
+
+    let stats = pool_stats(&loaded, &pool);
+    assert_eq!((stats.counts[&Kind::Swap], stats.counts[&Kind::AddLiquidity]), (1, 2));
+    assert_eq!(stats.volume, BTreeMap::from([(a.clone(), dec!(1))]));
+    assert_eq!(stats.net_flows[&a], dec!(111));
+    assert_eq!(stats.accounts.len(), 1);
+    assert_eq!((stats.first, stats.last), (Some(NOW), Some(NOW + 180)));
+
+    // A new indexer carries on the numbering of the stored history
+    let mut resumed = Indexer::new(&NetworkDefinition::simulator());
+    resumed.register(radiswap, Blueprint::Radiswap);
+    resumed.resume(&loaded);
+    let manifest = manifests::swap(account, radiswap, token_b, dec!(10));
+    let records = execute(&mut ledger, &mut resumed, NOW + 240, manifest);
+    assert_eq!((records[0].transaction, records[0].kind), (3, Kind::Swap));
+}
+
+#[test]
+fn test_index_market_lending() {
+    let mut ledger = Ledger::new();
+    let market = ledger.new_market();
+    let mut indexer = Indexer::new(&NetworkDefinition::simulator());
+    indexer.register(market.component, Blueprint::ConstantSumAmm);
+    let b = indexer.resource_address(market.pool.token_b);
+
+    // The lender pays B and gets its claim and the bonds, the ccB tokens minted for the deposit stay in the market
+    let manifest = manifests::lend_a(ledger.account, market.component, market.pool.token_b, dec!(500));
+    let records = execute(&mut ledger, &mut indexer, NOW, manifest);
+    assert_eq!(records.len(), 1);
+    assert_eq!((records[0].blueprint, records[0].method.as_str(), records[0].kind),
+        (Blueprint::ConstantSumAmm, "lend_a", Kind::Lend));
+    assert_eq!((records[0].account_changes[&b], records[0].component_changes[&b]), (dec!(-500), dec!(500)));
+    assert_eq!(records[0].account_changes.values().filter(|amount| amount.is_positive()).count(), 2);
+
+    let stats = pool_stats(&records, &indexer.component_address(market.component));
+    assert_eq!(stats.counts[&Kind::Lend], 1);
+    assert!(stats.volume.is_empty());
+}
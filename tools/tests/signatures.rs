@@ -0,0 +1,29 @@
+mod fixtures;
+
+use defi_tools::signatures::{abi_signatures, ArgType, BLUEPRINTS, SIGNATURES};
+use fixtures::*;
+
+#[test]
+fn test_signatures_match_the_exported_abi() {
+    let mut ledger = Ledger::new();
+
+    for blueprint in BLUEPRINTS {
+        let abi = ledger.test_runner.export_abi(ledger.package, blueprint);
+        let mut exported = abi_signatures(blueprint, &abi).unwrap();
+        exported.sort();
+
+        let mut listed: Vec<(String, bool, Vec<ArgType>)> = SIGNATURES
+            .iter()
+            .filter(|signature| signature.blueprint == *blueprint)
+            .map(|signature| (signature.name.to_owned(), signature.is_function, signature.args.to_vec()))
+            .collect();
+        listed.sort();
+
+        assert_eq!(listed, exported, "{}", blueprint);
+    }
+
+    // Every signature belongs to one of the blueprints compared above
+    for signature in SIGNATURES {
+        assert!(BLUEPRINTS.contains(&signature.blueprint), "{}", signature.blueprint);
+    }
+}
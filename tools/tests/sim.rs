@@ -0,0 +1,64 @@
+use std::path::PathBuf;
+
+use defi_tools::model::{MarketModel, RadiswapModel, Side};
+use defi_tools::sim::{backtest, load_prices, parse_prices, SimConfig};
+use scrypto::prelude::*;
+
+#[test]
+fn test_rejected_calls_leave_the_models_untouched() {
+    let mut pool = RadiswapModel::instantiate(0, dec!(100), dec!(900000), dec!("0.003")).unwrap();
+    let (a, b, units) = pool.add_liquidity(60, dec!(10), dec!(90000)).unwrap();
+    assert_eq!((a, b, units), (dec!(0), dec!(0), dec!(10)));
+
+    let before = pool.clone();
+    assert_eq!(pool.remove_liquidity(120, dec!(1000)), Err("Insufficient balance".to_owned()));
+    assert_eq!(pool, before);
+
+    let (mut market, locked) =
+        MarketModel::locking_liquidity(0, dec!(10), dec!(90000), 1000, dec!("0.1"), dec!(9000), dec!(0), dec!(1))
+            .unwrap();
+    assert_eq!(locked.cctoken_b, dec!(90000));
+
+    // Token B is only worth rebalancing into above the strike rate
+    let before = (market.clone(), pool.clone());
+    assert_eq!(
+        market.rebalance(&mut pool, 120, Side::B, dec!(9000), dec!(0)),
+        Err("Market price does not justify rebalancing into token b".to_owned())
+    );
+    assert_eq!((market, pool), before);
+}
+
+#[test]
+fn test_a_pool_without_price_history_has_no_price() {
+    let pool = RadiswapModel::instantiate(0, dec!(100), dec!(900000), dec!("0.003")).unwrap();
+    assert_eq!(pool.price(30, Side::A), Err("Not enough price history for a time weighted average price".to_owned()));
+    assert_eq!(pool.price(60, Side::A), Ok(dec!(9000)));
+}
+
+#[test]
+fn test_flat_prices_leave_the_lps_whole() {
+    let prices = parse_prices("timestamp,price\n0,9000\n3600,9000\n7200,9000\n").unwrap();
+    let config = SimConfig { lend_every: 0, borrow_every: 0, ..Default::default() };
+    let report = backtest(config, prices).unwrap();
+
+    assert_eq!(report.arbitrage_swaps, 0);
+    assert_eq!(report.radiswap_lp.withdrawn_a, dec!(100));
+    assert_eq!(report.radiswap_lp.impermanent_loss(), 0.0);
+    assert_eq!(report.market_lp.withdrawn_b, dec!(90000));
+    assert_eq!(report.bad_debt, dec!(0));
+}
+
+#[test]
+fn test_backtest_of_the_sample_prices() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("data/prices.csv");
+    let report = backtest(SimConfig::default(), load_prices(&path).unwrap()).unwrap();
+
+    assert!(report.arbitrage_swaps > 0);
+    let lend_failures: usize =
+        report.failures.iter().filter(|(call, _)| call.starts_with("lend")).map(|(_, count)| count).sum();
+    assert_eq!(report.lending.count + lend_failures, 30);
+    // The price ends above where it started, so the pool sold token A on the way up
+    assert!(report.radiswap_lp.withdrawn_a < report.radiswap_lp.deposited_a);
+    assert!(report.radiswap_lp.impermanent_loss() < 0.0);
+    assert_eq!(report.bad_debt, report.covered_by_insurance + report.socialized);
+}
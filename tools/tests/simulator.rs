@@ -0,0 +1,40 @@
+mod fixtures;
+
+use defi_tools::model::Side;
+use defi_tools::simulator::Session;
+use fixtures::*;
+use lending::manifests;
+use scrypto::prelude::*;
+
+#[test]
+fn test_session_reports_account_changes_and_new_entities() {
+    let mut ledger = Ledger::new();
+    let (account, package, public_key) = (ledger.account, ledger.package, ledger.public_key);
+    let (token_a, token_b) = (ledger.new_token(dec!(1000)), ledger.new_token(dec!(90000)));
+    let mut session = Session::new(ledger.test_runner.substate_store_mut(), public_key, account);
+
+    let outcome = session
+        .execute(manifests::instantiate_radiswap(package, account, token_a, dec!(100), token_b, dec!(9000),
+            dec!("0.003")))
+        .unwrap();
+    assert_eq!(outcome.new_components.len(), 1);
+    assert_eq!(outcome.account_changes[&token_a], dec!(-100));
+    assert_eq!(outcome.account_changes[&token_b], dec!(-9000));
+    // Resources are created in order: the pool unit minter badge, the admin badge and the pool units. The
+    // minter badge stays with the pool.
+    assert!(!outcome.account_changes.contains_key(&outcome.new_resources[0]));
+    assert_eq!(outcome.account_changes[&outcome.new_resources[1]], dec!(1));
+    assert_eq!(outcome.account_changes[&outcome.new_resources[2]], dec!(100));
+    assert!(outcome.cost_units > 0);
+
+    let pool = outcome.new_components[0];
+    let (_, quoted) = session.client().radiswap(pool).unwrap().to_model().swap(NOW, Side::A, dec!(1)).unwrap();
+    let outcome = session.execute(manifests::swap(account, pool, token_a, dec!(1))).unwrap();
+    assert!(outcome.new_components.is_empty());
+    assert_eq!(outcome.account_changes[&token_a], dec!(-1));
+    assert_eq!(outcome.account_changes[&token_b], quoted);
+
+    // A failed transaction gives the error instead of the changes
+    let error = session.execute(manifests::swap(account, pool, token_a, dec!(1000000))).unwrap_err();
+    assert!(error.starts_with("Transaction failed"), "{}", error);
+}
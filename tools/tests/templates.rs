@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::PathBuf;
+
+use defi_tools::template::{render, render_and_check, template_names, Vars};
+use scrypto::radix_engine_interface::node::NetworkDefinition;
+
+fn rtm_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../rtm")
+}
+
+// The example config with the amounts usually given on the command line
+fn vars() -> Vars {
+    let mut vars = Vars::load(&rtm_dir().join("sim.env")).unwrap();
+    for (name, value) in [
+        ("AMOUNT", "10"),
+        ("INPUT", "$BTC"),
+        ("COLLATERAL", "1"),
+        ("CC_AMOUNT", "9000"),
+        ("BORROW_AMOUNT", "9000"),
+        ("MIN_OUTPUT", "0"),
+        ("BOND_AMOUNT", "1"),
+    ] {
+        vars.set(name, value);
+    }
+    vars
+}
+
+#[test]
+fn test_every_template_renders_and_checks() {
+    let names = template_names(&rtm_dir()).unwrap();
+    assert!(names.contains(&"csumlockliquidity".to_owned()));
+
+    for name in names {
+        let template = fs::read_to_string(rtm_dir().join(format!("{}.rtm", name))).unwrap();
+        if let Err(errors) = render_and_check(&template, &vars(), &NetworkDefinition::simulator()) {
+            panic!("{}: {:?}", name, errors);
+        }
+    }
+}
+
+#[test]
+fn test_references_and_missing_placeholders() {
+    let mut vars = Vars::new();
+    vars.set("BTC", "resource_sim1qzl0qx5es4ttm6zyhghxvu2c7sckkr9r8ufw94f5t5ysu056gn");
+    vars.set("INPUT", "$BTC");
+    assert_eq!(
+        render("ResourceAddress(\"$INPUT\") $AMOUNT $", &vars),
+        Err(vec!["no value for $AMOUNT".to_owned()])
+    );
+
+    vars.set("AMOUNT", "1");
+    assert_eq!(
+        render("ResourceAddress(\"$INPUT\") $AMOUNT $", &vars).unwrap(),
+        "ResourceAddress(\"resource_sim1qzl0qx5es4ttm6zyhghxvu2c7sckkr9r8ufw94f5t5ysu056gn\") 1 $"
+    );
+}
+
+#[test]
+fn test_wrong_argument_types_are_rejected() {
+    // `swap` takes a bucket, not an amount
+    let template = "CALL_METHOD ComponentAddress(\"$RADISWAP\") \"swap\" Decimal(\"$AMOUNT\");";
+    let errors = render_and_check(template, &vars(), &NetworkDefinition::simulator()).err().unwrap();
+    assert_eq!(errors, vec!["instruction 0: swap takes (Bucket), got (Decimal)".to_owned()]);
+
+    // Values which don't fit the manifest syntax fail to compile
+    let mut vars = vars();
+    vars.set("AMOUNT", "ten");
+    let template = "CALL_METHOD ComponentAddress(\"$RADISWAP\") \"set_fee\" Decimal(\"$AMOUNT\");";
+    assert!(render_and_check(template, &vars, &NetworkDefinition::simulator()).is_err());
+}
+
+#[test]
+fn test_unknown_calls_are_rejected() {
+    // A typo in the method name would otherwise only fail once submitted
+    let template = "CALL_METHOD ComponentAddress(\"$RADISWAP\") \"swpa\" Decimal(\"$AMOUNT\");";
+    let errors = render_and_check(template, &vars(), &NetworkDefinition::simulator()).err().unwrap();
+    assert_eq!(errors, vec!["instruction 0: swpa is not a function or method of the package".to_owned()]);
+
+    // Methods of the account are not checked
+    let template = "CALL_METHOD ComponentAddress(\"$ACCOUNT\") \"lock_fee\" Decimal(\"$AMOUNT\");";
+    assert!(render_and_check(template, &vars(), &NetworkDefinition::simulator()).is_ok());
+}